@@ -0,0 +1,20 @@
+-- internal notes admins leave on a booking (call logs, quotes, follow ups)
+CREATE TABLE IF NOT EXISTS main.booking_notes (
+    note_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    booking_id varchar NOT NULL REFERENCES main.booking_requests (booking_id) ON DELETE CASCADE,
+    body text NOT NULL,
+    follow_up_at timestamptz,
+    author varchar NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS booking_notes_booking_id_idx ON main.booking_notes (booking_id);
+
+-- automatic log of everything that happens to a booking
+CREATE TABLE IF NOT EXISTS main.booking_activity (
+    activity_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    booking_id varchar NOT NULL REFERENCES main.booking_requests (booking_id) ON DELETE CASCADE,
+    kind varchar NOT NULL,
+    detail text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS booking_activity_booking_id_idx ON main.booking_activity (booking_id);
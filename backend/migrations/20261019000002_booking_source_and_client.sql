@@ -0,0 +1,8 @@
+-- where an inquiry came from, bookings logged by an admin aren't from the web form
+ALTER TABLE main.booking_requests
+    ADD COLUMN IF NOT EXISTS source varchar NOT NULL DEFAULT 'web_form'
+        CHECK (source IN ('web_form', 'phone', 'in_person', 'instagram', 'referral'));
+
+-- existing client the booking was logged for, if any
+ALTER TABLE main.booking_requests
+    ADD COLUMN IF NOT EXISTS client_id varchar REFERENCES main.clients (client_id);
@@ -0,0 +1,3 @@
+-- cancelled bookings are kept for the record but leave the pending queue
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS cancelled_at timestamptz;
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS cancellation_reason text;
@@ -0,0 +1,14 @@
+-- when and where a booking's session takes place
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS scheduled_at timestamptz;
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS duration_minutes integer;
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS location text;
+
+-- open session times offered to clients, claimed by at most one booking
+CREATE TABLE IF NOT EXISTS main.availability_slots (
+    slot_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    starts_at timestamptz NOT NULL,
+    ends_at timestamptz NOT NULL CHECK (ends_at > starts_at),
+    booking_id varchar UNIQUE REFERENCES main.booking_requests (booking_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS availability_slots_starts_at_idx ON main.availability_slots (starts_at);
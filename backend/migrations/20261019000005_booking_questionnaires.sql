@@ -0,0 +1,9 @@
+-- extra questions the booking form asks for a category, questions is a JSON array of question definitions
+CREATE TABLE IF NOT EXISTS main.booking_questionnaires (
+    category text PRIMARY KEY,
+    questions jsonb NOT NULL DEFAULT '[]'::jsonb,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- answers to those questions, keyed by category then question key
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS answers jsonb NOT NULL DEFAULT '{}'::jsonb;
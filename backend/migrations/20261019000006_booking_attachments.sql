@@ -0,0 +1,11 @@
+-- reference images clients upload with a booking request, the files themselves are stored on disk under attachment_id.
+-- booking_id stays NULL until the booking that uses the upload is submitted
+CREATE TABLE IF NOT EXISTS main.booking_attachments (
+    attachment_id varchar PRIMARY KEY,
+    booking_id varchar REFERENCES main.booking_requests (booking_id) ON DELETE CASCADE,
+    file_name text NOT NULL,
+    content_type text NOT NULL,
+    size_bytes bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS booking_attachments_booking_id_idx ON main.booking_attachments (booking_id);
@@ -0,0 +1,2 @@
+-- set when a new booking looks like one submitted earlier, cleared by merging or dismissing it
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS duplicate_of varchar REFERENCES main.booking_requests (booking_id) ON DELETE SET NULL;
@@ -0,0 +1,17 @@
+-- background work that has to happen at a certain time, polled by the job scheduler so it survives restarts
+CREATE TABLE IF NOT EXISTS main.scheduled_jobs (
+    job_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    kind text NOT NULL,
+    -- what the job is about, e.g. a booking_id
+    reference_id varchar NOT NULL,
+    payload jsonb NOT NULL DEFAULT '{}'::jsonb,
+    run_at timestamptz NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    -- set while a scheduler is working on the job so a crashed run is picked up again later
+    locked_until timestamptz,
+    last_error text,
+    completed_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS scheduled_jobs_due_idx ON main.scheduled_jobs (run_at) WHERE completed_at IS NULL;
+CREATE INDEX IF NOT EXISTS scheduled_jobs_reference_idx ON main.scheduled_jobs (kind, reference_id);
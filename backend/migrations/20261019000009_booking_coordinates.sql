@@ -0,0 +1,3 @@
+-- coordinates of the session location, used to work out the light for the shoot
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS latitude double precision;
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS longitude double precision;
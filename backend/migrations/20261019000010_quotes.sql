@@ -0,0 +1,32 @@
+-- estimates sent to a client before the work is booked in, an accepted quote becomes an invoice
+CREATE TABLE IF NOT EXISTS main.quotes (
+    quote_id varchar PRIMARY KEY,
+    quote_number bigint GENERATED ALWAYS AS IDENTITY,
+    client_id varchar NOT NULL REFERENCES main.clients (client_id),
+    booking_id varchar REFERENCES main.booking_requests (booking_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    -- the client's accept/decline link stops working after this
+    expires_at timestamptz NOT NULL,
+    amount_subtotal numeric NOT NULL DEFAULT 0,
+    amount_tax numeric NOT NULL DEFAULT 0,
+    amount_total numeric GENERATED ALWAYS AS (amount_subtotal + amount_tax) STORED,
+    notes text,
+    -- draft, sent, accepted or declined
+    status text NOT NULL DEFAULT 'draft',
+    sent_at timestamptz,
+    responded_at timestamptz,
+    decline_reason text,
+    -- the invoice the quote turned into once accepted
+    invoice_id varchar REFERENCES main.invoices (invoice_id)
+);
+CREATE INDEX IF NOT EXISTS quotes_booking_id_idx ON main.quotes (booking_id);
+
+-- same shape as invoice_items so lines carry straight over to the invoice
+CREATE TABLE IF NOT EXISTS main.quote_items (
+    quote_item_id varchar PRIMARY KEY,
+    quote_id varchar NOT NULL REFERENCES main.quotes (quote_id) ON DELETE CASCADE,
+    description text NOT NULL,
+    quantity integer NOT NULL,
+    unit_price numeric NOT NULL
+);
+CREATE INDEX IF NOT EXISTS quote_items_quote_id_idx ON main.quote_items (quote_id);
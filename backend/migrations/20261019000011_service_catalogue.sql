@@ -0,0 +1,19 @@
+-- the services and add-ons on the price list, also used as line item presets on invoices and quotes
+CREATE TABLE IF NOT EXISTS main.service_catalogue (
+    service_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    -- package or addon
+    kind text NOT NULL DEFAULT 'package',
+    -- a booking category, NULL for items offered with every category
+    category varchar,
+    name text NOT NULL,
+    description text,
+    hourly_rate numeric,
+    per_photo_fee numeric,
+    -- edited photos that come with the hours before per photo fees apply
+    included_photos integer NOT NULL DEFAULT 0,
+    flat_fee numeric,
+    -- inactive items are hidden from the public price list
+    active boolean NOT NULL DEFAULT true,
+    sort_order integer NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,24 @@
+-- price ranges the public estimate calculator works from, one row per booking category
+CREATE TABLE IF NOT EXISTS main.pricing_rules (
+    category varchar PRIMARY KEY,
+    hourly_min numeric NOT NULL,
+    hourly_max numeric NOT NULL,
+    -- edited photos each hour of shooting comes with
+    photos_per_hour integer NOT NULL,
+    per_photo_min numeric NOT NULL,
+    per_photo_max numeric NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+-- the ranges from the pricing page: $25-$60/hour with 4+ photos, $10-$30 per extra edited photo
+INSERT INTO main.pricing_rules (category, hourly_min, hourly_max, photos_per_hour, per_photo_min, per_photo_max)
+VALUES
+    ('portraiture', 25, 60, 4, 10, 30),
+    ('real_estate', 25, 60, 4, 10, 30),
+    ('automotive', 25, 60, 4, 10, 30),
+    ('event', 25, 60, 4, 10, 30),
+    ('product', 25, 60, 4, 10, 30),
+    ('other', 25, 60, 4, 10, 30)
+ON CONFLICT (category) DO NOTHING;
+
+-- the estimate a client got from the calculator before submitting their booking request
+ALTER TABLE main.booking_requests ADD COLUMN IF NOT EXISTS estimate jsonb;
@@ -0,0 +1,14 @@
+-- invoices start as drafts and can be deleted until they're issued, after that they can only be voided
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = 'main' AND table_name = 'invoices' AND column_name = 'issued_at'
+    ) THEN
+        ALTER TABLE main.invoices ADD COLUMN issued_at timestamptz;
+        -- every invoice made before drafts existed has already gone out
+        UPDATE main.invoices SET issued_at = created_at;
+    END IF;
+END $$;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS voided_at timestamptz;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS void_reason text;
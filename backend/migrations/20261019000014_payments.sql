@@ -0,0 +1,20 @@
+-- every payment received against an invoice, payment_completed and paid_at on invoices are kept in sync from this
+CREATE TABLE IF NOT EXISTS main.payments (
+    payment_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    invoice_id varchar NOT NULL REFERENCES main.invoices (invoice_id),
+    amount numeric NOT NULL CHECK (amount > 0),
+    method varchar,
+    paid_at timestamptz NOT NULL,
+    -- check number, transaction id, etc.
+    reference text,
+    notes text,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS payments_invoice_id_idx ON main.payments (invoice_id);
+
+-- invoices marked paid before the ledger existed get a single payment for their total
+INSERT INTO main.payments (invoice_id, amount, method, paid_at, notes)
+SELECT invoice_id, amount_total, payment_method, COALESCE(paid_at, created_at), 'Recorded before the payments ledger'
+FROM main.invoices i
+WHERE payment_completed AND amount_total > 0
+    AND NOT EXISTS (SELECT 1 FROM main.payments p WHERE p.invoice_id = i.invoice_id);
@@ -0,0 +1,18 @@
+-- hosted checkout pages created for an invoice's balance due
+CREATE TABLE IF NOT EXISTS main.checkout_sessions (
+    session_id varchar PRIMARY KEY,
+    invoice_id varchar NOT NULL REFERENCES main.invoices (invoice_id),
+    amount numeric NOT NULL,
+    url text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    -- set when the payment is recorded, a session only ever pays once
+    completed_at timestamptz
+);
+CREATE INDEX IF NOT EXISTS checkout_sessions_invoice_id_idx ON main.checkout_sessions (invoice_id);
+
+-- webhook events already handled, providers retry events so each one is only applied once
+CREATE TABLE IF NOT EXISTS main.payment_events (
+    event_id varchar PRIMARY KEY,
+    kind text NOT NULL,
+    received_at timestamptz NOT NULL DEFAULT now()
+);
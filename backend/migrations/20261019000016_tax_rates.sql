@@ -0,0 +1,18 @@
+-- sales tax by jurisdiction, state is '' for a rate that covers the whole country
+CREATE TABLE IF NOT EXISTS main.tax_rates (
+    country varchar NOT NULL,
+    state varchar NOT NULL DEFAULT '',
+    -- percent, 8.875 for 8.875%
+    rate numeric NOT NULL CHECK (rate >= 0 AND rate <= 100),
+    name text,
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (country, state)
+);
+
+-- services and prints can be taxed differently, untaxed lines are left out of the tax
+ALTER TABLE main.invoice_items ADD COLUMN IF NOT EXISTS taxable boolean NOT NULL DEFAULT true;
+ALTER TABLE main.quote_items ADD COLUMN IF NOT EXISTS taxable boolean NOT NULL DEFAULT true;
+ALTER TABLE main.service_catalogue ADD COLUMN IF NOT EXISTS taxable boolean NOT NULL DEFAULT true;
+
+-- the rate amount_tax was calculated with, NULL when the tax was entered by hand
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS tax_rate numeric;
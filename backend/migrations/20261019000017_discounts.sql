@@ -0,0 +1,37 @@
+-- reusable coupon codes, uses are counted from the invoices that aren't void
+CREATE TABLE IF NOT EXISTS main.coupons (
+    -- stored upper case, codes are matched case insensitively
+    code varchar PRIMARY KEY,
+    -- percent or fixed
+    discount_kind text NOT NULL,
+    discount_value numeric NOT NULL CHECK (discount_value >= 0),
+    -- NULL for unlimited
+    max_uses integer,
+    expires_at timestamptz,
+    active boolean NOT NULL DEFAULT true,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- a percent or fixed discount off a single line
+ALTER TABLE main.invoice_items ADD COLUMN IF NOT EXISTS discount_kind text;
+ALTER TABLE main.invoice_items ADD COLUMN IF NOT EXISTS discount_value numeric;
+ALTER TABLE main.quote_items ADD COLUMN IF NOT EXISTS discount_kind text;
+ALTER TABLE main.quote_items ADD COLUMN IF NOT EXISTS discount_value numeric;
+
+-- a discount off the whole invoice and the coupon used on it, amount_discount is the two together.
+-- amount_subtotal is after line discounts, the total takes amount_discount off before tax is added
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS discount_kind text;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS discount_value numeric;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS coupon_code varchar;
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = 'main' AND table_name = 'invoices' AND column_name = 'amount_discount'
+    ) THEN
+        ALTER TABLE main.invoices ADD COLUMN amount_discount numeric NOT NULL DEFAULT 0;
+        ALTER TABLE main.invoices DROP COLUMN amount_total;
+        ALTER TABLE main.invoices ADD COLUMN amount_total numeric
+            GENERATED ALWAYS AS (amount_subtotal - amount_discount + amount_tax) STORED;
+    END IF;
+END $$;
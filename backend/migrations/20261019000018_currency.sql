@@ -0,0 +1,4 @@
+-- the ISO 4217 currency an invoice or quote is billed in, everything before this was in dollars
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS currency varchar(3) NOT NULL DEFAULT 'USD';
+ALTER TABLE main.quotes ADD COLUMN IF NOT EXISTS currency varchar(3) NOT NULL DEFAULT 'USD';
+ALTER TABLE main.checkout_sessions ADD COLUMN IF NOT EXISTS currency varchar(3) NOT NULL DEFAULT 'USD';
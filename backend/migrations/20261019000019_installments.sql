@@ -0,0 +1,10 @@
+-- a payment plan for an invoice, such as a retainer, a midpoint payment and a final payment.
+-- what's been paid on each comes from the payments ledger, applied to the earliest due first
+CREATE TABLE IF NOT EXISTS main.installments (
+    installment_id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    invoice_id varchar NOT NULL REFERENCES main.invoices(invoice_id),
+    label text NOT NULL,
+    due_date timestamptz NOT NULL,
+    amount numeric NOT NULL CHECK (amount > 0)
+);
+CREATE INDEX IF NOT EXISTS installments_invoice_id_idx ON main.installments (invoice_id, due_date);
@@ -0,0 +1,9 @@
+-- overdue tracking, set and cleared by the overdue sweep job
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS overdue_at timestamptz;
+-- an admin can stop reminder emails for an invoice, e.g. while a payment arrangement is worked out
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS reminders_paused boolean NOT NULL DEFAULT false;
+-- reminders and late fees since the invoice last went overdue
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS reminders_sent integer NOT NULL DEFAULT 0;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS last_reminder_at timestamptz;
+ALTER TABLE main.invoices ADD COLUMN IF NOT EXISTS late_fees_applied integer NOT NULL DEFAULT 0;
+CREATE INDEX IF NOT EXISTS invoices_overdue_idx ON main.invoices (overdue_at) WHERE overdue_at IS NOT NULL;
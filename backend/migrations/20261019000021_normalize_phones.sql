@@ -0,0 +1,36 @@
+-- phone numbers saved before booking validation are in whatever format the client typed.
+-- find_booking matches on E.164, so bring the old ones over the same way validation::normalize_phone does.
+-- numbers that can't be normalized are left as they were
+CREATE OR REPLACE FUNCTION pg_temp.normalize_phone(raw varchar) RETURNS varchar AS $$
+DECLARE
+    trimmed text := btrim(raw);
+    international boolean := trimmed LIKE '+%' OR trimmed LIKE '00%';
+    digits text;
+    number text;
+BEGIN
+    IF trimmed LIKE '+%' THEN
+        trimmed := substr(trimmed, 2);
+    ELSIF trimmed LIKE '00%' THEN
+        trimmed := substr(trimmed, 3);
+    END IF;
+    IF trimmed !~ '^[0-9 .()-]+$' THEN
+        RETURN raw;
+    END IF;
+    digits := regexp_replace(trimmed, '[^0-9]', '', 'g');
+    IF international OR (length(digits) = 11 AND digits LIKE '1%') THEN
+        number := digits;
+    ELSE
+        number := '1' || digits;
+    END IF;
+    IF length(number) < 8 OR length(number) > 15 OR number LIKE '0%'
+        OR (number LIKE '1%' AND length(number) <> 11) THEN
+        RETURN raw;
+    END IF;
+    RETURN '+' || number;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+UPDATE main.booking_requests SET phone = pg_temp.normalize_phone(phone)
+WHERE phone IS NOT NULL AND phone <> pg_temp.normalize_phone(phone);
+UPDATE main.clients SET phone = pg_temp.normalize_phone(phone)
+WHERE phone IS NOT NULL AND phone <> pg_temp.normalize_phone(phone);
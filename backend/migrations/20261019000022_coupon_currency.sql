@@ -0,0 +1,4 @@
+-- a fixed amount coupon is only good on invoices in its currency, percent coupons leave it NULL.
+-- the fixed coupons made so far were all for the default currency
+ALTER TABLE main.coupons ADD COLUMN IF NOT EXISTS currency varchar;
+UPDATE main.coupons SET currency = 'USD' WHERE discount_kind = 'fixed' AND currency IS NULL;
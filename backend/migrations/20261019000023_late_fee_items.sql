@@ -0,0 +1,5 @@
+-- late fee and interest lines are added on top of the invoice discount and coupon, and interest
+-- isn't charged on them. the sweep has only ever added them untaxed with these descriptions
+ALTER TABLE main.invoice_items ADD COLUMN IF NOT EXISTS late_fee boolean NOT NULL DEFAULT false;
+UPDATE main.invoice_items SET late_fee = true
+WHERE NOT late_fee AND NOT taxable AND (description = 'Late fee' OR description LIKE 'Late fee (%' OR description LIKE 'Interest (%');
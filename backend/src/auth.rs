@@ -1,4 +1,6 @@
 use crate::AppState;
+use crate::client_ip::ClientIp;
+use crate::rate_limit::{RateLimited, RateLimiters};
 use argon2::{
     Argon2,
     password_hash::{
@@ -9,10 +11,8 @@ use axum::Json;
 use axum::extract::{Request, State};
 use axum::http::StatusCode;
 use axum::middleware::Next;
-use axum::response::Response;
-use dotenvy::dotenv;
-use serde::Deserialize;
-use std::env;
+use axum::response::{IntoResponse, Response};
+use std::net::IpAddr;
 use tower_sessions::Session;
 
 #[derive(serde::Deserialize)]
@@ -20,30 +20,38 @@ pub struct LoginInfo {
     username: String,
     password: String,
 }
-#[derive(Debug, Deserialize)]
-pub struct TurnstileResponse {
-    pub(crate) success: bool,
-    #[serde(default)]
-    #[serde(rename = "error-codes")]
-    pub(crate) error_codes: Vec<String>,
-
-    // Optional fields Cloudflare may return
-    #[serde(default)]
-    pub(crate) hostname: Option<String>,
-    #[serde(default)]
-    pub(crate) action: Option<String>,
-    #[serde(default)]
-    pub(crate) cdata: Option<String>,
-    #[serde(default)]
-    pub(crate) challenge_ts: Option<String>,
-}
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::rate_limit::RateLimiters;
+    use std::net::{IpAddr, Ipv4Addr};
+
     #[test]
     //example test
     fn test_password_hashing() {}
+
+    #[test]
+    fn failed_logins_only_lock_out_their_own_address() {
+        let limiters = RateLimiters::from_env();
+        let attacker = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+        let admin = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 20));
+        for _ in 0..5 {
+            assert!(check_login_failures(&limiters, attacker, "admin").is_ok());
+            record_login_result(&limiters, attacker, "admin", false);
+        }
+        assert!(check_login_failures(&limiters, attacker, "admin").is_err());
+        //the admin can still log in from somewhere else
+        assert!(check_login_failures(&limiters, admin, "admin").is_ok());
+
+        //a successful login clears the failures from that address
+        record_login_result(&limiters, admin, "admin", false);
+        record_login_result(&limiters, admin, "admin", true);
+        for _ in 0..4 {
+            record_login_result(&limiters, admin, "admin", false);
+        }
+        assert!(check_login_failures(&limiters, admin, "admin").is_ok());
+    }
 }
 //takes in password and returns the hashed password
 fn hash_password(password: &str) -> Result<String, Error> {
@@ -80,90 +88,62 @@ async fn verify_password(username: &str, password_attempt: &str, state: AppState
         Err(_) => false,
     }
 }
-pub async fn verify_turnstile(
-    token: &str,
-    remote_ip: Option<&str>,
-    expected_action: Option<&str>,
-    expected_hostname: Option<&str>,
-) -> Result<TurnstileResponse, reqwest::Error> {
-    dotenv().expect(".env not found");
-    //set env for production vs dev
-    let secret: String = env::var("TURNSTILE_SECRET_KEY").unwrap();
-    //INITIALIZE DATABASE
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL not found");
-    // Basic sanity checks from docs (token max length 2048)
-    if token.is_empty() || token.len() > 2048 {
-        return Ok(TurnstileResponse {
-            success: false,
-            error_codes: vec!["invalid-input-response".to_string()],
-            hostname: None,
-            action: None,
-            cdata: None,
-            challenge_ts: None,
-        });
-    }
-
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()?;
-
-    // Send as application/x-www-form-urlencoded
-    let mut form = vec![
-        ("secret", secret.to_string()),
-        ("response", token.to_string()),
-    ];
-    if let Some(ip) = remote_ip {
-        form.push(("remoteip", ip.to_string()));
-    }
+//failed attempts are counted per account and address together, so failures from one address
+//can't lock the account out everywhere
+fn login_key(client_ip: IpAddr, username: &str) -> String {
+    format!("{}|{}", client_ip, username)
+}
 
-    let resp = client
-        .post("https://challenges.cloudflare.com/turnstile/v0/siteverify")
-        .form(&form)
-        .send()
-        .await?
-        .error_for_status()? // treat non-2xx as error
-        .json::<TurnstileResponse>()
-        .await?;
+fn check_login_failures(
+    limiters: &RateLimiters,
+    client_ip: IpAddr,
+    username: &str,
+) -> Result<(), RateLimited> {
+    limiters
+        .login_failures
+        .peek(&login_key(client_ip, username))
+}
 
-    // Optional: enforce action/hostname matching your expectations
-    if resp.success {
-        if let (Some(exp), Some(got)) = (expected_action, resp.action.as_deref()) {
-            if exp != got {
-                return Ok(TurnstileResponse {
-                    success: false,
-                    error_codes: vec!["action-mismatch".to_string()],
-                    ..resp
-                });
-            }
-        }
-        if let (Some(exp), Some(got)) = (expected_hostname, resp.hostname.as_deref()) {
-            if exp != got {
-                return Ok(TurnstileResponse {
-                    success: false,
-                    error_codes: vec!["hostname-mismatch".to_string()],
-                    ..resp
-                });
-            }
-        }
+fn record_login_result(limiters: &RateLimiters, client_ip: IpAddr, username: &str, success: bool) {
+    let key = login_key(client_ip, username);
+    if success {
+        limiters.login_failures.reset(&key);
+    } else {
+        limiters.login_failures.record(&key);
     }
-
-    Ok(resp)
 }
+
 #[axum::debug_handler]
 pub async fn login(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     session: Session,
     Json(payload): Json<LoginInfo>,
-) {
+) -> Result<StatusCode, Response> {
     let username = "admin";
     let password = payload.password;
-    match verify_password(&username, &password, state).await {
+    //slow down password guessing from one address or against one account
+    state
+        .rate_limiters
+        .login_per_ip
+        .check(&client_ip.to_string())
+        .map_err(|limited| limited.into_response())?;
+    check_login_failures(&state.rate_limiters, client_ip, username)
+        .map_err(|limited| limited.into_response())?;
+    let rate_limiters = state.rate_limiters.clone();
+    let verified = verify_password(&username, &password, state).await;
+    record_login_result(&rate_limiters, client_ip, username, verified);
+    match verified {
         true => {
             println!("Login successful!");
             session.insert("user_id", username).await.unwrap();
             session.save().await.unwrap();
+            Ok(StatusCode::OK)
+        }
+        false => {
+            println!("Login failed!");
+            Err(StatusCode::UNAUTHORIZED.into_response())
         }
-        false => println!("Login failed!"),
     }
 }
 pub async fn logout(session: Session) -> StatusCode {
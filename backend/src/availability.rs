@@ -0,0 +1,168 @@
+use crate::AppState;
+use crate::invoicing::invoice::ApiResponse;
+use crate::sun::{StudioLocation, sun_times_at};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+//an open session time clients can pick
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AvailabilitySlot {
+    pub(crate) slot_id: i64,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) ends_at: OffsetDateTime,
+}
+
+//future slots that no booking has claimed yet
+pub(crate) async fn open_slots(
+    client: &sqlx::PgPool,
+    starting_after: OffsetDateTime,
+) -> Result<Vec<AvailabilitySlot>, sqlx::Error> {
+    sqlx::query_as!(
+        AvailabilitySlot,
+        r#"SELECT slot_id, starts_at, ends_at FROM main.availability_slots
+        WHERE booking_id IS NULL AND starts_at > $1
+        ORDER BY starts_at"#,
+        starting_after
+    )
+    .fetch_all(client)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct AvailabilityQuery {
+    //where the shoot would be, the studio's location is used when left out
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    timezone: Option<String>,
+    //only return slots that overlap golden hour
+    #[serde(default)]
+    golden_hour_only: bool,
+}
+
+#[derive(Serialize)]
+pub struct SuggestedSlot {
+    #[serde(flatten)]
+    slot: AvailabilitySlot,
+    //None when there's no location to work the light out for
+    golden_hour: Option<bool>,
+}
+
+pub async fn get_availability(
+    State(state): State<AppState>,
+    Query(query): Query<AvailabilityQuery>,
+) -> Result<Json<Vec<SuggestedSlot>>, StatusCode> {
+    let slots = open_slots(&state.db_pool, OffsetDateTime::now_utc())
+        .await
+        .map_err(|e| {
+            println!("Error getting availability: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let location = match (query.latitude, query.longitude) {
+        (Some(latitude), Some(longitude)) => Some(StudioLocation {
+            latitude,
+            longitude,
+            timezone: query
+                .timezone
+                .as_deref()
+                .and_then(|timezone| timezone.parse().ok())
+                .or(state.studio.as_ref().map(|studio| studio.timezone))
+                .unwrap_or(chrono_tz::America::New_York),
+        }),
+        _ => state.studio.clone(),
+    };
+    let suggested = slots
+        .into_iter()
+        .map(|slot| {
+            let golden_hour = location.as_ref().map(|location| {
+                sun_times_at(
+                    slot.starts_at,
+                    location.latitude,
+                    location.longitude,
+                    location.timezone,
+                )
+                .in_golden_hour(slot.starts_at, slot.ends_at)
+            });
+            SuggestedSlot { slot, golden_hour }
+        })
+        .filter(|slot| !query.golden_hour_only || slot.golden_hour == Some(true))
+        .collect();
+    Ok(Json(suggested))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewAvailabilitySlot {
+    #[serde(with = "time::serde::iso8601")]
+    starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    ends_at: OffsetDateTime,
+}
+
+pub async fn create_availability_slot(
+    State(state): State<AppState>,
+    Json(payload): Json<NewAvailabilitySlot>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    if payload.ends_at <= payload.starts_at {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                message: "Slot must end after it starts".to_string(),
+            }),
+        ));
+    }
+    let slot_id = sqlx::query_scalar!(
+        "INSERT INTO main.availability_slots (starts_at, ends_at) VALUES ($1, $2) RETURNING slot_id",
+        payload.starts_at,
+        payload.ends_at,
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error creating availability slot: {}", e),
+            }),
+        )
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: format!("Availability slot {} created", slot_id),
+        }),
+    ))
+}
+
+//only open slots can be removed, a claimed slot belongs to a booking
+pub async fn delete_availability_slot(
+    State(state): State<AppState>,
+    Path(slot_id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let deleted = sqlx::query!(
+        "DELETE FROM main.availability_slots WHERE slot_id = $1 AND booking_id IS NULL",
+        slot_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error deleting availability slot: {}", e),
+            }),
+        )
+    })?;
+    if deleted.rows_affected() == 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                message: "Slot doesn't exist or is already booked".to_string(),
+            }),
+        ));
+    }
+    Ok(StatusCode::OK)
+}
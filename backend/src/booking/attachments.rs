@@ -0,0 +1,326 @@
+use crate::AppState;
+use crate::client_ip::ClientIp;
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::extract::{Multipart, Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use std::path::PathBuf;
+use time::{Duration, OffsetDateTime};
+
+//uploads are kept outside ./server_files/hdr_images so they're never served publicly
+const ATTACHMENT_DIR: &str = "./server_files/booking_attachments";
+pub(crate) const MAX_ATTACHMENTS: usize = 5;
+pub(crate) const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+//uploads that never made it onto a booking are deleted after this long
+const ABANDONED_AFTER: Duration = Duration::hours(24);
+
+//works out the image type from the file's first bytes rather than trusting the upload's headers
+fn sniff_image_type(bytes: &[u8]) -> Option<&'static str> {
+    //ISO media files (HEIC) name their brand after a 4 byte box size
+    let brand = bytes.get(4..12).unwrap_or_default();
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if [&b"ftypheic"[..], b"ftypheix", b"ftypmif1"].contains(&brand) {
+        //photos straight off an iPhone
+        Some("image/heic")
+    } else {
+        None
+    }
+}
+
+//ids are handed to unauthenticated clients, so they're long enough not to be guessed
+fn new_attachment_id() -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                            abcdefghijklmnopqrstuvwxyz\
+                            0123456789";
+    let mut rng = rand::rng();
+    (0..24)
+        .map(|_| CHARSET[rng.random_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+//only the id is used on disk, the client's file name is just stored for display
+fn attachment_path(attachment_id: &str) -> PathBuf {
+    PathBuf::from(ATTACHMENT_DIR).join(attachment_id)
+}
+
+//keeps the last path segment of an uploaded file's name without anything that would break a header
+fn clean_file_name(file_name: Option<&str>) -> String {
+    let file_name: String = file_name
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(200)
+        .collect();
+    let file_name = file_name.trim().to_string();
+    if file_name.is_empty() {
+        "attachment".to_string()
+    } else {
+        file_name
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ApiResponse {
+            message: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BookingAttachment {
+    attachment_id: String,
+    file_name: String,
+    content_type: String,
+    size_bytes: i64,
+    #[serde(with = "time::serde::iso8601")]
+    created_at: OffsetDateTime,
+}
+
+//first step of attaching images to a booking request: the form uploads each image here
+//and sends the returned attachment_ids along with the booking
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<BookingAttachment>), Response> {
+    state
+        .rate_limiters
+        .attachment_per_ip
+        .check(&client_ip.to_string())
+        .map_err(|limited| limited.into_response())?;
+    let field = loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => break field,
+            Ok(Some(_)) => continue,
+            Ok(None) => {
+                return Err(error(StatusCode::BAD_REQUEST, "No file was uploaded"));
+            }
+            Err(e) => return Err(error(e.status(), &e.body_text())),
+        }
+    };
+    let file_name = clean_file_name(field.file_name());
+    let bytes = field
+        .bytes()
+        .await
+        .map_err(|e| error(e.status(), &e.body_text()))?;
+    if bytes.len() > MAX_ATTACHMENT_BYTES {
+        return Err(error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!(
+                "Images must be smaller than {} MB",
+                MAX_ATTACHMENT_BYTES / 1024 / 1024
+            ),
+        ));
+    }
+    let Some(content_type) = sniff_image_type(&bytes) else {
+        return Err(error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only JPEG, PNG, GIF, WebP and HEIC images can be attached",
+        ));
+    };
+
+    let attachment = BookingAttachment {
+        attachment_id: new_attachment_id(),
+        file_name,
+        content_type: content_type.to_string(),
+        size_bytes: bytes.len() as i64,
+        created_at: OffsetDateTime::now_utc(),
+    };
+    let path = attachment_path(&attachment.attachment_id);
+    let saved = async {
+        tokio::fs::create_dir_all(ATTACHMENT_DIR).await?;
+        tokio::fs::write(&path, &bytes).await
+    };
+    saved.await.map_err(|e| {
+        println!("Error saving attachment: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error saving attachment")
+    })?;
+    let inserted = sqlx::query!(
+        r#"INSERT INTO main.booking_attachments (attachment_id, file_name, content_type, size_bytes, created_at)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        attachment.attachment_id,
+        attachment.file_name,
+        attachment.content_type,
+        attachment.size_bytes,
+        attachment.created_at,
+    )
+    .execute(&state.db_pool)
+    .await;
+    if let Err(e) = inserted {
+        println!("Error saving attachment: {}", e);
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error saving attachment",
+        ));
+    }
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+//how many of the uploads are still waiting for a booking
+pub(crate) async fn count_unclaimed<'e>(
+    executor: impl PgExecutor<'e>,
+    attachment_ids: &[String],
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM main.booking_attachments
+        WHERE attachment_id = ANY($1) AND booking_id IS NULL"#,
+        attachment_ids
+    )
+    .fetch_one(executor)
+    .await
+}
+
+//second step, links the uploads to the booking they were sent with
+pub(crate) async fn claim_attachments<'e>(
+    executor: impl PgExecutor<'e>,
+    booking_id: &str,
+    attachment_ids: &[String],
+) -> Result<u64, sqlx::Error> {
+    let claimed = sqlx::query!(
+        r#"UPDATE main.booking_attachments SET booking_id = $1
+        WHERE attachment_id = ANY($2) AND booking_id IS NULL"#,
+        booking_id,
+        attachment_ids
+    )
+    .execute(executor)
+    .await?;
+    Ok(claimed.rows_affected())
+}
+
+//drops a booking's attachment rows ahead of deleting the booking, pass the returned ids to
+//remove_attachment_files once the transaction has committed
+pub(crate) async fn delete_booking_attachments<'e>(
+    executor: impl PgExecutor<'e>,
+    booking_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "DELETE FROM main.booking_attachments WHERE booking_id = $1 RETURNING attachment_id",
+        booking_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub(crate) async fn remove_attachment_files(attachment_ids: &[String]) {
+    for attachment_id in attachment_ids {
+        if let Err(e) = tokio::fs::remove_file(attachment_path(attachment_id)).await {
+            println!("Error removing attachment file {}: {}", attachment_id, e);
+        }
+    }
+}
+
+//deletes uploads that were never submitted with a booking, returns how many were removed
+pub(crate) async fn remove_abandoned_attachments(
+    client: &sqlx::PgPool,
+) -> Result<usize, sqlx::Error> {
+    let removed = sqlx::query_scalar!(
+        r#"DELETE FROM main.booking_attachments
+        WHERE booking_id IS NULL AND created_at < $1
+        RETURNING attachment_id"#,
+        OffsetDateTime::now_utc() - ABANDONED_AFTER
+    )
+    .fetch_all(client)
+    .await?;
+    remove_attachment_files(&removed).await;
+    Ok(removed.len())
+}
+
+//images the client attached to a booking, oldest first
+pub async fn list_booking_attachments(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+) -> Result<Json<Vec<BookingAttachment>>, StatusCode> {
+    let attachments = sqlx::query_as!(
+        BookingAttachment,
+        r#"SELECT attachment_id, file_name, content_type, size_bytes, created_at
+        FROM main.booking_attachments WHERE booking_id = $1 ORDER BY created_at"#,
+        booking_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        println!("Error getting booking attachments: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(attachments))
+}
+
+//the image itself, for the admin booking view
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    Path(attachment_id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let attachment = sqlx::query!(
+        "SELECT file_name, content_type FROM main.booking_attachments WHERE attachment_id = $1",
+        attachment_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| {
+        println!("Error finding attachment: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let bytes = tokio::fs::read(attachment_path(&attachment_id))
+        .await
+        .map_err(|e| {
+            println!("Error reading attachment {}: {}", attachment_id, e);
+            StatusCode::NOT_FOUND
+        })?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}\"", attachment.file_name),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_images_by_content() {
+        assert_eq!(
+            sniff_image_type(&[0xFF, 0xD8, 0xFF, 0xE0, 0, 0]),
+            Some("image/jpeg")
+        );
+        assert_eq!(
+            sniff_image_type(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(
+            sniff_image_type(b"\0\0\0\x18ftypheic\0\0"),
+            Some("image/heic")
+        );
+        //a script renamed to .jpg is still rejected
+        assert_eq!(sniff_image_type(b"<script>alert(1)</script>"), None);
+        assert_eq!(
+            clean_file_name(Some("C:\\Users\\jane\\mood \"board\".png")),
+            "mood board.png"
+        );
+    }
+}
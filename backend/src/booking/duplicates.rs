@@ -0,0 +1,453 @@
+use crate::AppState;
+use crate::booking::attachments::{delete_booking_attachments, remove_attachment_files};
+use crate::booking::reminders::{cancel_reminders, schedule_reminders};
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::booking::{BookingDetails, BookingRequest};
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
+
+//only bookings submitted this recently are compared against a new one
+const DUPLICATE_WINDOW: Duration = Duration::days(30);
+
+//an earlier booking a new one is compared against
+struct DuplicateCandidate {
+    booking_id: String,
+    booking_number: i64,
+    first_name: String,
+    last_name: String,
+    email: Option<String>,
+    phone: Option<String>,
+}
+
+//lowercase letters and digits only, so "O'Brien" and "obrien" compare equal
+fn normalize_name(first_name: &str, last_name: &str) -> String {
+    format!("{}{}", first_name, last_name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+//number of single character edits between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+//catches typos like "Jon Smith" and "John Smith" without matching short unrelated names
+fn names_similar(a: &str, b: &str) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let allowed_edits = if a.chars().count().min(b.chars().count()) >= 8 {
+        2
+    } else {
+        1
+    };
+    a == b || edit_distance(a, b) <= allowed_edits
+}
+
+//why the candidate looks like the same person, if it does
+fn duplicate_reason(
+    booking: &BookingDetails,
+    candidate: &DuplicateCandidate,
+) -> Option<&'static str> {
+    if booking.email.is_some() && booking.email == candidate.email {
+        Some("same email")
+    } else if booking.phone.is_some() && booking.phone == candidate.phone {
+        Some("same phone")
+    } else if names_similar(
+        &normalize_name(&booking.first_name, &booking.last_name),
+        &normalize_name(&candidate.first_name, &candidate.last_name),
+    ) {
+        Some("similar name")
+    } else {
+        None
+    }
+}
+
+//compares a just created booking against recent ones and flags it if it looks like a resubmission
+pub(crate) async fn flag_duplicate(
+    client: &sqlx::PgPool,
+    booking_id: &str,
+    booking: &BookingDetails,
+) -> Result<(), sqlx::Error> {
+    let candidates = sqlx::query_as!(
+        DuplicateCandidate,
+        r#"SELECT booking_id, booking_number, first_name, last_name, email, phone
+        FROM main.booking_requests
+        WHERE booking_id <> $1 AND cancelled_at IS NULL AND created_at > $2
+        ORDER BY created_at DESC"#,
+        booking_id,
+        OffsetDateTime::now_utc() - DUPLICATE_WINDOW,
+    )
+    .fetch_all(client)
+    .await?;
+    let Some((candidate, reason)) = candidates.iter().find_map(|candidate| {
+        duplicate_reason(booking, candidate).map(|reason| (candidate, reason))
+    }) else {
+        return Ok(());
+    };
+    sqlx::query!(
+        "UPDATE main.booking_requests SET duplicate_of = $1 WHERE booking_id = $2",
+        candidate.booking_id,
+        booking_id,
+    )
+    .execute(client)
+    .await?;
+    record_activity(
+        client,
+        booking_id,
+        ActivityKind::DuplicateFlagged,
+        &format!(
+            "Possible duplicate of booking #{} ({})",
+            candidate.booking_number, reason
+        ),
+    )
+    .await
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(ApiResponse { message })).into_response()
+}
+
+//one set of answers per category, the kept booking's answers win
+fn merge_answers(kept: Value, merged: Value) -> Value {
+    match (kept, merged) {
+        (Value::Object(mut kept), Value::Object(merged)) => {
+            for (category, answers) in merged {
+                kept.entry(category).or_insert(answers);
+            }
+            Value::Object(kept)
+        }
+        (kept, _) => kept,
+    }
+}
+
+//combines the newer booking into the older one
+fn merge_into(kept: BookingRequest, merged: BookingRequest) -> BookingRequest {
+    let mut categories = kept.categories.unwrap_or_default();
+    for category in merged.categories.unwrap_or_default() {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    let comments: Vec<String> = [kept.comments, merged.comments]
+        .into_iter()
+        .flatten()
+        .map(|comments| comments.trim().to_string())
+        .filter(|comments| !comments.is_empty())
+        .collect();
+    //coordinates belong to a location, so they're taken together
+    let (location, latitude, longitude) = if kept.location.is_some() {
+        (kept.location, kept.latitude, kept.longitude)
+    } else {
+        (merged.location, merged.latitude, merged.longitude)
+    };
+    //the merged booking is still active if either one was
+    let (cancelled_at, cancellation_reason) =
+        if kept.cancelled_at.is_some() && merged.cancelled_at.is_some() {
+            (kept.cancelled_at, kept.cancellation_reason)
+        } else {
+            (None, None)
+        };
+    BookingRequest {
+        categories: Some(categories),
+        comments: Some(comments.join("\n\n")),
+        email: kept.email.or(merged.email),
+        phone: kept.phone.or(merged.phone),
+        timezone: kept.timezone.or(merged.timezone),
+        client_id: kept.client_id.or(merged.client_id),
+        completed: kept.completed || merged.completed,
+        cancelled_at,
+        cancellation_reason,
+        scheduled_at: kept.scheduled_at.or(merged.scheduled_at),
+        duration_minutes: kept.duration_minutes.or(merged.duration_minutes),
+        location,
+        latitude,
+        longitude,
+        answers: merge_answers(kept.answers, merged.answers),
+        estimate: kept.estimate.or(merged.estimate),
+        duplicate_of: None,
+        ..kept
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BookingMerge {
+    booking_id: String,
+    other_booking_id: String,
+}
+
+//merge two bookings for the same person, the older booking_number is kept
+pub async fn merge_bookings(
+    State(state): State<AppState>,
+    Json(payload): Json<BookingMerge>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    println!(
+        "merging bookings {} and {}",
+        payload.booking_id, payload.other_booking_id
+    );
+    if payload.booking_id == payload.other_booking_id {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Can't merge a booking with itself".to_string(),
+        ));
+    }
+    let internal_error = |e: sqlx::Error| {
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error merging bookings: {}", e),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let mut bookings = sqlx::query_as!(
+        BookingRequest,
+        r#"SELECT * FROM main.booking_requests WHERE booking_id = $1 OR booking_id = $2
+        ORDER BY booking_number FOR UPDATE"#,
+        payload.booking_id,
+        payload.other_booking_id,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if bookings.len() != 2 {
+        return Err(error(
+            StatusCode::NOT_FOUND,
+            "ERROR: Booking_ID could not be found".to_string(),
+        ));
+    }
+    let merged = bookings.pop().unwrap();
+    let kept = bookings.pop().unwrap();
+    let merged_id = merged.booking_id.clone();
+    let merged_number = merged.booking_number;
+    let kept = merge_into(kept, merged);
+
+    sqlx::query!(
+        r#"UPDATE main.booking_requests SET categories = $1, comments = $2, email = $3, phone = $4,
+            timezone = $5, client_id = $6, completed = $7, cancelled_at = $8,
+            cancellation_reason = $9, scheduled_at = $10, duration_minutes = $11,
+            location = $12, latitude = $13, longitude = $14, answers = $15, estimate = $16,
+            duplicate_of = NULL
+        WHERE booking_id = $17"#,
+        kept.categories.as_deref(),
+        kept.comments,
+        kept.email,
+        kept.phone,
+        kept.timezone,
+        kept.client_id,
+        kept.completed,
+        kept.cancelled_at,
+        kept.cancellation_reason,
+        kept.scheduled_at,
+        kept.duration_minutes,
+        kept.location,
+        kept.latitude,
+        kept.longitude,
+        kept.answers,
+        kept.estimate,
+        kept.booking_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    //move everything that belonged to the merged booking
+    sqlx::query!(
+        "UPDATE main.booking_notes SET booking_id = $1 WHERE booking_id = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    sqlx::query!(
+        "UPDATE main.booking_activity SET booking_id = $1 WHERE booking_id = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    sqlx::query!(
+        "UPDATE main.booking_attachments SET booking_id = $1 WHERE booking_id = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    sqlx::query!(
+        "UPDATE main.invoices SET booking_id = $1 WHERE booking_id = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    sqlx::query!(
+        "UPDATE main.quotes SET booking_id = $1 WHERE booking_id = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    //reminders follow the session the kept booking ended up with
+    cancel_reminders(&mut *tx, &merged_id)
+        .await
+        .map_err(internal_error)?;
+    let reminders = match kept.scheduled_at {
+        Some(scheduled_at) if kept.cancelled_at.is_none() => {
+            schedule_reminders(&mut tx, &state.reminders, &kept.booking_id, scheduled_at).await
+        }
+        _ => cancel_reminders(&mut *tx, &kept.booking_id).await,
+    };
+    reminders.map_err(internal_error)?;
+    sqlx::query!(
+        "UPDATE main.booking_requests SET duplicate_of = $1 WHERE duplicate_of = $2",
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    //a booking holds at most one slot, the merged booking's is released if both had one
+    sqlx::query!(
+        r#"UPDATE main.availability_slots SET booking_id = $1 WHERE booking_id = $2
+        AND NOT EXISTS (SELECT 1 FROM main.availability_slots WHERE booking_id = $1)"#,
+        kept.booking_id,
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    //attachments were moved above, anything still on the merged booking goes with it
+    let removed_attachments = delete_booking_attachments(&mut *tx, &merged_id)
+        .await
+        .map_err(internal_error)?;
+    sqlx::query!(
+        "DELETE FROM main.booking_requests WHERE booking_id = $1",
+        merged_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    record_activity(
+        &mut *tx,
+        &kept.booking_id,
+        ActivityKind::Merged,
+        &format!(
+            "Merged booking #{} ({}) into this booking",
+            merged_number, merged_id
+        ),
+    )
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    remove_attachment_files(&removed_attachments).await;
+
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: format!(
+                "Booking #{} merged into booking #{}",
+                merged_number, kept.booking_number
+            ),
+        }),
+    ))
+}
+
+//the admin decided a flagged booking isn't a duplicate after all
+pub async fn dismiss_duplicate(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let updated = sqlx::query!(
+        "UPDATE main.booking_requests SET duplicate_of = NULL WHERE booking_id = $1",
+        booking_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error dismissing duplicate: {}", e),
+            }),
+        )
+    })?;
+    if updated.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "ERROR: Booking_ID could not be found".to_string(),
+            }),
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(first_name: &str, last_name: &str, email: Option<&str>) -> DuplicateCandidate {
+        DuplicateCandidate {
+            booking_id: "aB3dE9".into(),
+            booking_number: 41,
+            first_name: first_name.into(),
+            last_name: last_name.into(),
+            email: email.map(String::from),
+            phone: None,
+        }
+    }
+
+    #[test]
+    fn flags_same_contact_or_similar_name() {
+        let booking = BookingDetails {
+            first_name: "John".into(),
+            last_name: "Smith".into(),
+            phone: Some("+15551234567".into()),
+            email: Some("john@example.com".into()),
+            categories: vec!["portraiture".into()],
+            comments: None,
+            timezone: None,
+            answers: Value::Null,
+        };
+        assert_eq!(
+            duplicate_reason(
+                &booking,
+                &candidate("Johnny", "S", Some("john@example.com"))
+            ),
+            Some("same email")
+        );
+        assert_eq!(
+            duplicate_reason(&booking, &candidate("Jon", "Smith", None)),
+            Some("similar name")
+        );
+        assert_eq!(
+            duplicate_reason(
+                &booking,
+                &candidate("Jane", "Doe", Some("jane@example.com"))
+            ),
+            None
+        );
+        //short names need to be closer to count
+        assert!(!names_similar("alsmi", "edsmi"));
+    }
+}
@@ -0,0 +1,404 @@
+use crate::AppState;
+use crate::booking::BookingDetails;
+use crate::booking::questionnaire::{Questionnaire, load_questionnaires, validate_answers};
+use crate::booking::reminders::{cancel_reminders, schedule_reminders};
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::invoicing::invoice::ApiResponse;
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+fn describe_optional(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("(none)")
+}
+
+//human readable list of what changed, stored on the booking's timeline
+fn describe_changes(old: &BookingDetails, new: &BookingDetails) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.first_name != new.first_name {
+        changes.push(format!(
+            "first name: {} -> {}",
+            old.first_name, new.first_name
+        ));
+    }
+    if old.last_name != new.last_name {
+        changes.push(format!("last name: {} -> {}", old.last_name, new.last_name));
+    }
+    if old.email != new.email {
+        changes.push(format!(
+            "email: {} -> {}",
+            describe_optional(&old.email),
+            describe_optional(&new.email)
+        ));
+    }
+    if old.phone != new.phone {
+        changes.push(format!(
+            "phone: {} -> {}",
+            describe_optional(&old.phone),
+            describe_optional(&new.phone)
+        ));
+    }
+    if old.categories != new.categories {
+        changes.push(format!(
+            "categories: {} -> {}",
+            old.categories.join(", "),
+            new.categories.join(", ")
+        ));
+    }
+    if old.comments != new.comments {
+        changes.push("comments updated".to_string());
+    }
+    if old.timezone != new.timezone {
+        changes.push(format!(
+            "timezone: {} -> {}",
+            describe_optional(&old.timezone),
+            describe_optional(&new.timezone)
+        ));
+    }
+    if old.answers != new.answers {
+        changes.push("questionnaire answers updated".to_string());
+    }
+    changes
+}
+
+//the answers to store after an edit. leaving answers out keeps the stored ones, and only answers
+//that differ from what's stored are validated, so answers to a questionnaire deleted since the
+//booking was made survive an edit
+fn edited_answers(
+    questionnaires: &[Questionnaire],
+    categories: &[String],
+    current: &Value,
+    submitted: &Value,
+    errors: &mut ValidationErrors,
+) -> Value {
+    let Value::Object(submitted) = submitted else {
+        if submitted.is_null() {
+            return current.clone();
+        }
+        errors.add("answers", "Answers must be an object");
+        return current.clone();
+    };
+    let stored =
+        |category: &str, key: &str| current.get(category).and_then(|answers| answers.get(key));
+    //split what was sent into answers that are already stored and ones that changed
+    let mut answers = Map::new();
+    let mut changed = Map::new();
+    for (category, category_answers) in submitted {
+        let Some(category_answers) = category_answers.as_object() else {
+            changed.insert(category.clone(), category_answers.clone());
+            continue;
+        };
+        for (key, answer) in category_answers {
+            let target = if stored(category, key) == Some(answer) {
+                &mut answers
+            } else {
+                &mut changed
+            };
+            target
+                .entry(category.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("category answers are an object")
+                .insert(key.clone(), answer.clone());
+        }
+    }
+    let validated = validate_answers(
+        questionnaires,
+        categories,
+        &Value::Object(changed),
+        false,
+        errors,
+    );
+    if let Value::Object(validated) = validated {
+        for (category, category_answers) in validated {
+            let merged = answers
+                .entry(category)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("category answers are an object");
+            if let Value::Object(category_answers) = category_answers {
+                merged.extend(category_answers);
+            }
+        }
+    }
+    Value::Object(answers)
+}
+
+fn internal_error(message: String) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse { message }),
+    )
+        .into_response()
+}
+
+//fix a booking's contact details, categories or comments
+pub async fn edit_booking(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+    Json(payload): Json<BookingDetails>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    println!("Editing booking: {}", booking_id);
+    let questionnaires = load_questionnaires(&state.db_pool)
+        .await
+        .map_err(|e| internal_error(format!("Error getting questionnaires: {}", e)))?;
+    let details = payload
+        .validate()
+        .map_err(|errors| errors.into_response())?;
+
+    let mut tx =
+        state.db_pool.begin().await.map_err(|e| {
+            internal_error(format!("Error creating postgres transaction pool: {}", e))
+        })?;
+    //lock the row so the change history matches what was actually replaced
+    let current = sqlx::query_as!(
+        BookingDetails,
+        r#"SELECT first_name, last_name, phone, email,
+            COALESCE(categories, '{}') AS "categories!", NULLIF(comments, '') AS comments, timezone, answers
+        FROM main.booking_requests WHERE booking_id = $1 FOR UPDATE"#,
+        booking_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| internal_error(format!("Error finding booking: {}", e)))?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "ERROR: Booking_ID could not be found".to_string(),
+            }),
+        )
+            .into_response()
+    })?;
+
+    let mut errors = ValidationErrors::new();
+    let answers = edited_answers(
+        &questionnaires,
+        &details.categories,
+        &current.answers,
+        &details.answers,
+        &mut errors,
+    );
+    let details = errors
+        .finish(BookingDetails { answers, ..details })
+        .map_err(|errors| errors.into_response())?;
+
+    let changes = describe_changes(&current, &details);
+    if changes.is_empty() {
+        return Ok((
+            StatusCode::OK,
+            Json(ApiResponse {
+                message: "Nothing to update".to_string(),
+            }),
+        ));
+    }
+
+    sqlx::query!(
+        r#"UPDATE main.booking_requests SET first_name = $1, last_name = $2, phone = $3,
+        email = $4, categories = $5, comments = $6, timezone = $7, answers = $8
+        WHERE booking_id = $9"#,
+        details.first_name,
+        details.last_name,
+        details.phone,
+        details.email,
+        &details.categories,
+        details.comments.clone().unwrap_or("".to_string()),
+        details.timezone,
+        details.answers,
+        booking_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| internal_error(format!("Error editing booking: {}", e)))?;
+    record_activity(
+        &mut *tx,
+        &booking_id,
+        ActivityKind::Edited,
+        &format!("Edited {}", changes.join("; ")),
+    )
+    .await
+    .map_err(|e| internal_error(format!("Error recording booking changes: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| internal_error(format!("Error editing booking in database: {}", e)))?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Booking Successfully Updated".to_string(),
+        }),
+    ))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BookingCancel {
+    //false restores a cancelled booking to the pending queue
+    cancelled: bool,
+    reason: Option<String>,
+}
+
+//archive a booking without deleting it, or bring it back
+pub async fn change_cancellation_status(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+    Json(payload): Json<BookingCancel>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    println!("changing booking cancellation status");
+    let reason = payload
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    let mut tx =
+        state.db_pool.begin().await.map_err(|e| {
+            internal_error(format!("Error creating postgres transaction pool: {}", e))
+        })?;
+    let updated = sqlx::query_scalar!(
+        r#"UPDATE main.booking_requests SET
+            cancelled_at = CASE WHEN $1 THEN COALESCE(cancelled_at, $2) ELSE NULL END,
+            cancellation_reason = CASE WHEN $1 THEN $3 ELSE NULL END
+        WHERE booking_id = $4
+        RETURNING scheduled_at"#,
+        payload.cancelled,
+        OffsetDateTime::now_utc(),
+        reason,
+        booking_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| internal_error(format!("Error changing cancellation status: {}", e)))?;
+    let Some(scheduled_at) = updated else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "ERROR: Booking_ID could not be found".to_string(),
+            }),
+        )
+            .into_response());
+    };
+    //no reminders for a cancelled session, a restored one gets them back
+    let reminders = match scheduled_at {
+        Some(scheduled_at) if !payload.cancelled => {
+            schedule_reminders(&mut tx, &state.reminders, &booking_id, scheduled_at).await
+        }
+        _ => cancel_reminders(&mut *tx, &booking_id).await,
+    };
+    reminders.map_err(|e| internal_error(format!("Error updating reminders: {}", e)))?;
+
+    let (kind, detail) = if payload.cancelled {
+        (
+            ActivityKind::Cancelled,
+            match &reason {
+                Some(reason) => format!("Cancelled: {}", reason),
+                None => "Cancelled".to_string(),
+            },
+        )
+    } else {
+        (ActivityKind::Restored, "Restored to pending".to_string())
+    };
+    record_activity(&mut *tx, &booking_id, kind, &detail)
+        .await
+        .map_err(|e| internal_error(format!("Error recording booking activity: {}", e)))?;
+    tx.commit()
+        .await
+        .map_err(|e| internal_error(format!("Error changing cancellation status: {}", e)))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: if payload.cancelled {
+                "Booking cancelled".to_string()
+            } else {
+                "Booking restored".to_string()
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_only_changed_fields() {
+        let old = BookingDetails {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            phone: None,
+            email: Some("jane@exmaple.com".into()),
+            categories: vec!["portraiture".into()],
+            comments: None,
+            timezone: None,
+            answers: serde_json::Value::Null,
+        };
+        let new = BookingDetails {
+            email: Some("jane@example.com".into()),
+            ..old.clone()
+        };
+        assert_eq!(
+            describe_changes(&old, &new),
+            vec!["email: jane@exmaple.com -> jane@example.com".to_string()]
+        );
+    }
+
+    fn portrait_questionnaire() -> Vec<Questionnaire> {
+        serde_json::from_value(serde_json::json!([{
+            "category": "portraiture",
+            "questions": [{"key": "people", "label": "How many people?", "kind": "number"}]
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_stored_answers_when_an_edit_leaves_them_out() {
+        //the edit page doesn't send answers
+        let payload: BookingDetails = serde_json::from_value(serde_json::json!({
+            "first_name": "Jane",
+            "last_name": "Doe",
+            "phone": null,
+            "email": "jane@example.com",
+            "categories": ["portraiture", "event"],
+            "comments": null,
+            "timezone": null
+        }))
+        .unwrap();
+        //the event questionnaire was deleted after the booking was made
+        let stored = serde_json::json!({
+            "portraiture": {"people": 3},
+            "event": {"venue": "The Barn"}
+        });
+        let categories = payload.categories.clone();
+        let mut errors = ValidationErrors::new();
+        let answers = edited_answers(
+            &portrait_questionnaire(),
+            &categories,
+            &stored,
+            &payload.answers,
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(answers, stored);
+
+        //sending them back with one change only validates the change
+        let mut errors = ValidationErrors::new();
+        let answers = edited_answers(
+            &portrait_questionnaire(),
+            &categories,
+            &stored,
+            &serde_json::json!({
+                "portraiture": {"people": "4"},
+                "event": {"venue": "The Barn"}
+            }),
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            answers,
+            serde_json::json!({"portraiture": {"people": 4}, "event": {"venue": "The Barn"}})
+        );
+    }
+}
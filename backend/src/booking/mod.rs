@@ -0,0 +1,1023 @@
+pub mod attachments;
+pub mod duplicates;
+pub mod edit;
+pub mod questionnaire;
+pub mod reminders;
+pub mod schedule;
+pub mod timeline;
+
+use crate::{AppState, booking};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Json, debug_handler};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::booking::attachments::{MAX_ATTACHMENTS, claim_attachments, count_unclaimed};
+use crate::booking::questionnaire::{
+    Questionnaire, answer_summary, load_questionnaires, validate_answers,
+};
+use crate::booking::schedule::client_timezone;
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::client_ip::ClientIp;
+use crate::clientele::Client;
+use crate::email::{Mailer, OutgoingEmail};
+use crate::invoicing::invoice::ApiResponse;
+use crate::pricing::{self, EstimateRequest};
+use crate::sun::{SunTimes, sun_times_at};
+use crate::validation::{ValidationErrors, is_valid_email, is_valid_timezone, normalize_phone};
+use axum::response::{IntoResponse, Response};
+use sqlx::PgExecutor;
+use time::OffsetDateTime;
+//
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct Category {
+    value: String,
+    label: String,
+}
+#[derive(Serialize, Deserialize)]
+pub struct IncomingBookingRequest {
+    first_name: String,
+    last_name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    categories: Vec<Category>,
+    comments: Option<String>,
+    timezone: Option<String>,
+    //answers to the selected categories' questionnaires, {category: {question_key: answer}}
+    #[serde(default)]
+    answers: serde_json::Value,
+    //ids returned by /booking/attachments for images uploaded with the form
+    #[serde(default)]
+    attachments: Vec<String>,
+    //what they picked in the price calculator, priced again here before it's saved
+    #[serde(default)]
+    estimate: Option<EstimateRequest>,
+    //token from whichever captcha widget CAPTCHA_PROVIDER is set to
+    #[serde(alias = "captcha_token")]
+    turnstile_token: String,
+}
+//action the booking form's captcha widget is rendered with
+const BOOKING_CAPTCHA_ACTION: &str = "booking";
+
+//categories the booking form offers, (value, label)
+pub(crate) const BOOKING_CATEGORIES: [(&str, &str); 6] = [
+    ("portraiture", "Portraiture"),
+    ("real_estate", "Real Estate"),
+    ("automotive", "Automotive"),
+    ("event", "Event"),
+    ("product", "Product"),
+    ("other", "Other"),
+];
+
+pub(crate) fn category_label(value: &str) -> &str {
+    BOOKING_CATEGORIES
+        .iter()
+        .find(|(category, _)| *category == value)
+        .map(|(_, label)| *label)
+        .unwrap_or(value)
+}
+
+//the contact details and request info of a booking, normalized before they're stored
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct BookingDetails {
+    pub(crate) first_name: String,
+    pub(crate) last_name: String,
+    pub(crate) phone: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) categories: Vec<String>,
+    pub(crate) comments: Option<String>,
+    pub(crate) timezone: Option<String>,
+    #[serde(default)]
+    pub(crate) answers: serde_json::Value,
+}
+
+impl From<IncomingBookingRequest> for BookingDetails {
+    fn from(request: IncomingBookingRequest) -> Self {
+        BookingDetails {
+            first_name: request.first_name,
+            last_name: request.last_name,
+            phone: request.phone,
+            email: request.email,
+            // Extract just the values from categories array
+            categories: request
+                .categories
+                .into_iter()
+                .map(|category| category.value)
+                .collect(),
+            comments: request.comments,
+            timezone: request.timezone,
+            answers: request.answers,
+        }
+    }
+}
+
+impl BookingDetails {
+    //trims and normalizes every field, collecting all problems instead of stopping at the first
+    pub(crate) fn validate(self) -> Result<BookingDetails, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        //treat blank optional fields as missing
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let first_name = self.first_name.trim().to_string();
+        if first_name.is_empty() {
+            errors.add("first_name", "First name is required");
+        } else if first_name.chars().count() > 100 {
+            errors.add("first_name", "First name must be less than 100 characters");
+        }
+        let last_name = self.last_name.trim().to_string();
+        if last_name.is_empty() {
+            errors.add("last_name", "Last name is required");
+        } else if last_name.chars().count() > 100 {
+            errors.add("last_name", "Last name must be less than 100 characters");
+        }
+
+        let email = non_empty(self.email).map(|email| email.to_lowercase());
+        if let Some(email) = &email
+            && !is_valid_email(email)
+        {
+            errors.add("email", "Invalid email address");
+        }
+        let mut phone = non_empty(self.phone);
+        if let Some(raw_phone) = &phone {
+            match normalize_phone(raw_phone) {
+                Some(normalized) => phone = Some(normalized),
+                None => errors.add("phone", "Invalid phone number"),
+            }
+        }
+        if email.is_none() && phone.is_none() {
+            errors.add(
+                "email",
+                "You must provide either a phone number or an email",
+            );
+        }
+
+        let mut categories: Vec<String> = Vec::new();
+        for category in self.categories {
+            if !BOOKING_CATEGORIES
+                .iter()
+                .any(|(value, _)| *value == category)
+            {
+                errors.add("categories", &format!("Unknown category: {}", category));
+            } else if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+        if categories.is_empty() {
+            errors.add("categories", "Please select at least one category");
+        }
+
+        let comments = non_empty(self.comments);
+        if let Some(comments) = &comments
+            && comments.chars().count() > 3000
+        {
+            errors.add("comments", "Comments must be less than 3000 characters");
+        }
+
+        let timezone = non_empty(self.timezone);
+        if let Some(timezone) = &timezone
+            && !is_valid_timezone(timezone)
+        {
+            errors.add("timezone", "Unknown timezone");
+        }
+
+        errors.finish(BookingDetails {
+            first_name,
+            last_name,
+            phone,
+            email,
+            categories,
+            comments,
+            timezone,
+            answers: self.answers,
+        })
+    }
+
+    //checks the questionnaire answers once the rest of the details are valid.
+    //require_answers is false for admins, who may not know every answer yet
+    pub(crate) fn validate_answers(
+        self,
+        questionnaires: &[Questionnaire],
+        require_answers: bool,
+    ) -> Result<BookingDetails, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let answers = validate_answers(
+            questionnaires,
+            &self.categories,
+            &self.answers,
+            require_answers,
+            &mut errors,
+        );
+        errors.finish(BookingDetails { answers, ..self })
+    }
+}
+
+//how an inquiry reached us
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BookingSource {
+    WebForm,
+    Phone,
+    InPerson,
+    Instagram,
+    Referral,
+}
+
+impl BookingSource {
+    //value stored in booking_requests.source
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            BookingSource::WebForm => "web_form",
+            BookingSource::Phone => "phone",
+            BookingSource::InPerson => "in_person",
+            BookingSource::Instagram => "instagram",
+            BookingSource::Referral => "referral",
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            BookingSource::WebForm => "web form",
+            BookingSource::Phone => "phone",
+            BookingSource::InPerson => "in person",
+            BookingSource::Instagram => "Instagram",
+            BookingSource::Referral => "referral",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BookingRequest {
+    first_name: String,
+    last_name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    booking_id: String,
+    categories: Option<Vec<String>>,
+    comments: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    created_at: OffsetDateTime,
+    completed: bool,
+    booking_number: i64,
+    timezone: Option<String>,
+    //one of the BookingSource values
+    source: String,
+    client_id: Option<String>,
+    #[serde(with = "time::serde::iso8601::option")]
+    cancelled_at: Option<OffsetDateTime>,
+    cancellation_reason: Option<String>,
+    #[serde(with = "time::serde::iso8601::option")]
+    scheduled_at: Option<OffsetDateTime>,
+    duration_minutes: Option<i32>,
+    location: Option<String>,
+    answers: serde_json::Value,
+    //an earlier booking this one looks like a resubmission of
+    duplicate_of: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    //the price calculator estimate the client submitted with the request
+    estimate: Option<serde_json::Value>,
+}
+
+//generates a random 6-character string
+//and checks if it's unique against all ID types in the database
+pub(crate) async fn generate_id(client: &sqlx::PgPool) -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                            abcdefghijklmnopqrstuvwxyz\
+                            0123456789";
+
+    loop {
+        let new_id: String = {
+            let mut rng = rand::rng();
+            (0..6)
+                .map(|_| {
+                    let idx = rng.random_range(0..CHARSET.len());
+                    CHARSET[idx] as char
+                })
+                .collect()
+        };
+        //make sure id isn't already in use by an invoice, booking request, client, or quote
+        let invoice_test = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM main.invoices  WHERE invoice_id = $1
+                UNION ALL
+                SELECT 1 FROM main.booking_requests WHERE booking_id = $1
+                UNION ALL
+                SELECT 1 FROM main.clients WHERE client_id = $1
+                UNION ALL
+                SELECT 1 FROM main.quotes WHERE quote_id = $1
+            ) AS "exists!"
+            "#,
+            new_id
+        )
+        .fetch_one(client)
+        .await;
+        if !invoice_test.unwrap().exists {
+            return new_id;
+        }
+    }
+}
+
+#[axum::debug_handler]
+pub async fn create_booking_request(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    Json(payload): Json<IncomingBookingRequest>,
+) -> Result<(StatusCode, Json<String>), Response> {
+    //get client_id from the database
+    println!("creating booking request");
+    state
+        .rate_limiters
+        .booking_per_ip
+        .check(&client_ip.to_string())
+        .map_err(|limited| limited.into_response())?;
+    let client = state.db_pool;
+    //VERIFY CAPTCHA TOKEN
+    let captcha = state
+        .captcha
+        .verify(&payload.turnstile_token, client_ip, BOOKING_CAPTCHA_ACTION)
+        .await
+        .map_err(|e| {
+            println!("Error verifying captcha token: {}", e);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json("COULD NOT VERIFY CAPTCHA TOKEN".to_string()),
+            )
+                .into_response()
+        })?;
+    if !captcha.success {
+        println!(
+            "captcha token failed verification: {:?}",
+            captcha.error_codes
+        );
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json("FAILED TO VERIFY CAPTCHA TOKEN".to_string()),
+        )
+            .into_response());
+    }
+    //VALIDATE AND NORMALIZE SUBMISSION
+    let estimate_request = payload.estimate.clone();
+    let mut attachment_ids = payload.attachments.clone();
+    attachment_ids.sort();
+    attachment_ids.dedup();
+    if attachment_ids.len() > MAX_ATTACHMENTS {
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "attachments",
+            &format!("You can attach at most {} images", MAX_ATTACHMENTS),
+        );
+        return Err(errors.into_response());
+    }
+    let details = BookingDetails::from(payload)
+        .validate()
+        .map_err(|errors| errors.into_response())?;
+    let questionnaires = load_questionnaires(&client).await.map_err(|e| {
+        println!("Error getting questionnaires: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("ERROR: Booking request Not Created!".to_string()),
+        )
+            .into_response()
+    })?;
+    let details = details
+        .validate_answers(&questionnaires, true)
+        .map_err(|errors| errors.into_response())?;
+    let estimate = match &estimate_request {
+        Some(request) => Some(pricing::estimate(&client, request).await?),
+        None => None,
+    };
+    let not_created = |e: sqlx::Error| {
+        println!("Error creating booking request: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("ERROR: Booking request Not Created!".to_string()),
+        )
+            .into_response()
+    };
+    let attachments_expired = || {
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "attachments",
+            "Some images have expired, please upload them again",
+        );
+        errors.into_response()
+    };
+    //the booking and its attachments are saved together, a booking is never left without
+    //images the client was told were saved
+    let mut tx = client.begin().await.map_err(not_created)?;
+    if !attachment_ids.is_empty() {
+        let unclaimed = count_unclaimed(&mut *tx, &attachment_ids)
+            .await
+            .map_err(not_created)?;
+        if unclaimed != attachment_ids.len() as i64 {
+            return Err(attachments_expired());
+        }
+    }
+    if let Some(email) = &details.email {
+        state
+            .rate_limiters
+            .booking_per_email
+            .check(email)
+            .map_err(|limited| limited.into_response())?;
+    }
+    let new_booking_id = generate_id(&client).await;
+
+    //create new booking request in database
+    let booking_number = insert_booking(
+        &mut *tx,
+        &new_booking_id,
+        &details,
+        BookingSource::WebForm,
+        None,
+    )
+    .await
+    .map_err(not_created)?;
+    if !attachment_ids.is_empty() {
+        //an upload claimed by another booking since the check above
+        let claimed = claim_attachments(&mut *tx, &new_booking_id, &attachment_ids)
+            .await
+            .map_err(not_created)?;
+        if claimed != attachment_ids.len() as u64 {
+            return Err(attachments_expired());
+        }
+    }
+    let create_booking = tx.commit().await.map(|_| booking_number);
+    match create_booking {
+        Ok(booking_number) => {
+            println!("booking request created successfully!");
+            if let Some(estimate) = &estimate {
+                pricing::attach_estimate(&client, &new_booking_id, estimate)
+                    .await
+                    .unwrap_or_else(|e| println!("Error saving booking estimate: {}", e));
+            }
+            record_activity(
+                &client,
+                &new_booking_id,
+                ActivityKind::Created,
+                "Booking request submitted through the booking form",
+            )
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+            duplicates::flag_duplicate(&client, &new_booking_id, &details)
+                .await
+                .unwrap_or_else(|e| println!("Error checking for duplicate bookings: {}", e));
+            //let the admin and client know without holding up the response
+            let mailer = state.mailer.clone();
+            tokio::spawn(async move {
+                send_booking_emails(
+                    &mailer,
+                    &client,
+                    &new_booking_id,
+                    &details,
+                    &questionnaires,
+                    booking_number,
+                )
+                .await;
+            });
+            Ok((
+                StatusCode::CREATED,
+                Json("Booking request created!".to_string()),
+            ))
+        }
+        Err(e) => {
+            println!("Error creating booking request: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("ERROR: Booking request Not Created!".to_string()),
+            )
+                .into_response())
+        }
+    }
+}
+
+//saves a validated booking and returns its booking_number
+async fn insert_booking<'e>(
+    executor: impl PgExecutor<'e>,
+    booking_id: &str,
+    details: &BookingDetails,
+    source: BookingSource,
+    client_id: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    let current_utc = OffsetDateTime::now_utc();
+    sqlx::query_scalar!(
+                "INSERT INTO main.booking_requests (booking_id, created_at, first_name, last_name, phone, email, categories, comments, timezone, completed, source, client_id, answers) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) RETURNING booking_number",
+                booking_id,
+                current_utc,
+                details.first_name,
+                details.last_name,
+                details.phone,
+                details.email,
+                &details.categories,
+                details.comments.clone().unwrap_or("".to_string()),
+                details.timezone,
+                false,
+                source.as_str(),
+                client_id,
+                details.answers,
+    )
+        .fetch_one(executor)
+        .await
+}
+
+//a booking logged by an admin for an inquiry that didn't come through the booking form
+#[derive(Serialize, Deserialize)]
+pub struct AdminBookingRequest {
+    //name, email and phone can be left out when linking an existing client
+    first_name: Option<String>,
+    last_name: Option<String>,
+    phone: Option<String>,
+    email: Option<String>,
+    categories: Vec<String>,
+    comments: Option<String>,
+    timezone: Option<String>,
+    #[serde(default)]
+    answers: serde_json::Value,
+    source: BookingSource,
+    client_id: Option<String>,
+}
+
+//create a booking directly from the admin panel, no captcha or rate limits
+pub async fn admin_create_booking(
+    State(state): State<AppState>,
+    Json(payload): Json<AdminBookingRequest>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let client = &state.db_pool;
+    println!("admin creating booking");
+    //fill in anything left blank from the linked client
+    let mut details = BookingDetails {
+        first_name: payload.first_name.unwrap_or_default(),
+        last_name: payload.last_name.unwrap_or_default(),
+        phone: payload.phone,
+        email: payload.email,
+        categories: payload.categories,
+        comments: payload.comments,
+        timezone: payload.timezone,
+        answers: payload.answers,
+    };
+    if let Some(client_id) = &payload.client_id {
+        let linked_client = sqlx::query_as!(
+            Client,
+            "SELECT * FROM main.clients WHERE client_id = $1",
+            client_id
+        )
+        .fetch_optional(client)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    message: format!("Error finding client: {}", e),
+                }),
+            )
+                .into_response()
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    message: "ERROR: Client_ID could not be found".to_string(),
+                }),
+            )
+                .into_response()
+        })?;
+        if details.first_name.trim().is_empty() {
+            details.first_name = linked_client.first_name;
+        }
+        if details.last_name.trim().is_empty() {
+            details.last_name = linked_client.last_name;
+        }
+        details.email = details.email.or(Some(linked_client.email));
+        details.phone = details.phone.or(linked_client.phone);
+        details.timezone = details.timezone.or(linked_client.timezone);
+    }
+    let questionnaires = load_questionnaires(client).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error getting questionnaires: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    let details = details
+        .validate()
+        .and_then(|details| details.validate_answers(&questionnaires, false))
+        .map_err(|errors| errors.into_response())?;
+
+    let new_booking_id = generate_id(client).await;
+    let booking_number = insert_booking(
+        client,
+        &new_booking_id,
+        &details,
+        payload.source,
+        payload.client_id.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        println!("Error creating booking request: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: "ERROR: Booking request Not Created!".to_string(),
+            }),
+        )
+            .into_response()
+    })?;
+    record_activity(
+        client,
+        &new_booking_id,
+        ActivityKind::Created,
+        &format!("Logged by admin ({})", payload.source.label()),
+    )
+    .await
+    .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+    duplicates::flag_duplicate(client, &new_booking_id, &details)
+        .await
+        .unwrap_or_else(|e| println!("Error checking for duplicate bookings: {}", e));
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: format!(
+                "Booking #{} created with Booking_ID: {}",
+                booking_number, new_booking_id
+            ),
+        }),
+    ))
+}
+
+//plain text summary of what the client submitted, shared by both emails
+fn booking_summary(
+    booking: &BookingDetails,
+    questionnaires: &[Questionnaire],
+    booking_number: i64,
+) -> String {
+    let categories: Vec<&str> = booking
+        .categories
+        .iter()
+        .map(|category| category_label(category))
+        .collect();
+    format!(
+        "Booking request #{}\n\
+        Name: {} {}\n\
+        Email: {}\n\
+        Phone: {}\n\
+        Categories: {}\n\
+        Timezone: {}\n\
+        \n\
+        {}\
+        Comments:\n{}\n",
+        booking_number,
+        booking.first_name,
+        booking.last_name,
+        booking.email.as_deref().unwrap_or("-"),
+        booking.phone.as_deref().unwrap_or("-"),
+        categories.join(", "),
+        booking.timezone.as_deref().unwrap_or("-"),
+        answer_summary(questionnaires, &booking.answers),
+        booking.comments.as_deref().unwrap_or(""),
+    )
+}
+
+//notify the admin of a new booking request and send the client an acknowledgement
+async fn send_booking_emails(
+    mailer: &Mailer,
+    client: &sqlx::PgPool,
+    booking_id: &str,
+    booking: &BookingDetails,
+    questionnaires: &[Questionnaire],
+    booking_number: i64,
+) {
+    let summary = booking_summary(booking, questionnaires, booking_number);
+    let mut emails = Vec::new();
+    if let Some(admin) = mailer.admin_address() {
+        emails.push(OutgoingEmail {
+            to: admin,
+            reply_to: booking.email.clone(),
+            subject: format!(
+                "New booking request #{} from {} {}",
+                booking_number, booking.first_name, booking.last_name
+            ),
+            body: summary.clone(),
+        });
+    }
+    if let Some(client_email) = booking.email.clone() {
+        emails.push(OutgoingEmail {
+            to: client_email,
+            reply_to: mailer.admin_address(),
+            subject: "We received your booking request".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                Thanks for reaching out! We received your booking request and will get back to you soon.\n\
+                Here is a copy of what you sent us:\n\n{}",
+                booking.first_name, summary
+            ),
+        });
+    }
+    for email in emails {
+        send_booking_email(mailer, client, booking_id, email).await;
+    }
+}
+
+//sends an email about a booking and logs it to the booking's timeline
+pub(crate) async fn send_booking_email(
+    mailer: &Mailer,
+    client: &sqlx::PgPool,
+    booking_id: &str,
+    email: OutgoingEmail,
+) {
+    let detail = format!("\"{}\" sent to {}", email.subject, email.to);
+    match mailer.send(email).await {
+        Ok(()) => record_activity(client, booking_id, ActivityKind::EmailSent, &detail)
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e)),
+        Err(e) => println!("Error sending booking email: {}", e),
+    }
+}
+pub async fn get_pending_bookings(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BookingRequest>>, StatusCode> {
+    let client = state.db_pool;
+
+    let pending_bookings = sqlx::query_as!(
+        BookingRequest,
+        "SELECT * FROM main.booking_requests WHERE NOT completed AND cancelled_at IS NULL ORDER BY created_at;"
+    )
+    .fetch_all(&client)
+    .await;
+    match pending_bookings {
+        Ok(bookings) => Ok(Json(bookings)),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn booking_exists(
+    booking_id: &str,
+    database: &sqlx::PgPool,
+) -> Result<bool, sqlx::Error> {
+    //TODO CHECK IF new_client IS ALREADY IN DATABASE
+    let booking_exists = sqlx::query_scalar!(
+        "SELECT EXISTS (SELECT 1 FROM main.booking_requests WHERE booking_id = $1) ",
+        booking_id
+    )
+    .fetch_one(database)
+    .await
+    .expect("DB error");
+
+    Ok(booking_exists.unwrap())
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BookingComplete {
+    completed: bool,
+}
+//Mark a booking as completed in the database
+pub async fn change_completion_status(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+    Json(payload): Json<BookingComplete>,
+) -> Result<(StatusCode, Json<String>), (StatusCode, Json<String>)> {
+    let client = &state.db_pool;
+    println!("changing booking completion status");
+    //change booking completion status in database
+    let change_completion_status = sqlx::query!(
+        r#"UPDATE main.booking_requests SET completed = $1
+        WHERE booking_id=$2
+        "#,
+        &payload.completed,
+        &booking_id
+    )
+    .execute(client)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error changing  completion status: {}", e).to_string(),
+            }),
+        )
+    });
+    if change_completion_status.is_ok() {
+        let detail = if payload.completed {
+            "Marked as completed"
+        } else {
+            "Marked as pending"
+        };
+        record_activity(client, &booking_id, ActivityKind::StatusChanged, detail)
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+    }
+    Ok((
+        StatusCode::OK,
+        Json("Booking marked as completed!".to_string()),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FindBookingQuery {
+    first_name: Option<String>,
+    last_name: Option<String>,
+    name: Option<String>,
+    year: Option<String>,
+    month: Option<String>,
+    booking_number: Option<String>,
+    booking_id: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+}
+#[derive(Serialize, Deserialize, sqlx::FromRow, Debug)]
+pub struct FoundBooking {
+    booking_number: i64,
+    booking_id: String,
+}
+pub(crate) async fn find_booking(
+    State(state): State<AppState>,
+    Query(q): Query<FindBookingQuery>,
+) -> Result<Json<Vec<FoundBooking>>, StatusCode> {
+    let client = &state.db_pool;
+    println!("-----FINDING YOUR BOOKING REQUEST!!!-----");
+    println!("{:?}", q.email);
+    //phone numbers are stored in E.164, so search with the same format
+    let phone = q
+        .phone
+        .map(|phone| normalize_phone(&phone).unwrap_or(phone));
+    let mut booking_number: Option<i64> = None;
+    //CONVERT INVOICE NUMBER STRING TO NUMBER
+    if (q.booking_number.is_some()) {
+        booking_number = q.booking_number.unwrap_or("".to_string()).parse().ok();
+    }
+    let mut month: Option<i32> = None;
+    //CONVERT MONTH STRING TO NUMBER
+    if (q.month.is_some()) {
+        month = q.month.unwrap_or("".to_string()).parse().ok();
+    }
+    let mut year: Option<i32> = None;
+    //CONVERT YEAR NUMBER STRING TO NUMBER
+    if (q.year.is_some()) {
+        year = q.year.unwrap_or("".to_string()).parse().ok();
+    }
+
+    // Check if all inputs are None before querying
+    let all_none = q.first_name.is_none()
+        && q.last_name.is_none()
+        && q.email.is_none()
+        && phone.is_none()
+        && booking_number.is_none()
+        && q.booking_id.is_none()
+        && year.is_none()
+        && month.is_none();
+    if all_none {
+        println!("ALL INPUTS ARE NONE!");
+        return Ok(Json(vec![])); // Return early without hitting the DB
+    }
+    let find_bookings = sqlx::query_as!(
+        FoundBooking,
+        r#"SELECT booking_id, booking_number FROM main.booking_requests
+        WHERE ($1::varchar IS NULL OR first_name ILIKE $1::varchar)
+        AND ($2::varchar IS NULL OR email ILIKE $2::varchar)
+        AND ($3::varchar IS NULL OR phone = $3::varchar)
+        AND ($4::varchar IS NULL OR last_name ILIKE $4::varchar)
+        AND ($5::bigint IS NULL OR booking_number = $5::bigint)
+        AND ($6::varchar IS NULL OR booking_id ILIKE $6::varchar)
+        AND ($7::integer IS NULL OR EXTRACT(YEAR FROM created_at) = $7::integer)
+        AND (($8::integer IS NULL OR $7::integer IS NULL) OR EXTRACT(MONTH FROM created_at) = $8::integer)
+       "#,
+        q.first_name,
+        q.email,
+        phone,
+         q.last_name,
+        booking_number,
+        q.booking_id,
+        year,
+        month,
+    )
+        .fetch_all(client)
+        .await.map_err(|e| {
+        println!("Error finding booking: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+    /*for booking in find_bookings.iter().clone() {
+        println!("FOUND Booking: {:?}", booking);
+    }*/
+    let found_bookings = Json(find_bookings);
+    Ok(found_bookings)
+}
+
+//a booking along with the light on the day of its session
+#[derive(Serialize)]
+pub struct BookingView {
+    #[serde(flatten)]
+    booking: BookingRequest,
+    //only when the booking is scheduled at a location with coordinates
+    sun: Option<SunTimes>,
+}
+
+pub async fn view_booking(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+) -> Result<Json<BookingView>, StatusCode> {
+    let client = state.db_pool;
+
+    let booking_request = sqlx::query_as!(
+        BookingRequest,
+        "SELECT * FROM main.booking_requests WHERE booking_id = $1",
+        booking_id
+    )
+    .fetch_one(&client)
+    .await;
+    match booking_request {
+        Ok(booking) => {
+            let sun = match (booking.scheduled_at, booking.latitude, booking.longitude) {
+                (Some(scheduled_at), Some(latitude), Some(longitude)) => Some(sun_times_at(
+                    scheduled_at,
+                    latitude,
+                    longitude,
+                    client_timezone(booking.timezone.as_deref()),
+                )),
+                _ => None,
+            };
+            Ok(Json(BookingView { booking, sun }))
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captcha::Captcha;
+    use crate::captcha::mock::MockCaptchaVerifier;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+
+    fn booking_request(email: &str) -> IncomingBookingRequest {
+        IncomingBookingRequest {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            phone: None,
+            email: Some(email.into()),
+            categories: vec![Category {
+                value: "portraiture".into(),
+                label: "Portraiture".into(),
+            }],
+            comments: Some("Family portraits in the park".into()),
+            timezone: Some("America/New_York".into()),
+            answers: serde_json::Value::Null,
+            attachments: Vec::new(),
+            estimate: None,
+            turnstile_token: "test-token".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_booking_when_captcha_fails() {
+        let verifier = Arc::new(MockCaptchaVerifier::new(false));
+        let state = AppState::for_tests(Captcha::new(verifier.clone(), None, 0.5));
+        let client_ip: IpAddr = "198.51.100.1".parse().unwrap();
+
+        let response = create_booking_request(
+            State(state),
+            ClientIp(client_ip),
+            Json(booking_request("jane@example.com")),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let checks = verifier.checks.lock().unwrap();
+        assert_eq!(
+            checks[0],
+            (
+                "test-token".to_string(),
+                Some(client_ip),
+                Some(BOOKING_CAPTCHA_ACTION.to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_booking_after_captcha_passes() {
+        let state = AppState::for_tests(Captcha::new(
+            Arc::new(MockCaptchaVerifier::new(true)),
+            None,
+            0.5,
+        ));
+
+        let response = create_booking_request(
+            State(state),
+            ClientIp("198.51.100.1".parse().unwrap()),
+            Json(booking_request("not-an-email")),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
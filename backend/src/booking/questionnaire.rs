@@ -0,0 +1,430 @@
+use crate::AppState;
+use crate::booking::{BOOKING_CATEGORIES, category_label};
+use crate::invoicing::invoice::ApiResponse;
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json as JsonColumn;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum QuestionKind {
+    //single line answer
+    Text,
+    LongText,
+    Number,
+    YesNo,
+    //one of the options
+    Choice,
+    //any number of the options
+    MultiChoice,
+}
+
+//one question the booking form asks for a category
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Question {
+    //answers are stored under this key, so it shouldn't change once bookings use it
+    pub(crate) key: String,
+    pub(crate) label: String,
+    pub(crate) kind: QuestionKind,
+    #[serde(default)]
+    pub(crate) required: bool,
+    //only used by choice questions
+    #[serde(default)]
+    pub(crate) options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Questionnaire {
+    pub(crate) category: String,
+    pub(crate) questions: Vec<Question>,
+}
+
+pub(crate) async fn load_questionnaires(
+    client: &sqlx::PgPool,
+) -> Result<Vec<Questionnaire>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT category, questions AS "questions: JsonColumn<Vec<Question>>"
+        FROM main.booking_questionnaires ORDER BY category"#
+    )
+    .fetch_all(client)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Questionnaire {
+            category: row.category,
+            questions: row.questions.0,
+        })
+        .collect())
+}
+
+//checks a single answer against its question, returning the value to store
+//Ok(None) means the question was left blank
+fn validate_answer(question: &Question, answer: &Value) -> Result<Option<Value>, String> {
+    let answer = match answer {
+        Value::Null => return Ok(None),
+        Value::String(text) if text.trim().is_empty() => return Ok(None),
+        Value::Array(values) if values.is_empty() => return Ok(None),
+        answer => answer,
+    };
+    match question.kind {
+        QuestionKind::Text | QuestionKind::LongText => {
+            let max_length = if question.kind == QuestionKind::Text {
+                300
+            } else {
+                3000
+            };
+            let Value::String(text) = answer else {
+                return Err("Answer must be text".to_string());
+            };
+            let text = text.trim();
+            if text.chars().count() > max_length {
+                return Err(format!(
+                    "Answer must be less than {} characters",
+                    max_length
+                ));
+            }
+            Ok(Some(Value::String(text.to_string())))
+        }
+        QuestionKind::Number => {
+            //form inputs often send numbers as strings
+            let number = match answer {
+                Value::Number(number) => number.as_f64(),
+                Value::String(text) => text.trim().parse::<f64>().ok(),
+                _ => None,
+            };
+            match number {
+                //keep whole numbers like a guest count as integers
+                Some(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                    Ok(Some(Value::from(number as i64)))
+                }
+                Some(number) => serde_json::Number::from_f64(number)
+                    .map(|number| Some(Value::Number(number)))
+                    .ok_or_else(|| "Answer must be a number".to_string()),
+                None => Err("Answer must be a number".to_string()),
+            }
+        }
+        QuestionKind::YesNo => match answer {
+            Value::Bool(yes) => Ok(Some(Value::Bool(*yes))),
+            _ => Err("Answer must be yes or no".to_string()),
+        },
+        QuestionKind::Choice => match answer {
+            Value::String(choice) if question.options.contains(choice) => {
+                Ok(Some(Value::String(choice.clone())))
+            }
+            _ => Err("Please pick one of the options".to_string()),
+        },
+        QuestionKind::MultiChoice => {
+            let Value::Array(values) = answer else {
+                return Err("Please pick from the options".to_string());
+            };
+            let mut choices: Vec<Value> = Vec::new();
+            for value in values {
+                match value {
+                    Value::String(choice) if question.options.contains(choice) => {
+                        if !choices.contains(value) {
+                            choices.push(value.clone());
+                        }
+                    }
+                    _ => return Err("Please pick from the options".to_string()),
+                }
+            }
+            Ok(Some(Value::Array(choices)))
+        }
+    }
+}
+
+//validates the answers for the booking's categories, keyed by category then question key.
+//answers to questions that don't exist are dropped so an old form can still submit
+pub(crate) fn validate_answers(
+    questionnaires: &[Questionnaire],
+    categories: &[String],
+    answers: &Value,
+    require_answers: bool,
+    errors: &mut ValidationErrors,
+) -> Value {
+    let empty = Map::new();
+    let submitted = match answers {
+        Value::Object(submitted) => submitted,
+        Value::Null => &empty,
+        _ => {
+            errors.add("answers", "Answers must be an object");
+            &empty
+        }
+    };
+    let mut validated = Map::new();
+    for questionnaire in questionnaires
+        .iter()
+        .filter(|questionnaire| categories.contains(&questionnaire.category))
+    {
+        let category_answers = submitted
+            .get(&questionnaire.category)
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        let mut category_validated = Map::new();
+        for question in &questionnaire.questions {
+            let field = format!("answers.{}.{}", questionnaire.category, question.key);
+            let answer = category_answers.get(&question.key).unwrap_or(&Value::Null);
+            match validate_answer(question, answer) {
+                Ok(Some(answer)) => {
+                    category_validated.insert(question.key.clone(), answer);
+                }
+                Ok(None) if question.required && require_answers => {
+                    errors.add(&field, "This question is required")
+                }
+                Ok(None) => {}
+                Err(message) => errors.add(&field, &message),
+            }
+        }
+        if !category_validated.is_empty() {
+            validated.insert(
+                questionnaire.category.clone(),
+                Value::Object(category_validated),
+            );
+        }
+    }
+    Value::Object(validated)
+}
+
+fn describe_answer(answer: &Value) -> String {
+    match answer {
+        Value::String(text) => text.clone(),
+        Value::Bool(true) => "Yes".to_string(),
+        Value::Bool(false) => "No".to_string(),
+        Value::Array(values) => values
+            .iter()
+            .map(describe_answer)
+            .collect::<Vec<String>>()
+            .join(", "),
+        answer => answer.to_string(),
+    }
+}
+
+//plain text "label: answer" lines for emails, grouped by category
+pub(crate) fn answer_summary(questionnaires: &[Questionnaire], answers: &Value) -> String {
+    let mut summary = String::new();
+    for questionnaire in questionnaires {
+        let Some(category_answers) = answers
+            .get(&questionnaire.category)
+            .and_then(Value::as_object)
+        else {
+            continue;
+        };
+        summary.push_str(&format!("{}:\n", category_label(&questionnaire.category)));
+        for question in &questionnaire.questions {
+            if let Some(answer) = category_answers.get(&question.key) {
+                summary.push_str(&format!(
+                    "  {}: {}\n",
+                    question.label,
+                    describe_answer(answer)
+                ));
+            }
+        }
+    }
+    //blank line before whatever follows
+    if !summary.is_empty() {
+        summary.push('\n');
+    }
+    summary
+}
+
+//checks a questionnaire an admin is saving
+fn validate_questions(questions: Vec<Question>) -> Result<Vec<Question>, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    let mut validated: Vec<Question> = Vec::new();
+    for (index, question) in questions.into_iter().enumerate() {
+        let field = format!("questions.{}", index);
+        let key = question.key.trim().to_string();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            errors.add(
+                &field,
+                "Key must be lowercase letters, numbers and underscores",
+            );
+        } else if validated.iter().any(|existing| existing.key == key) {
+            errors.add(&field, &format!("Duplicate key: {}", key));
+        }
+        let label = question.label.trim().to_string();
+        if label.is_empty() {
+            errors.add(&field, "Label is required");
+        }
+        let mut options: Vec<String> = Vec::new();
+        for option in question.options {
+            let option = option.trim().to_string();
+            if !option.is_empty() && !options.contains(&option) {
+                options.push(option);
+            }
+        }
+        match question.kind {
+            QuestionKind::Choice | QuestionKind::MultiChoice if options.is_empty() => {
+                errors.add(&field, "Choice questions need at least one option")
+            }
+            QuestionKind::Choice | QuestionKind::MultiChoice => {}
+            _ => options.clear(),
+        }
+        validated.push(Question {
+            key,
+            label,
+            kind: question.kind,
+            required: question.required,
+            options,
+        });
+    }
+    errors.finish(validated)
+}
+
+//questionnaires for every category, the booking form uses this to render its extra questions
+pub async fn get_questionnaires(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Questionnaire>>, StatusCode> {
+    let questionnaires = load_questionnaires(&state.db_pool).await.map_err(|e| {
+        println!("Error getting questionnaires: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(questionnaires))
+}
+
+//replace the questions asked for a category
+pub async fn save_questionnaire(
+    State(state): State<AppState>,
+    Path(category): Path<String>,
+    Json(payload): Json<Vec<Question>>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    if !BOOKING_CATEGORIES
+        .iter()
+        .any(|(value, _)| *value == category)
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: format!("Unknown category: {}", category),
+            }),
+        )
+            .into_response());
+    }
+    let questions = validate_questions(payload).map_err(|errors| errors.into_response())?;
+    sqlx::query!(
+        r#"INSERT INTO main.booking_questionnaires (category, questions, updated_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (category) DO UPDATE SET questions = $2, updated_at = $3"#,
+        category,
+        JsonColumn(&questions) as _,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error saving questionnaire: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: format!("Questionnaire for {} saved", category_label(&category)),
+        }),
+    ))
+}
+
+//stop asking extra questions for a category, answers already on bookings are kept
+pub async fn delete_questionnaire(
+    State(state): State<AppState>,
+    Path(category): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    sqlx::query!(
+        "DELETE FROM main.booking_questionnaires WHERE category = $1",
+        category
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error deleting questionnaire: {}", e),
+            }),
+        )
+    })?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn real_estate() -> Vec<Questionnaire> {
+        vec![Questionnaire {
+            category: "real_estate".into(),
+            questions: vec![
+                Question {
+                    key: "square_feet".into(),
+                    label: "Property size (sq ft)".into(),
+                    kind: QuestionKind::Number,
+                    required: true,
+                    options: vec![],
+                },
+                Question {
+                    key: "access".into(),
+                    label: "How do we get in?".into(),
+                    kind: QuestionKind::Choice,
+                    required: false,
+                    options: vec!["Lockbox".into(), "Agent on site".into()],
+                },
+            ],
+        }]
+    }
+
+    #[test]
+    fn validates_answers_for_selected_categories() {
+        let categories = vec!["real_estate".to_string()];
+        let mut errors = ValidationErrors::new();
+        let answers = validate_answers(
+            &real_estate(),
+            &categories,
+            &json!({"real_estate": {"square_feet": "2400", "access": "Lockbox", "pets": "no"}}),
+            true,
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        //numbers are converted and unknown questions dropped
+        assert_eq!(
+            answers,
+            json!({"real_estate": {"square_feet": 2400, "access": "Lockbox"}})
+        );
+
+        let mut errors = ValidationErrors::new();
+        validate_answers(
+            &real_estate(),
+            &categories,
+            &json!({"real_estate": {"access": "Front door"}}),
+            true,
+            &mut errors,
+        );
+        let fields: Vec<String> = serde_json::to_value(&errors).unwrap()["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["field"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "answers.real_estate.square_feet",
+                "answers.real_estate.access"
+            ]
+        );
+    }
+}
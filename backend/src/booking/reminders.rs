@@ -0,0 +1,264 @@
+use crate::AppState;
+use crate::booking::category_label;
+use crate::booking::schedule::{local_time, self_service_link};
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::email::OutgoingEmail;
+use crate::jobs::{JobKind, cancel_jobs, schedule_job};
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use std::env;
+use time::{Duration, OffsetDateTime};
+
+//when reminders go out before a session
+#[derive(Clone)]
+pub(crate) struct ReminderConfig {
+    pub(crate) offsets: Vec<Duration>,
+}
+
+impl ReminderConfig {
+    //REMINDER_OFFSETS_HOURS is a comma separated list like "72,24", empty disables reminders
+    pub(crate) fn from_env() -> Self {
+        let offsets = env::var("REMINDER_OFFSETS_HOURS").unwrap_or_else(|_| "72,24".into());
+        ReminderConfig {
+            offsets: offsets
+                .split(',')
+                .map(str::trim)
+                .filter(|hours| !hours.is_empty())
+                .map(|hours| {
+                    Duration::hours(
+                        hours
+                            .parse()
+                            .expect("REMINDER_OFFSETS_HOURS must be a list of hours"),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+//what the reminder job needs to know, the session time guards against sending a reminder for an old time
+#[derive(Serialize, Deserialize)]
+struct ReminderPayload {
+    #[serde(with = "time::serde::iso8601")]
+    scheduled_at: OffsetDateTime,
+}
+
+//replaces a booking's pending reminders with ones for its new session time
+pub(crate) async fn schedule_reminders(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    config: &ReminderConfig,
+    booking_id: &str,
+    scheduled_at: OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    cancel_jobs(&mut **tx, JobKind::SessionReminder, booking_id).await?;
+    let now = OffsetDateTime::now_utc();
+    for offset in &config.offsets {
+        let run_at = scheduled_at - *offset;
+        //a reminder that would already be late is skipped
+        if run_at <= now {
+            continue;
+        }
+        let payload = serde_json::to_value(ReminderPayload { scheduled_at })
+            .expect("reminder payload serializes");
+        schedule_job(
+            &mut **tx,
+            JobKind::SessionReminder,
+            booking_id,
+            run_at,
+            payload,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn cancel_reminders<'e>(
+    executor: impl PgExecutor<'e>,
+    booking_id: &str,
+) -> Result<(), sqlx::Error> {
+    cancel_jobs(executor, JobKind::SessionReminder, booking_id).await
+}
+
+//what to tell clients to prepare for each category
+fn preparation_tips(category: &str) -> &'static str {
+    match category {
+        "portraiture" => {
+            "Bring an outfit change or two, solid colors photograph best. Get a good night's sleep and stay hydrated."
+        }
+        "real_estate" => {
+            "Turn on every light, open the blinds, clear countertops and put away personal items. Park cars away from the front of the house."
+        }
+        "automotive" => {
+            "Wash and detail the vehicle the day before, and remove anything from the dash, seats and cup holders."
+        }
+        "event" => {
+            "Send over a schedule of the day and a list of must-have shots and people. Let us know who our point of contact is."
+        }
+        "product" => {
+            "Make sure products are clean, unpackaged and free of dust or fingerprints. Bring any props or brand colors you'd like used."
+        }
+        _ => "Let us know if there's anything specific you'd like us to bring or plan for.",
+    }
+}
+
+//"tomorrow", "in 3 days", "in 5 hours"
+fn time_until(scheduled_at: OffsetDateTime, now: OffsetDateTime) -> String {
+    let until = scheduled_at - now;
+    match until.whole_days() {
+        0 => match until.whole_hours() {
+            ..=1 => "in 1 hour".to_string(),
+            hours => format!("in {} hours", hours),
+        },
+        1 => "tomorrow".to_string(),
+        days => format!("in {} days", days),
+    }
+}
+
+struct ReminderBooking {
+    first_name: String,
+    email: Option<String>,
+    phone: Option<String>,
+    timezone: Option<String>,
+    categories: Option<Vec<String>>,
+    scheduled_at: Option<OffsetDateTime>,
+    duration_minutes: Option<i32>,
+    location: Option<String>,
+    cancelled_at: Option<OffsetDateTime>,
+}
+
+//run by the job scheduler, an Err retries the job later
+pub(crate) async fn send_session_reminder(
+    state: &AppState,
+    booking_id: &str,
+    payload: &serde_json::Value,
+) -> Result<(), String> {
+    let payload: ReminderPayload =
+        serde_json::from_value(payload.clone()).map_err(|e| e.to_string())?;
+    let booking = sqlx::query_as!(
+        ReminderBooking,
+        r#"SELECT first_name, email, phone, timezone, categories, scheduled_at,
+            duration_minutes, location, cancelled_at
+        FROM main.booking_requests WHERE booking_id = $1"#,
+        booking_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let now = OffsetDateTime::now_utc();
+    //the booking was merged, cancelled or moved since this reminder was queued
+    let Some(booking) = booking.filter(|booking| {
+        booking.cancelled_at.is_none() && booking.scheduled_at == Some(payload.scheduled_at)
+    }) else {
+        println!("skipping outdated reminder for booking {}", booking_id);
+        return Ok(());
+    };
+    //a job that ran late after downtime, the session has already started
+    if payload.scheduled_at <= now {
+        println!(
+            "skipping reminder for past session of booking {}",
+            booking_id
+        );
+        return Ok(());
+    }
+
+    let timezone = booking.timezone.as_deref();
+    let when = local_time(payload.scheduled_at, timezone);
+    let until = time_until(payload.scheduled_at, now);
+    let location = booking
+        .location
+        .as_deref()
+        .unwrap_or("we'll confirm the location with you");
+    let categories = booking.categories.unwrap_or_default();
+
+    if let Some(email) = booking.email {
+        let mut tips = String::new();
+        for category in &categories {
+            tips.push_str(&format!(
+                "- {}: {}\n",
+                category_label(category),
+                preparation_tips(category)
+            ));
+        }
+        let reminder = OutgoingEmail {
+            to: email,
+            reply_to: state.mailer.admin_address(),
+            subject: format!("Reminder: your session is {}", until),
+            body: format!(
+                "Hi {},\n\n\
+                Just a reminder that your session is {}, on {}.\n\
+                Location: {}\n\
+                Length: {} minutes\n\n\
+                How to prepare:\n{}\n\
+                Need to reschedule or cancel? Use this link (changes close {} hours before the session):\n{}\n",
+                booking.first_name,
+                until,
+                when,
+                location,
+                booking.duration_minutes.unwrap_or(60),
+                tips,
+                state.self_service.change_window.whole_hours(),
+                self_service_link(state, booking_id, payload.scheduled_at),
+            ),
+        };
+        let detail = format!("\"{}\" sent to {}", reminder.subject, reminder.to);
+        state
+            .mailer
+            .send(reminder)
+            .await
+            .map_err(|e| e.to_string())?;
+        record_activity(&state.db_pool, booking_id, ActivityKind::EmailSent, &detail)
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+    }
+
+    //SMS is a courtesy on top of the email, a failure here doesn't resend the email
+    if let Some(phone) = booking.phone.filter(|_| state.sms.is_enabled()) {
+        let text = format!(
+            "Hi {}, reminder: your photo session is {} ({}) at {}.",
+            booking.first_name, until, when, location
+        );
+        match state.sms.send(&phone, &text).await {
+            Ok(()) => record_activity(
+                &state.db_pool,
+                booking_id,
+                ActivityKind::SmsSent,
+                &format!("Reminder text sent to {}", phone),
+            )
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e)),
+            Err(e) => println!("Error sending reminder SMS: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn describes_time_until_session() {
+        let session = datetime!(2026-03-14 15:00 UTC);
+        assert_eq!(
+            time_until(session, datetime!(2026-03-11 15:00 UTC)),
+            "in 3 days"
+        );
+        assert_eq!(
+            time_until(session, datetime!(2026-03-13 15:00 UTC)),
+            "tomorrow"
+        );
+        assert_eq!(
+            time_until(session, datetime!(2026-03-14 10:00 UTC)),
+            "in 5 hours"
+        );
+        assert_eq!(
+            time_until(session, datetime!(2026-03-14 13:30 UTC)),
+            "in 1 hour"
+        );
+        assert_eq!(
+            time_until(session, datetime!(2026-03-14 14:40 UTC)),
+            "in 1 hour"
+        );
+    }
+}
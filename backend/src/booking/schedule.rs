@@ -0,0 +1,734 @@
+use crate::AppState;
+use crate::availability::{AvailabilitySlot, open_slots};
+use crate::booking::reminders::{cancel_reminders, schedule_reminders};
+use crate::booking::send_booking_email;
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::email::OutgoingEmail;
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use std::env;
+use time::{Duration, OffsetDateTime};
+
+//purpose the self-service link tokens are signed for
+const SELF_SERVICE_LINK: &str = "booking_self_service";
+
+//settings for the links clients use to manage their own session
+#[derive(Clone)]
+pub(crate) struct SelfServiceConfig {
+    //frontend base url the links point at
+    pub(crate) site_url: String,
+    //clients can't reschedule or cancel once the session is closer than this
+    pub(crate) change_window: Duration,
+}
+
+impl SelfServiceConfig {
+    pub(crate) fn from_env() -> Self {
+        let hours: i64 = env::var("SELF_SERVICE_CHANGE_WINDOW_HOURS")
+            .ok()
+            .map(|hours| {
+                hours
+                    .parse()
+                    .expect("SELF_SERVICE_CHANGE_WINDOW_HOURS must be a number")
+            })
+            .unwrap_or(48);
+        SelfServiceConfig {
+            site_url: env::var("PUBLIC_SITE_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".into())
+                .trim_end_matches('/')
+                .to_string(),
+            change_window: Duration::hours(hours),
+        }
+    }
+}
+
+//the client's IANA timezone, eastern time when it's missing or unknown
+pub(crate) fn client_timezone(timezone: Option<&str>) -> Tz {
+    timezone
+        .and_then(|timezone| timezone.parse().ok())
+        .unwrap_or(chrono_tz::America::New_York)
+}
+
+//formats a time in the client's timezone, "Saturday, March 14 2026 at 3:30 PM EDT"
+pub(crate) fn local_time(at: OffsetDateTime, timezone: Option<&str>) -> String {
+    let timezone = client_timezone(timezone);
+    DateTime::from_timestamp(at.unix_timestamp(), 0)
+        .expect("invalid timestamp")
+        .with_timezone(&timezone)
+        .format("%A, %B %-d %Y at %-I:%M %p %Z")
+        .to_string()
+}
+
+//last moment a client can still reschedule or cancel themselves
+fn change_deadline(scheduled_at: OffsetDateTime, config: &SelfServiceConfig) -> OffsetDateTime {
+    scheduled_at - config.change_window
+}
+
+//a client can only move to a slot they'd still be able to change afterwards
+fn earliest_self_service_slot(now: OffsetDateTime, config: &SelfServiceConfig) -> OffsetDateTime {
+    now + config.change_window
+}
+
+pub(crate) fn self_service_link(
+    state: &AppState,
+    booking_id: &str,
+    scheduled_at: OffsetDateTime,
+) -> String {
+    //stays valid for a week after the session so the client can still look it up
+    let token = state.link_signer.sign(
+        SELF_SERVICE_LINK,
+        booking_id,
+        scheduled_at + Duration::days(7),
+    );
+    format!("{}/booking/manage/{}", state.self_service.site_url, token)
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ApiResponse {
+            message: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+//what the scheduling handlers need to know about a booking
+struct ScheduledBooking {
+    booking_id: String,
+    booking_number: i64,
+    first_name: String,
+    last_name: String,
+    email: Option<String>,
+    timezone: Option<String>,
+    categories: Option<Vec<String>>,
+    scheduled_at: Option<OffsetDateTime>,
+    duration_minutes: Option<i32>,
+    location: Option<String>,
+    cancelled_at: Option<OffsetDateTime>,
+}
+
+async fn find_scheduled_booking(
+    client: &sqlx::PgPool,
+    booking_id: &str,
+) -> Result<ScheduledBooking, Response> {
+    sqlx::query_as!(
+        ScheduledBooking,
+        r#"SELECT booking_id, booking_number, first_name, last_name, email, timezone, categories,
+            scheduled_at, duration_minutes, location, cancelled_at
+        FROM main.booking_requests WHERE booking_id = $1"#,
+        booking_id
+    )
+    .fetch_optional(client)
+    .await
+    .map_err(|e| {
+        println!("Error finding booking: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error finding booking")
+    })?
+    .ok_or_else(|| {
+        error(
+            StatusCode::NOT_FOUND,
+            "ERROR: Booking_ID could not be found",
+        )
+    })
+}
+
+//gives up whatever slot the booking holds and claims a new one starting after the given time,
+//returning its times
+async fn move_to_slot(
+    tx: &mut Transaction<'_, Postgres>,
+    booking_id: &str,
+    slot_id: i64,
+    starting_after: OffsetDateTime,
+) -> Result<(OffsetDateTime, OffsetDateTime), Response> {
+    release_slot(tx, booking_id).await?;
+    let slot = sqlx::query!(
+        r#"UPDATE main.availability_slots SET booking_id = $1
+        WHERE slot_id = $2 AND booking_id IS NULL AND starts_at > $3
+        RETURNING starts_at, ends_at"#,
+        booking_id,
+        slot_id,
+        starting_after,
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| {
+        println!("Error claiming availability slot: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error claiming slot")
+    })?
+    .ok_or_else(|| error(StatusCode::CONFLICT, "That time is no longer available"))?;
+    Ok((slot.starts_at, slot.ends_at))
+}
+
+async fn release_slot(
+    tx: &mut Transaction<'_, Postgres>,
+    booking_id: &str,
+) -> Result<(), Response> {
+    sqlx::query!(
+        "UPDATE main.availability_slots SET booking_id = NULL WHERE booking_id = $1",
+        booking_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        println!("Error releasing availability slot: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error releasing slot")
+    })?;
+    Ok(())
+}
+
+async fn save_schedule(
+    tx: &mut Transaction<'_, Postgres>,
+    booking_id: &str,
+    scheduled_at: OffsetDateTime,
+    duration_minutes: i32,
+    location: Option<&str>,
+    coordinates: Option<(f64, f64)>,
+) -> Result<(), Response> {
+    //a new location replaces the old coordinates too, even if it doesn't come with any
+    sqlx::query!(
+        r#"UPDATE main.booking_requests SET scheduled_at = $1, duration_minutes = $2,
+        location = COALESCE($3, location),
+        latitude = CASE WHEN $3 IS NULL THEN latitude ELSE $4 END,
+        longitude = CASE WHEN $3 IS NULL THEN longitude ELSE $5 END
+        WHERE booking_id = $6"#,
+        scheduled_at,
+        duration_minutes,
+        location,
+        coordinates.map(|(latitude, _)| latitude),
+        coordinates.map(|(_, longitude)| longitude),
+        booking_id,
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| {
+        println!("Error scheduling booking: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error scheduling booking",
+        )
+    })?;
+    Ok(())
+}
+
+//email the client their session time along with the link to manage it
+fn send_schedule_confirmation(
+    state: &AppState,
+    booking: &ScheduledBooking,
+    scheduled_at: OffsetDateTime,
+    location: Option<String>,
+) {
+    let Some(email) = booking.email.clone() else {
+        return;
+    };
+    let link = self_service_link(state, &booking.booking_id, scheduled_at);
+    let confirmation = OutgoingEmail {
+        to: email,
+        reply_to: state.mailer.admin_address(),
+        subject: format!(
+            "Your session is booked for {}",
+            local_time(scheduled_at, booking.timezone.as_deref())
+        ),
+        body: format!(
+            "Hi {},\n\n\
+            Your session (booking #{}) is scheduled for {}.\n\
+            Location: {}\n\n\
+            Need to reschedule or cancel? You can do it yourself up to {} hours before the session:\n{}\n",
+            booking.first_name,
+            booking.booking_number,
+            local_time(scheduled_at, booking.timezone.as_deref()),
+            location.as_deref().unwrap_or("to be confirmed"),
+            state.self_service.change_window.whole_hours(),
+            link
+        ),
+    };
+    let mailer = state.mailer.clone();
+    let client = state.db_pool.clone();
+    let booking_id = booking.booking_id.clone();
+    tokio::spawn(async move {
+        send_booking_email(&mailer, &client, &booking_id, confirmation).await;
+    });
+}
+
+fn notify_admin(state: &AppState, booking: &ScheduledBooking, subject: String, body: String) {
+    let Some(admin) = state.mailer.admin_address() else {
+        return;
+    };
+    let notification = OutgoingEmail {
+        to: admin,
+        reply_to: booking.email.clone(),
+        subject,
+        body,
+    };
+    let mailer = state.mailer.clone();
+    let client = state.db_pool.clone();
+    let booking_id = booking.booking_id.clone();
+    tokio::spawn(async move {
+        send_booking_email(&mailer, &client, &booking_id, notification).await;
+    });
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScheduleBooking {
+    //claim an availability slot...
+    slot_id: Option<i64>,
+    //...or set the time directly
+    #[serde(default, with = "time::serde::iso8601::option")]
+    scheduled_at: Option<OffsetDateTime>,
+    duration_minutes: Option<i32>,
+    location: Option<String>,
+    //where the location is, for sunrise and golden hour times
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+//admin sets a booking's session time, the client gets an email with their self-service link
+pub async fn schedule_booking(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+    Json(payload): Json<ScheduleBooking>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    println!("Scheduling booking: {}", booking_id);
+    let booking = find_scheduled_booking(&state.db_pool, &booking_id).await?;
+    if booking.cancelled_at.is_some() {
+        return Err(error(StatusCode::CONFLICT, "Booking is cancelled"));
+    }
+    let location = payload
+        .location
+        .map(|location| location.trim().to_string())
+        .filter(|location| !location.is_empty());
+    let coordinates = match (payload.latitude, payload.longitude) {
+        (Some(latitude), Some(longitude))
+            if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+        {
+            Some((latitude, longitude))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Latitude and longitude must be given together and be valid coordinates",
+            ));
+        }
+    };
+    if coordinates.is_some() && location.is_none() {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Coordinates need a location",
+        ));
+    }
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        println!("Error creating postgres transaction pool: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error scheduling booking",
+        )
+    })?;
+    let (scheduled_at, duration_minutes) = match (payload.slot_id, payload.scheduled_at) {
+        (Some(slot_id), _) => {
+            let (starts_at, ends_at) =
+                move_to_slot(&mut tx, &booking_id, slot_id, OffsetDateTime::now_utc()).await?;
+            (starts_at, (ends_at - starts_at).whole_minutes() as i32)
+        }
+        (None, Some(scheduled_at)) => {
+            release_slot(&mut tx, &booking_id).await?;
+            (scheduled_at, payload.duration_minutes.unwrap_or(60))
+        }
+        (None, None) => {
+            return Err(error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "must provide either slot_id or scheduled_at!",
+            ));
+        }
+    };
+    if duration_minutes <= 0 {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Duration must be positive",
+        ));
+    }
+    save_schedule(
+        &mut tx,
+        &booking_id,
+        scheduled_at,
+        duration_minutes,
+        location.as_deref(),
+        coordinates,
+    )
+    .await?;
+    schedule_reminders(&mut tx, &state.reminders, &booking_id, scheduled_at)
+        .await
+        .map_err(|e| {
+            println!("Error scheduling reminders: {}", e);
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error scheduling booking",
+            )
+        })?;
+    record_activity(
+        &mut *tx,
+        &booking_id,
+        ActivityKind::Scheduled,
+        &format!(
+            "Scheduled for {}",
+            local_time(scheduled_at, booking.timezone.as_deref())
+        ),
+    )
+    .await
+    .map_err(|e| {
+        println!("Error recording booking activity: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error scheduling booking",
+        )
+    })?;
+    tx.commit().await.map_err(|e| {
+        println!("Error scheduling booking: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error scheduling booking",
+        )
+    })?;
+
+    send_schedule_confirmation(
+        &state,
+        &booking,
+        scheduled_at,
+        location.or(booking.location.clone()),
+    );
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Booking scheduled".to_string(),
+        }),
+    ))
+}
+
+//looks up the booking a self-service token was issued for
+async fn booking_from_token(state: &AppState, token: &str) -> Result<ScheduledBooking, Response> {
+    let booking_id = state
+        .link_signer
+        .verify(SELF_SERVICE_LINK, token)
+        .map_err(|_| error(StatusCode::NOT_FOUND, "This link is invalid or has expired"))?;
+    find_scheduled_booking(&state.db_pool, &booking_id).await
+}
+
+//checks that the client can still change the session, returning its current time
+fn changeable_schedule(
+    state: &AppState,
+    booking: &ScheduledBooking,
+) -> Result<OffsetDateTime, (StatusCode, &'static str)> {
+    if booking.cancelled_at.is_some() {
+        return Err((StatusCode::CONFLICT, "This booking is cancelled"));
+    }
+    let Some(scheduled_at) = booking.scheduled_at else {
+        return Err((StatusCode::CONFLICT, "This booking isn't scheduled yet"));
+    };
+    if OffsetDateTime::now_utc() > change_deadline(scheduled_at, &state.self_service) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "It's too close to the session to change it online, please contact us",
+        ));
+    }
+    Ok(scheduled_at)
+}
+
+//what the client sees on their manage booking page
+#[derive(Serialize, Deserialize)]
+pub struct SelfServiceBooking {
+    booking_number: i64,
+    first_name: String,
+    categories: Vec<String>,
+    #[serde(with = "time::serde::iso8601::option")]
+    scheduled_at: Option<OffsetDateTime>,
+    duration_minutes: Option<i32>,
+    location: Option<String>,
+    timezone: Option<String>,
+    cancelled: bool,
+    //whether reschedule and cancel are still allowed
+    can_change: bool,
+    #[serde(with = "time::serde::iso8601::option")]
+    change_deadline: Option<OffsetDateTime>,
+    available_slots: Vec<AvailabilitySlot>,
+}
+
+pub async fn view_self_service_booking(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<SelfServiceBooking>, Response> {
+    let booking = booking_from_token(&state, &token).await?;
+    let can_change = changeable_schedule(&state, &booking).is_ok();
+    let available_slots = if can_change {
+        open_slots(
+            &state.db_pool,
+            earliest_self_service_slot(OffsetDateTime::now_utc(), &state.self_service),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error getting availability: {}", e);
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error getting availability",
+            )
+        })?
+    } else {
+        Vec::new()
+    };
+    Ok(Json(SelfServiceBooking {
+        booking_number: booking.booking_number,
+        first_name: booking.first_name,
+        categories: booking.categories.unwrap_or_default(),
+        scheduled_at: booking.scheduled_at,
+        duration_minutes: booking.duration_minutes,
+        location: booking.location,
+        timezone: booking.timezone,
+        cancelled: booking.cancelled_at.is_some(),
+        can_change,
+        change_deadline: booking
+            .scheduled_at
+            .map(|scheduled_at| change_deadline(scheduled_at, &state.self_service)),
+        available_slots,
+    }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SelfServiceReschedule {
+    slot_id: i64,
+}
+
+//client moves their session to another open slot
+pub async fn self_service_reschedule(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(payload): Json<SelfServiceReschedule>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let booking = booking_from_token(&state, &token).await?;
+    let previous = changeable_schedule(&state, &booking)
+        .map_err(|(status, message)| error(status, message))?;
+    println!("Client rescheduling booking: {}", booking.booking_id);
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        println!("Error creating postgres transaction pool: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error rescheduling booking",
+        )
+    })?;
+    let earliest = earliest_self_service_slot(OffsetDateTime::now_utc(), &state.self_service);
+    let (starts_at, ends_at) =
+        move_to_slot(&mut tx, &booking.booking_id, payload.slot_id, earliest).await?;
+    save_schedule(
+        &mut tx,
+        &booking.booking_id,
+        starts_at,
+        (ends_at - starts_at).whole_minutes() as i32,
+        None,
+        None,
+    )
+    .await?;
+    schedule_reminders(&mut tx, &state.reminders, &booking.booking_id, starts_at)
+        .await
+        .map_err(|e| {
+            println!("Error scheduling reminders: {}", e);
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error rescheduling booking",
+            )
+        })?;
+    let timezone = booking.timezone.as_deref();
+    let detail = format!(
+        "Client rescheduled from {} to {}",
+        local_time(previous, timezone),
+        local_time(starts_at, timezone)
+    );
+    record_activity(
+        &mut *tx,
+        &booking.booking_id,
+        ActivityKind::Rescheduled,
+        &detail,
+    )
+    .await
+    .map_err(|e| {
+        println!("Error recording booking activity: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error rescheduling booking",
+        )
+    })?;
+    tx.commit().await.map_err(|e| {
+        println!("Error rescheduling booking: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error rescheduling booking",
+        )
+    })?;
+
+    notify_admin(
+        &state,
+        &booking,
+        format!(
+            "Booking #{} rescheduled by {} {}",
+            booking.booking_number, booking.first_name, booking.last_name
+        ),
+        format!("{}.\n", detail),
+    );
+    send_schedule_confirmation(&state, &booking, starts_at, booking.location.clone());
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Session rescheduled".to_string(),
+        }),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SelfServiceCancel {
+    reason: Option<String>,
+}
+
+//client cancels their session, the booking is archived like an admin cancellation
+pub async fn self_service_cancel(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(payload): Json<SelfServiceCancel>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let booking = booking_from_token(&state, &token).await?;
+    let scheduled_at = changeable_schedule(&state, &booking)
+        .map_err(|(status, message)| error(status, message))?;
+    println!("Client cancelling booking: {}", booking.booking_id);
+    let reason = payload
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    let detail = match &reason {
+        Some(reason) => format!("Cancelled by client: {}", reason),
+        None => "Cancelled by client".to_string(),
+    };
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        println!("Error creating postgres transaction pool: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error cancelling booking",
+        )
+    })?;
+    release_slot(&mut tx, &booking.booking_id).await?;
+    cancel_reminders(&mut *tx, &booking.booking_id)
+        .await
+        .map_err(|e| {
+            println!("Error cancelling reminders: {}", e);
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error cancelling booking",
+            )
+        })?;
+    sqlx::query!(
+        r#"UPDATE main.booking_requests SET cancelled_at = $1, cancellation_reason = $2
+        WHERE booking_id = $3"#,
+        OffsetDateTime::now_utc(),
+        detail,
+        booking.booking_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error cancelling booking: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error cancelling booking",
+        )
+    })?;
+    record_activity(
+        &mut *tx,
+        &booking.booking_id,
+        ActivityKind::Cancelled,
+        &detail,
+    )
+    .await
+    .map_err(|e| {
+        println!("Error recording booking activity: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error cancelling booking",
+        )
+    })?;
+    tx.commit().await.map_err(|e| {
+        println!("Error cancelling booking: {}", e);
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Error cancelling booking",
+        )
+    })?;
+
+    notify_admin(
+        &state,
+        &booking,
+        format!(
+            "Booking #{} cancelled by {} {}",
+            booking.booking_number, booking.first_name, booking.last_name
+        ),
+        format!(
+            "The session on {} was cancelled.\n{}\n",
+            local_time(scheduled_at, booking.timezone.as_deref()),
+            detail
+        ),
+    );
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Session cancelled".to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn formats_times_in_client_timezone() {
+        let at = datetime!(2026-03-14 19:30 UTC);
+        assert_eq!(
+            local_time(at, Some("America/Los_Angeles")),
+            "Saturday, March 14 2026 at 12:30 PM PDT"
+        );
+        //unknown or missing timezones fall back to eastern time
+        assert_eq!(
+            local_time(at, None),
+            "Saturday, March 14 2026 at 3:30 PM EDT"
+        );
+    }
+
+    #[test]
+    fn change_deadline_is_window_before_session() {
+        let config = SelfServiceConfig {
+            site_url: "http://localhost:3000".into(),
+            change_window: Duration::hours(48),
+        };
+        assert_eq!(
+            change_deadline(datetime!(2026-03-14 19:30 UTC), &config),
+            datetime!(2026-03-12 19:30 UTC)
+        );
+    }
+
+    #[test]
+    fn self_service_slots_start_after_change_window() {
+        let config = SelfServiceConfig {
+            site_url: "http://localhost:3000".into(),
+            change_window: Duration::hours(48),
+        };
+        let now = datetime!(2026-03-10 09:00 UTC);
+        let earliest = earliest_self_service_slot(now, &config);
+        assert_eq!(earliest, datetime!(2026-03-12 09:00 UTC));
+        //a session moved to the earliest offered slot can still be changed at the time it's moved
+        assert!(now <= change_deadline(earliest, &config));
+    }
+}
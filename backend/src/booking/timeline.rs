@@ -0,0 +1,183 @@
+use crate::AppState;
+use crate::booking::booking_exists;
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::PgExecutor;
+use time::OffsetDateTime;
+use tower_sessions::Session;
+
+//things that get logged to a booking's activity timeline automatically
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ActivityKind {
+    Created,
+    StatusChanged,
+    InvoiceLinked,
+    EmailSent,
+    SmsSent,
+    Edited,
+    Cancelled,
+    Restored,
+    Scheduled,
+    Rescheduled,
+    DuplicateFlagged,
+    Merged,
+    QuoteSent,
+    QuoteAccepted,
+    QuoteDeclined,
+    InvoiceVoided,
+    PaymentRecorded,
+    LateFeeCharged,
+}
+
+impl ActivityKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ActivityKind::Created => "created",
+            ActivityKind::StatusChanged => "status_changed",
+            ActivityKind::InvoiceLinked => "invoice_linked",
+            ActivityKind::EmailSent => "email_sent",
+            ActivityKind::SmsSent => "sms_sent",
+            ActivityKind::Edited => "edited",
+            ActivityKind::Cancelled => "cancelled",
+            ActivityKind::Restored => "restored",
+            ActivityKind::Scheduled => "scheduled",
+            ActivityKind::Rescheduled => "rescheduled",
+            ActivityKind::DuplicateFlagged => "duplicate_flagged",
+            ActivityKind::Merged => "merged",
+            ActivityKind::QuoteSent => "quote_sent",
+            ActivityKind::QuoteAccepted => "quote_accepted",
+            ActivityKind::QuoteDeclined => "quote_declined",
+            ActivityKind::InvoiceVoided => "invoice_voided",
+            ActivityKind::PaymentRecorded => "payment_recorded",
+            ActivityKind::LateFeeCharged => "late_fee_charged",
+        }
+    }
+}
+
+//adds an entry to a booking's activity timeline, works with a pool or an open transaction
+pub(crate) async fn record_activity<'e>(
+    executor: impl PgExecutor<'e>,
+    booking_id: &str,
+    kind: ActivityKind,
+    detail: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO main.booking_activity (booking_id, kind, detail) VALUES ($1, $2, $3)",
+        booking_id,
+        kind.as_str(),
+        detail,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewBookingNote {
+    body: String,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    follow_up_at: Option<OffsetDateTime>,
+}
+
+//add an internal note to a booking, never shown to the client
+pub async fn add_booking_note(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+    session: Session,
+    Json(payload): Json<NewBookingNote>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    let client = &state.db_pool;
+    let body = payload.body.trim();
+    if body.is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                message: "Note can't be empty".to_string(),
+            }),
+        ));
+    }
+    let exists = booking_exists(&booking_id, client).await.unwrap_or(false);
+    if !exists {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "ERROR: Booking_ID could not be found".to_string(),
+            }),
+        ));
+    }
+    //notes are signed with whoever is logged in
+    let author = session
+        .get::<String>("user_id")
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "admin".to_string());
+
+    sqlx::query!(
+        r#"INSERT INTO main.booking_notes (booking_id, body, follow_up_at, author, created_at)
+        VALUES ($1, $2, $3, $4, $5)"#,
+        booking_id,
+        body,
+        payload.follow_up_at,
+        author,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(client)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error adding note: {}", e),
+            }),
+        )
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: "Note added".to_string(),
+        }),
+    ))
+}
+
+//one row of a booking's timeline, either an admin note or an automatic activity
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimelineEntry {
+    //"note" or one of the ActivityKind strings
+    kind: String,
+    detail: String,
+    author: Option<String>,
+    #[serde(with = "time::serde::iso8601::option")]
+    follow_up_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601")]
+    created_at: OffsetDateTime,
+}
+
+//notes and activity for a booking, oldest first
+pub async fn view_booking_timeline(
+    State(state): State<AppState>,
+    Path(booking_id): Path<String>,
+) -> Result<Json<Vec<TimelineEntry>>, StatusCode> {
+    let client = &state.db_pool;
+    let timeline = sqlx::query_as!(
+        TimelineEntry,
+        r#"SELECT 'note' AS "kind!", body AS "detail!", author AS "author?",
+            follow_up_at AS "follow_up_at?", created_at AS "created_at!"
+        FROM main.booking_notes WHERE booking_id = $1
+        UNION ALL
+        SELECT kind, detail, NULL, NULL, created_at
+        FROM main.booking_activity WHERE booking_id = $1
+        ORDER BY 5"#,
+        booking_id
+    )
+    .fetch_all(client)
+    .await
+    .map_err(|e| {
+        println!("Error getting booking timeline: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(timeline))
+}
@@ -0,0 +1,56 @@
+use crate::captcha::{
+    CaptchaCheck, CaptchaError, CaptchaVerification, CaptchaVerifier, http_client, siteverify,
+};
+use async_trait::async_trait;
+use std::env;
+
+const DEFAULT_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+//hCaptcha siteverify client, Enterprise accounts also get a score back
+pub(crate) struct HCaptchaVerifier {
+    client: reqwest::Client,
+    secret: String,
+    //optional, makes hCaptcha reject tokens issued for a different site key
+    site_key: Option<String>,
+    verify_url: String,
+}
+
+impl HCaptchaVerifier {
+    pub(crate) fn new(secret: String, site_key: Option<String>, verify_url: String) -> Self {
+        HCaptchaVerifier {
+            client: http_client(),
+            secret,
+            site_key,
+            verify_url,
+        }
+    }
+
+    //HCAPTCHA_VERIFY_URL can point at a local mock instead of hCaptcha
+    pub(crate) fn from_env() -> Self {
+        HCaptchaVerifier::new(
+            env::var("HCAPTCHA_SECRET_KEY").expect("HCAPTCHA_SECRET_KEY not found"),
+            env::var("HCAPTCHA_SITE_KEY")
+                .ok()
+                .filter(|key| !key.is_empty()),
+            env::var("HCAPTCHA_VERIFY_URL").unwrap_or_else(|_| DEFAULT_VERIFY_URL.into()),
+        )
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for HCaptchaVerifier {
+    async fn verify(&self, check: CaptchaCheck<'_>) -> Result<CaptchaVerification, CaptchaError> {
+        let mut extra_form = Vec::new();
+        if let Some(site_key) = &self.site_key {
+            extra_form.push(("sitekey", site_key.clone()));
+        }
+        siteverify(
+            &self.client,
+            &self.verify_url,
+            &self.secret,
+            &check,
+            &extra_form,
+        )
+        .await
+    }
+}
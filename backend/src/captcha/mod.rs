@@ -0,0 +1,349 @@
+pub mod hcaptcha;
+pub mod recaptcha;
+pub mod turnstile;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+//everything a provider needs to check a token
+pub(crate) struct CaptchaCheck<'a> {
+    pub(crate) token: &'a str,
+    //resolved client IP (see client_ip.rs), not the proxy's
+    pub(crate) remote_ip: Option<IpAddr>,
+    //the action the widget was rendered with, e.g. "booking"
+    pub(crate) expected_action: Option<&'a str>,
+    //the site hostname the widget was solved on
+    pub(crate) expected_hostname: Option<&'a str>,
+}
+
+#[derive(Debug)]
+pub(crate) struct CaptchaVerification {
+    pub(crate) success: bool,
+    pub(crate) error_codes: Vec<String>,
+    //0.0 (bot) to 1.0 (human), only reCAPTCHA v3 and hCaptcha Enterprise send one
+    pub(crate) score: Option<f64>,
+}
+
+impl CaptchaVerification {
+    pub(crate) fn failed(error_code: &str) -> Self {
+        CaptchaVerification {
+            success: false,
+            error_codes: vec![error_code.to_string()],
+            score: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum CaptchaError {
+    //the provider couldn't be reached or sent back something unexpected
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for CaptchaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptchaError::Request(e) => write!(f, "captcha verification request failed: {}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for CaptchaError {
+    fn from(e: reqwest::Error) -> Self {
+        CaptchaError::Request(e)
+    }
+}
+
+#[async_trait]
+pub(crate) trait CaptchaVerifier: Send + Sync {
+    async fn verify(&self, check: CaptchaCheck<'_>) -> Result<CaptchaVerification, CaptchaError>;
+}
+
+//response shared by the Turnstile, hCaptcha and reCAPTCHA siteverify endpoints
+#[derive(Debug, Deserialize)]
+pub(crate) struct SiteVerifyResponse {
+    pub(crate) success: bool,
+    #[serde(default)]
+    #[serde(rename = "error-codes")]
+    pub(crate) error_codes: Vec<String>,
+
+    // Optional fields the provider may return
+    #[serde(default)]
+    pub(crate) hostname: Option<String>,
+    #[serde(default)]
+    pub(crate) action: Option<String>,
+    #[serde(default)]
+    pub(crate) score: Option<f64>,
+}
+
+//posts a token to a siteverify endpoint and checks the answer against what we expected
+pub(crate) async fn siteverify(
+    client: &reqwest::Client,
+    verify_url: &str,
+    secret: &str,
+    check: &CaptchaCheck<'_>,
+    extra_form: &[(&str, String)],
+) -> Result<CaptchaVerification, CaptchaError> {
+    // Basic sanity checks from docs (token max length 2048)
+    if check.token.is_empty() || check.token.len() > 2048 {
+        return Ok(CaptchaVerification::failed("invalid-input-response"));
+    }
+
+    // Send as application/x-www-form-urlencoded
+    let mut form = vec![
+        ("secret", secret.to_string()),
+        ("response", check.token.to_string()),
+    ];
+    if let Some(ip) = check.remote_ip {
+        form.push(("remoteip", ip.to_string()));
+    }
+    form.extend(extra_form.iter().cloned());
+
+    let resp = client
+        .post(verify_url)
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()? // treat non-2xx as error
+        .json::<SiteVerifyResponse>()
+        .await?;
+
+    // enforce action/hostname matching our expectations
+    if resp.success {
+        if let (Some(exp), Some(got)) = (check.expected_action, resp.action.as_deref())
+            && exp != got
+        {
+            return Ok(CaptchaVerification::failed("action-mismatch"));
+        }
+        if let (Some(exp), Some(got)) = (check.expected_hostname, resp.hostname.as_deref())
+            && exp != got
+        {
+            return Ok(CaptchaVerification::failed("hostname-mismatch"));
+        }
+    }
+
+    Ok(CaptchaVerification {
+        success: resp.success,
+        error_codes: resp.error_codes,
+        score: resp.score,
+    })
+}
+
+pub(crate) fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .expect("Failed to build captcha HTTP client")
+}
+
+//accepts every token, for local development without captcha keys
+pub(crate) struct DisabledVerifier;
+
+#[async_trait]
+impl CaptchaVerifier for DisabledVerifier {
+    async fn verify(&self, _check: CaptchaCheck<'_>) -> Result<CaptchaVerification, CaptchaError> {
+        Ok(CaptchaVerification {
+            success: true,
+            error_codes: Vec::new(),
+            score: None,
+        })
+    }
+}
+
+//the configured verifier plus the settings shared by every form, stored in AppState
+#[derive(Clone)]
+pub(crate) struct Captcha {
+    verifier: Arc<dyn CaptchaVerifier>,
+    expected_hostname: Option<String>,
+    //tokens scoring lower than this are treated as bots
+    min_score: f64,
+}
+
+impl Captcha {
+    pub(crate) fn new(
+        verifier: Arc<dyn CaptchaVerifier>,
+        expected_hostname: Option<String>,
+        min_score: f64,
+    ) -> Self {
+        Captcha {
+            verifier,
+            expected_hostname,
+            min_score,
+        }
+    }
+
+    //CAPTCHA_PROVIDER picks turnstile (default), hcaptcha, recaptcha or disabled.
+    //CAPTCHA_EXPECTED_HOSTNAME is optional, leave it unset to accept tokens from any hostname
+    pub(crate) fn from_env() -> Self {
+        let provider = env::var("CAPTCHA_PROVIDER").unwrap_or_else(|_| "turnstile".into());
+        let verifier: Arc<dyn CaptchaVerifier> = match provider.to_lowercase().as_str() {
+            "turnstile" => Arc::new(turnstile::TurnstileVerifier::from_env()),
+            "hcaptcha" => Arc::new(hcaptcha::HCaptchaVerifier::from_env()),
+            "recaptcha" => Arc::new(recaptcha::RecaptchaVerifier::from_env()),
+            "disabled" => {
+                let is_prod = env::var("RUST_STATUS").unwrap_or_default() == "production";
+                if is_prod {
+                    panic!("CAPTCHA_PROVIDER=disabled is not allowed in production");
+                }
+                println!("WARNING: captcha verification is disabled");
+                Arc::new(DisabledVerifier)
+            }
+            other => panic!("Unknown CAPTCHA_PROVIDER: {}", other),
+        };
+        let min_score = env::var("CAPTCHA_MIN_SCORE")
+            .ok()
+            .map(|score| score.parse().expect("CAPTCHA_MIN_SCORE must be a number"))
+            .unwrap_or(0.5);
+        Captcha::new(
+            verifier,
+            env::var("CAPTCHA_EXPECTED_HOSTNAME")
+                .ok()
+                .filter(|hostname| !hostname.is_empty()),
+            min_score,
+        )
+    }
+
+    pub(crate) async fn verify(
+        &self,
+        token: &str,
+        remote_ip: IpAddr,
+        expected_action: &str,
+    ) -> Result<CaptchaVerification, CaptchaError> {
+        let verification = self
+            .verifier
+            .verify(CaptchaCheck {
+                token,
+                remote_ip: Some(remote_ip),
+                expected_action: Some(expected_action),
+                expected_hostname: self.expected_hostname.as_deref(),
+            })
+            .await?;
+        if verification.success
+            && let Some(score) = verification.score
+            && score < self.min_score
+        {
+            return Ok(CaptchaVerification {
+                score: Some(score),
+                ..CaptchaVerification::failed("score-too-low")
+            });
+        }
+        Ok(verification)
+    }
+}
+
+//test double that accepts or rejects every token and remembers what it was asked
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::sync::Mutex;
+
+    //token, remote ip and expected action of one verify call
+    pub(crate) type SeenCheck = (String, Option<IpAddr>, Option<String>);
+
+    pub(crate) struct MockCaptchaVerifier {
+        accept: bool,
+        pub(crate) checks: Mutex<Vec<SeenCheck>>,
+    }
+
+    impl MockCaptchaVerifier {
+        pub(crate) fn new(accept: bool) -> Self {
+            MockCaptchaVerifier {
+                accept,
+                checks: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl CaptchaVerifier for MockCaptchaVerifier {
+        async fn verify(
+            &self,
+            check: CaptchaCheck<'_>,
+        ) -> Result<CaptchaVerification, CaptchaError> {
+            self.checks.lock().unwrap().push((
+                check.token.to_string(),
+                check.remote_ip,
+                check.expected_action.map(str::to_string),
+            ));
+            if self.accept {
+                Ok(CaptchaVerification {
+                    success: true,
+                    error_codes: Vec::new(),
+                    score: None,
+                })
+            } else {
+                Ok(CaptchaVerification::failed("invalid-input-response"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+
+    //local stand-in for a provider's siteverify endpoint
+    async fn mock_siteverify(response: serde_json::Value) -> String {
+        let app = Router::new().route(
+            "/siteverify",
+            post(move || {
+                let response = response.clone();
+                async move { Json(response) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/siteverify", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn rejects_scores_below_threshold() {
+        let url = mock_siteverify(serde_json::json!({
+            "success": true,
+            "score": 0.3,
+            "action": "booking",
+            "hostname": "example.com"
+        }))
+        .await;
+        let verifier = recaptcha::RecaptchaVerifier::new("secret".into(), url);
+        let ip: IpAddr = "198.51.100.1".parse().unwrap();
+
+        let strict = Captcha::new(Arc::new(verifier), Some("example.com".into()), 0.5);
+        let result = strict.verify("token", ip, "booking").await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error_codes, vec!["score-too-low".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatched_action() {
+        let url = mock_siteverify(serde_json::json!({
+            "success": true,
+            "action": "login"
+        }))
+        .await;
+        let verifier = hcaptcha::HCaptchaVerifier::new("secret".into(), None, url);
+        let captcha = Captcha::new(Arc::new(verifier), None, 0.5);
+        let result = captcha
+            .verify("token", "198.51.100.1".parse().unwrap(), "booking")
+            .await
+            .unwrap();
+        assert_eq!(result.error_codes, vec!["action-mismatch".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn disabled_accepts_everything() {
+        let captcha = Captcha::new(Arc::new(DisabledVerifier), None, 0.5);
+        let result = captcha
+            .verify("", "127.0.0.1".parse().unwrap(), "booking")
+            .await
+            .unwrap();
+        assert!(result.success);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::captcha::{
+    CaptchaCheck, CaptchaError, CaptchaVerification, CaptchaVerifier, http_client, siteverify,
+};
+use async_trait::async_trait;
+use std::env;
+
+const DEFAULT_VERIFY_URL: &str = "https://www.google.com/recaptcha/api/siteverify";
+
+//Google reCAPTCHA v3 siteverify client, every response carries a score
+pub(crate) struct RecaptchaVerifier {
+    client: reqwest::Client,
+    secret: String,
+    verify_url: String,
+}
+
+impl RecaptchaVerifier {
+    pub(crate) fn new(secret: String, verify_url: String) -> Self {
+        RecaptchaVerifier {
+            client: http_client(),
+            secret,
+            verify_url,
+        }
+    }
+
+    //RECAPTCHA_VERIFY_URL can point at a local mock instead of Google
+    pub(crate) fn from_env() -> Self {
+        RecaptchaVerifier::new(
+            env::var("RECAPTCHA_SECRET_KEY").expect("RECAPTCHA_SECRET_KEY not found"),
+            env::var("RECAPTCHA_VERIFY_URL").unwrap_or_else(|_| DEFAULT_VERIFY_URL.into()),
+        )
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for RecaptchaVerifier {
+    async fn verify(&self, check: CaptchaCheck<'_>) -> Result<CaptchaVerification, CaptchaError> {
+        let verification =
+            siteverify(&self.client, &self.verify_url, &self.secret, &check, &[]).await?;
+        //v3 always scores, a missing score means the token wasn't a v3 token
+        if verification.success && verification.score.is_none() {
+            return Ok(CaptchaVerification::failed("missing-score"));
+        }
+        Ok(verification)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::captcha::{
+    CaptchaCheck, CaptchaError, CaptchaVerification, CaptchaVerifier, http_client, siteverify,
+};
+use async_trait::async_trait;
+use std::env;
+
+const DEFAULT_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+//Cloudflare Turnstile siteverify client
+pub(crate) struct TurnstileVerifier {
+    client: reqwest::Client,
+    secret: String,
+    verify_url: String,
+}
+
+impl TurnstileVerifier {
+    pub(crate) fn new(secret: String, verify_url: String) -> Self {
+        TurnstileVerifier {
+            client: http_client(),
+            secret,
+            verify_url,
+        }
+    }
+
+    //TURNSTILE_VERIFY_URL can point at a local mock instead of Cloudflare
+    pub(crate) fn from_env() -> Self {
+        TurnstileVerifier::new(
+            env::var("TURNSTILE_SECRET_KEY").expect("TURNSTILE_SECRET_KEY not found"),
+            env::var("TURNSTILE_VERIFY_URL").unwrap_or_else(|_| DEFAULT_VERIFY_URL.into()),
+        )
+    }
+}
+
+#[async_trait]
+impl CaptchaVerifier for TurnstileVerifier {
+    async fn verify(&self, check: CaptchaCheck<'_>) -> Result<CaptchaVerification, CaptchaError> {
+        siteverify(&self.client, &self.verify_url, &self.secret, &check, &[]).await
+    }
+}
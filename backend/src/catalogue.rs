@@ -0,0 +1,483 @@
+use crate::AppState;
+use crate::booking::BOOKING_CATEGORIES;
+use crate::invoicing::currency::{Currency, DEFAULT_CURRENCY};
+use crate::invoicing::invoice::{ApiResponse, NewInvoiceItem, default_taxable};
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+const ITEM_KINDS: [&str; 2] = ["package", "addon"];
+
+//a service or add-on on the price list
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct CatalogueItem {
+    pub(crate) service_id: i64,
+    //package or addon
+    pub(crate) kind: String,
+    //None when it's offered with every category
+    pub(crate) category: Option<String>,
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) hourly_rate: Option<Decimal>,
+    pub(crate) per_photo_fee: Option<Decimal>,
+    //edited photos that come with the hours before per photo fees apply
+    pub(crate) included_photos: i32,
+    pub(crate) flat_fee: Option<Decimal>,
+    pub(crate) active: bool,
+    pub(crate) sort_order: i32,
+    //services are taxable, prints may not be depending on the state
+    pub(crate) taxable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCatalogueItem {
+    kind: String,
+    category: Option<String>,
+    name: String,
+    description: Option<String>,
+    hourly_rate: Option<Decimal>,
+    per_photo_fee: Option<Decimal>,
+    #[serde(default)]
+    included_photos: i32,
+    flat_fee: Option<Decimal>,
+    #[serde(default = "default_active")]
+    active: bool,
+    #[serde(default)]
+    sort_order: i32,
+    #[serde(default = "default_taxable")]
+    taxable: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CatalogueQuery {
+    category: Option<String>,
+}
+
+//pulls a catalogue item onto an invoice or quote, expanded into line items by preset_line_items
+#[derive(Serialize, Deserialize)]
+pub struct LineItemPreset {
+    service_id: i64,
+    #[serde(default)]
+    hours: i32,
+    //edited photos delivered
+    #[serde(default)]
+    photos: i32,
+    //how many times a flat fee applies, defaults to 1
+    quantity: Option<i32>,
+}
+
+fn validate_item(item: &NewCatalogueItem) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    if !ITEM_KINDS.contains(&item.kind.as_str()) {
+        errors.add("kind", "Kind must be package or addon");
+    }
+    if let Some(category) = &item.category
+        && !BOOKING_CATEGORIES
+            .iter()
+            .any(|(value, _)| value == category)
+    {
+        errors.add("category", "Unknown category");
+    }
+    if item.name.trim().is_empty() {
+        errors.add("name", "Name is required");
+    }
+    for (field, price) in [
+        ("hourly_rate", item.hourly_rate),
+        ("per_photo_fee", item.per_photo_fee),
+        ("flat_fee", item.flat_fee),
+    ] {
+        if price.is_some_and(|price| price.is_sign_negative()) {
+            errors.add(field, "Prices can't be negative");
+        }
+    }
+    if item.hourly_rate.is_none() && item.per_photo_fee.is_none() && item.flat_fee.is_none() {
+        errors.add(
+            "hourly_rate",
+            "Set at least one of hourly rate, per photo fee or flat fee",
+        );
+    }
+    if item.included_photos < 0 {
+        errors.add("included_photos", "Included photos can't be negative");
+    }
+    errors.finish(())
+}
+
+//a preset can't add zero or negative line items
+fn validate_preset(preset: &LineItemPreset) -> Result<(), &'static str> {
+    if preset.hours < 0 || preset.photos < 0 {
+        return Err("Hours and photos can't be negative");
+    }
+    if preset.quantity.is_some_and(|quantity| quantity < 1) {
+        return Err("Quantity must be at least 1");
+    }
+    Ok(())
+}
+
+//line items for a preset: hours at the hourly rate, photos past the included ones at the per photo fee,
+//and the flat fee
+pub(crate) fn line_items(item: &CatalogueItem, preset: &LineItemPreset) -> Vec<NewInvoiceItem> {
+    let mut items = Vec::new();
+    if let Some(hourly_rate) = item.hourly_rate
+        && preset.hours > 0
+    {
+        items.push(NewInvoiceItem {
+            description: format!("{} - hours of shooting", item.name),
+            quantity: preset.hours,
+            unit_price: hourly_rate,
+            taxable: item.taxable,
+            discount: None,
+            late_fee: false,
+        });
+    }
+    let extra_photos = preset.photos - item.included_photos;
+    if let Some(per_photo_fee) = item.per_photo_fee
+        && extra_photos > 0
+    {
+        let description = if item.included_photos > 0 {
+            format!("{} - additional edited photos", item.name)
+        } else {
+            format!("{} - edited photos", item.name)
+        };
+        items.push(NewInvoiceItem {
+            description,
+            quantity: extra_photos,
+            unit_price: per_photo_fee,
+            taxable: item.taxable,
+            discount: None,
+            late_fee: false,
+        });
+    }
+    if let Some(flat_fee) = item.flat_fee {
+        items.push(NewInvoiceItem {
+            description: item.name.clone(),
+            quantity: preset.quantity.unwrap_or(1),
+            unit_price: flat_fee,
+            taxable: item.taxable,
+            discount: None,
+            late_fee: false,
+        });
+    }
+    items
+}
+
+pub(crate) async fn find_catalogue_item(
+    client: &sqlx::PgPool,
+    service_id: i64,
+) -> Result<Option<CatalogueItem>, sqlx::Error> {
+    sqlx::query_as!(
+        CatalogueItem,
+        r#"SELECT service_id, kind, category, name, description, hourly_rate, per_photo_fee,
+            included_photos, flat_fee, active, sort_order, taxable
+        FROM main.service_catalogue WHERE service_id = $1"#,
+        service_id
+    )
+    .fetch_optional(client)
+    .await
+}
+
+//expands presets into line items for invoice and quote creation. catalogue prices are in the
+//default currency, so they can't be used on invoices or quotes in another one
+pub(crate) async fn preset_line_items(
+    client: &sqlx::PgPool,
+    presets: &[LineItemPreset],
+    currency: &Currency,
+) -> Result<Vec<NewInvoiceItem>, (StatusCode, Json<ApiResponse>)> {
+    if !presets.is_empty() && currency.code != DEFAULT_CURRENCY {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                message: format!(
+                    "Catalogue prices are in {}, add {} lines by hand",
+                    DEFAULT_CURRENCY, currency.code
+                ),
+            }),
+        ));
+    }
+    let mut items = Vec::new();
+    for preset in presets {
+        validate_preset(preset).map_err(|message| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse {
+                    message: format!("Catalogue item {}: {}", preset.service_id, message),
+                }),
+            )
+        })?;
+        let item = find_catalogue_item(client, preset.service_id)
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse {
+                        message: format!("Error getting catalogue item: {}", e),
+                    }),
+                )
+            })?
+            .ok_or_else(|| {
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ApiResponse {
+                        message: format!("Unknown catalogue item: {}", preset.service_id),
+                    }),
+                )
+            })?;
+        //a retired package can't be added at its old price
+        if !item.active {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse {
+                    message: format!("Catalogue item {} is no longer offered", preset.service_id),
+                }),
+            ));
+        }
+        items.extend(line_items(&item, preset));
+    }
+    Ok(items)
+}
+
+async fn list_catalogue(
+    client: &sqlx::PgPool,
+    category: Option<&str>,
+    include_inactive: bool,
+) -> Result<Vec<CatalogueItem>, sqlx::Error> {
+    //items without a category are offered with every category
+    sqlx::query_as!(
+        CatalogueItem,
+        r#"SELECT service_id, kind, category, name, description, hourly_rate, per_photo_fee,
+            included_photos, flat_fee, active, sort_order, taxable
+        FROM main.service_catalogue
+        WHERE ($1::varchar IS NULL OR category IS NULL OR category = $1)
+            AND (active OR $2)
+        ORDER BY kind DESC, category NULLS LAST, sort_order, service_id"#,
+        category,
+        include_inactive,
+    )
+    .fetch_all(client)
+    .await
+}
+
+//public price list
+pub async fn get_catalogue(
+    State(state): State<AppState>,
+    Query(q): Query<CatalogueQuery>,
+) -> Result<Json<Vec<CatalogueItem>>, StatusCode> {
+    list_catalogue(&state.db_pool, q.category.as_deref(), false)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error getting catalogue: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+//everything including inactive items, for the admin editor
+pub async fn get_full_catalogue(
+    State(state): State<AppState>,
+    Query(q): Query<CatalogueQuery>,
+) -> Result<Json<Vec<CatalogueItem>>, StatusCode> {
+    list_catalogue(&state.db_pool, q.category.as_deref(), true)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error getting catalogue: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn create_catalogue_item(
+    State(state): State<AppState>,
+    Json(payload): Json<NewCatalogueItem>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    validate_item(&payload).map_err(|errors| errors.into_response())?;
+    let service_id = sqlx::query_scalar!(
+        r#"INSERT INTO main.service_catalogue (kind, category, name, description, hourly_rate,
+            per_photo_fee, included_photos, flat_fee, active, sort_order, taxable)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) RETURNING service_id"#,
+        payload.kind,
+        payload.category,
+        payload.name.trim(),
+        payload.description,
+        payload.hourly_rate,
+        payload.per_photo_fee,
+        payload.included_photos,
+        payload.flat_fee,
+        payload.active,
+        payload.sort_order,
+        payload.taxable,
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error creating catalogue item: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: format!("Catalogue item {} created", service_id),
+        }),
+    ))
+}
+
+//replaces every field of an item, invoices and quotes that already used it keep their old prices
+pub async fn edit_catalogue_item(
+    State(state): State<AppState>,
+    Path(service_id): Path<i64>,
+    Json(payload): Json<NewCatalogueItem>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    validate_item(&payload).map_err(|errors| errors.into_response())?;
+    let updated = sqlx::query!(
+        r#"UPDATE main.service_catalogue SET kind = $1, category = $2, name = $3, description = $4,
+            hourly_rate = $5, per_photo_fee = $6, included_photos = $7, flat_fee = $8, active = $9,
+            sort_order = $10, taxable = $11
+        WHERE service_id = $12"#,
+        payload.kind,
+        payload.category,
+        payload.name.trim(),
+        payload.description,
+        payload.hourly_rate,
+        payload.per_photo_fee,
+        payload.included_photos,
+        payload.flat_fee,
+        payload.active,
+        payload.sort_order,
+        payload.taxable,
+        service_id,
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error editing catalogue item: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    if updated.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "Catalogue item not found".to_string(),
+            }),
+        )
+            .into_response());
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Catalogue item updated".to_string(),
+        }),
+    ))
+}
+
+pub async fn delete_catalogue_item(
+    State(state): State<AppState>,
+    Path(service_id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let deleted = sqlx::query!(
+        "DELETE FROM main.service_catalogue WHERE service_id = $1",
+        service_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error deleting catalogue item: {}", e),
+            }),
+        )
+    })?;
+    if deleted.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "Catalogue item not found".to_string(),
+            }),
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_package_into_line_items() {
+        let package = CatalogueItem {
+            service_id: 1,
+            kind: "package".to_string(),
+            category: Some("portraiture".to_string()),
+            name: "Portrait session".to_string(),
+            description: None,
+            hourly_rate: Some(Decimal::from(40)),
+            per_photo_fee: Some(Decimal::from(15)),
+            included_photos: 4,
+            flat_fee: None,
+            active: true,
+            sort_order: 0,
+            taxable: true,
+        };
+        let items = line_items(
+            &package,
+            &LineItemPreset {
+                service_id: 1,
+                hours: 2,
+                photos: 10,
+                quantity: None,
+            },
+        );
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].quantity, 2);
+        assert_eq!(items[0].unit_price, Decimal::from(40));
+        assert_eq!(
+            items[1].description,
+            "Portrait session - additional edited photos"
+        );
+        assert_eq!(items[1].quantity, 6);
+
+        //photos within the included ones don't add a line
+        let items = line_items(
+            &package,
+            &LineItemPreset {
+                service_id: 1,
+                hours: 1,
+                photos: 4,
+                quantity: None,
+            },
+        );
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn rejects_presets_that_add_nothing_or_less() {
+        let preset = |hours, photos, quantity| LineItemPreset {
+            service_id: 1,
+            hours,
+            photos,
+            quantity,
+        };
+        assert!(validate_preset(&preset(2, 10, None)).is_ok());
+        assert!(validate_preset(&preset(0, 0, Some(1))).is_ok());
+        assert!(validate_preset(&preset(0, 0, Some(0))).is_err());
+        assert!(validate_preset(&preset(0, 0, Some(-3))).is_err());
+        assert!(validate_preset(&preset(-1, 0, None)).is_err());
+        assert!(validate_preset(&preset(0, -5, None)).is_err());
+    }
+}
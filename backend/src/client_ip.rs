@@ -0,0 +1,126 @@
+use crate::AppState;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use ipnet::IpNet;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+//reverse proxies (nginx, cloudflare tunnel, etc.) whose X-Forwarded-For header can be trusted
+#[derive(Clone, Default)]
+pub(crate) struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    //TRUSTED_PROXIES is a comma separated list of IPs or CIDR ranges ("127.0.0.1,10.0.0.0/8")
+    pub(crate) fn from_env() -> Self {
+        let proxies = env::var("TRUSTED_PROXIES").unwrap_or_default();
+        TrustedProxies(
+            proxies
+                .split(',')
+                .map(str::trim)
+                .filter(|proxy| !proxy.is_empty())
+                .map(|proxy| {
+                    proxy
+                        .parse::<IpNet>()
+                        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                        .unwrap_or_else(|_| panic!("Invalid TRUSTED_PROXIES entry: {}", proxy))
+                })
+                .collect(),
+        )
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|proxy| proxy.contains(ip))
+    }
+}
+
+//finds the real client address. X-Forwarded-For is only believed when the request came
+//from a trusted proxy, and is read right to left so a client can't spoof it by sending its own
+pub(crate) fn resolve_client_ip(
+    peer: IpAddr,
+    forwarded_for: Option<&str>,
+    trusted: &TrustedProxies,
+) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+    let Some(forwarded_for) = forwarded_for else {
+        return peer;
+    };
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !trusted.contains(&ip) {
+                    break;
+                }
+            }
+            //garbage in the header, stop at the last address we could trust
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+//extractor for the resolved client IP of a request
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .copied()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        let forwarded_for = parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok());
+        Ok(ClientIp(resolve_client_ip(
+            peer.ip(),
+            forwarded_for,
+            &state.trusted_proxies,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted() -> TrustedProxies {
+        TrustedProxies(vec![
+            "127.0.0.1/32".parse().unwrap(),
+            "10.0.0.0/8".parse().unwrap(),
+        ])
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(
+            resolve_client_ip(peer, Some("198.51.100.1"), &trusted()),
+            peer
+        );
+    }
+
+    #[test]
+    fn skips_trusted_proxies_in_forwarded_for() {
+        let peer: IpAddr = "127.0.0.1".parse().unwrap();
+        //the client tried to spoof 1.2.3.4, the proxy appended the real address
+        let client = resolve_client_ip(peer, Some("1.2.3.4, 198.51.100.1, 10.0.0.2"), &trusted());
+        assert_eq!(client, "198.51.100.1".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn falls_back_to_peer_without_header() {
+        let peer: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(resolve_client_ip(peer, None, &trusted()), peer);
+    }
+}
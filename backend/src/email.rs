@@ -0,0 +1,251 @@
+use lettre::address::AddressError;
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::fmt;
+
+//how the connection to the SMTP relay is secured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SmtpSecurity {
+    //plain connection, only for local SMTP sinks (mailpit, mailhog, etc.)
+    None,
+    StartTls,
+    Tls,
+}
+
+//SMTP relay settings, loaded once at startup
+pub(crate) struct EmailConfig {
+    pub(crate) host: Option<String>,
+    pub(crate) port: u16,
+    pub(crate) security: SmtpSecurity,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) from: String,
+    pub(crate) admin: Option<String>,
+}
+
+impl EmailConfig {
+    //SMTP_HOST unset means email is disabled and messages are only logged
+    pub(crate) fn from_env() -> Self {
+        let security = match env::var("SMTP_SECURITY")
+            .unwrap_or_else(|_| "starttls".into())
+            .to_lowercase()
+            .as_str()
+        {
+            "none" => SmtpSecurity::None,
+            "tls" => SmtpSecurity::Tls,
+            _ => SmtpSecurity::StartTls,
+        };
+        let default_port = match security {
+            SmtpSecurity::None => 1025,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+        };
+        EmailConfig {
+            host: env::var("SMTP_HOST").ok().filter(|h| !h.is_empty()),
+            port: env::var("SMTP_PORT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(default_port),
+            security,
+            username: env::var("SMTP_USERNAME").ok().filter(|u| !u.is_empty()),
+            password: env::var("SMTP_PASSWORD").ok(),
+            from: env::var("EMAIL_FROM").unwrap_or_else(|_| "noreply@localhost".into()),
+            admin: env::var("ADMIN_EMAIL").ok().filter(|a| !a.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum EmailError {
+    Address(AddressError),
+    Message(lettre::error::Error),
+    Smtp(lettre::transport::smtp::Error),
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::Address(e) => write!(f, "invalid email address: {}", e),
+            EmailError::Message(e) => write!(f, "could not build email: {}", e),
+            EmailError::Smtp(e) => write!(f, "SMTP error: {}", e),
+        }
+    }
+}
+
+impl From<AddressError> for EmailError {
+    fn from(e: AddressError) -> Self {
+        EmailError::Address(e)
+    }
+}
+impl From<lettre::error::Error> for EmailError {
+    fn from(e: lettre::error::Error) -> Self {
+        EmailError::Message(e)
+    }
+}
+impl From<lettre::transport::smtp::Error> for EmailError {
+    fn from(e: lettre::transport::smtp::Error) -> Self {
+        EmailError::Smtp(e)
+    }
+}
+
+//a plain text email waiting to be sent
+pub(crate) struct OutgoingEmail {
+    pub(crate) to: String,
+    pub(crate) reply_to: Option<String>,
+    pub(crate) subject: String,
+    pub(crate) body: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct Mailer {
+    //None when SMTP_HOST isn't configured
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    admin: Option<Mailbox>,
+}
+
+impl Mailer {
+    pub(crate) fn new(config: EmailConfig) -> Result<Self, EmailError> {
+        let from: Mailbox = config.from.parse()?;
+        let admin = match config.admin {
+            Some(admin) => Some(admin.parse()?),
+            None => None,
+        };
+        let transport = match config.host {
+            Some(host) => {
+                let mut builder = match config.security {
+                    SmtpSecurity::None => {
+                        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)
+                    }
+                    SmtpSecurity::StartTls => {
+                        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?
+                    }
+                    SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+                }
+                .port(config.port);
+                if let Some(username) = config.username {
+                    builder = builder.credentials(Credentials::new(
+                        username,
+                        config.password.unwrap_or_default(),
+                    ));
+                }
+                Some(builder.build())
+            }
+            None => {
+                println!("SMTP_HOST not set, outgoing email is disabled");
+                None
+            }
+        };
+        Ok(Mailer {
+            transport,
+            from,
+            admin,
+        })
+    }
+
+    pub(crate) fn admin_address(&self) -> Option<String> {
+        self.admin.as_ref().map(|admin| admin.email.to_string())
+    }
+
+    pub(crate) async fn send(&self, email: OutgoingEmail) -> Result<(), EmailError> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject.as_str())
+            .header(ContentType::TEXT_PLAIN);
+        if let Some(reply_to) = &email.reply_to {
+            builder = builder.reply_to(reply_to.parse()?);
+        }
+        let message = builder.body(email.body)?;
+
+        match &self.transport {
+            Some(transport) => {
+                transport.send(message).await?;
+                println!("email sent to {}: {}", email.to, email.subject);
+            }
+            None => println!(
+                "email disabled, not sending to {}: {}",
+                email.to, email.subject
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    //minimal SMTP sink: accepts one message and hands back the DATA section
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    writer.write_all(b"250 OK\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let command = line.to_uppercase();
+            if command.starts_with("EHLO") {
+                writer.write_all(b"250 localhost\r\n").await.unwrap();
+            } else if command.starts_with("DATA") {
+                in_data = true;
+                writer.write_all(b"354 send data\r\n").await.unwrap();
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                writer.write_all(b"250 OK\r\n").await.unwrap();
+            }
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn sends_through_local_smtp_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let mailer = Mailer::new(EmailConfig {
+            host: Some("127.0.0.1".into()),
+            port,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "Studio <studio@example.com>".into(),
+            admin: Some("admin@example.com".into()),
+        })
+        .unwrap();
+        mailer
+            .send(OutgoingEmail {
+                to: "client@example.com".into(),
+                reply_to: None,
+                subject: "We received your booking request".into(),
+                body: "Thanks!".into(),
+            })
+            .await
+            .unwrap();
+        drop(mailer);
+
+        let data = sink.await.unwrap();
+        assert!(data.contains("To: client@example.com"));
+        assert!(data.contains("Subject: We received your booking request"));
+        assert!(data.contains("Thanks!"));
+    }
+}
@@ -0,0 +1,594 @@
+use crate::AppState;
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::invoicing::currency::{Currency, Money, currency_or_default};
+use crate::invoicing::invoice::ApiResponse;
+use crate::invoicing::payments::{
+    NewPayment, amount_paid, balance_due, insert_payment, invoice_payments,
+};
+use axum::Json;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode as ProviderStatus;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::env;
+use std::fmt;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+type HmacSha256 = Hmac<Sha256>;
+
+//webhooks signed longer ago than this are rejected so a captured request can't be replayed
+const WEBHOOK_TOLERANCE_SECONDS: i64 = 300;
+//how long to wait on the payment provider before giving up on a checkout link
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+//Stripe (or any API that speaks its Checkout Sessions format) credentials, loaded once at startup
+pub(crate) struct CheckoutConfig {
+    pub(crate) secret_key: String,
+    pub(crate) webhook_secret: String,
+    //point at a local mock server for testing
+    pub(crate) api_url: String,
+}
+
+impl CheckoutConfig {
+    //online payments are optional, None unless STRIPE_SECRET_KEY and STRIPE_WEBHOOK_SECRET are set
+    pub(crate) fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        Some(CheckoutConfig {
+            secret_key: var("STRIPE_SECRET_KEY")?,
+            webhook_secret: var("STRIPE_WEBHOOK_SECRET")?,
+            api_url: var("STRIPE_API_URL")
+                .unwrap_or_else(|| "https://api.stripe.com".into())
+                .trim_end_matches('/')
+                .to_string(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum CheckoutError {
+    Disabled,
+    Request(reqwest::Error),
+    Rejected(ProviderStatus, String),
+    BadSignature,
+}
+
+impl fmt::Display for CheckoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckoutError::Disabled => write!(f, "online payments aren't configured"),
+            CheckoutError::Request(e) => write!(f, "could not reach payment provider: {}", e),
+            CheckoutError::Rejected(status, body) => {
+                write!(
+                    f,
+                    "payment provider rejected request ({}): {}",
+                    status, body
+                )
+            }
+            CheckoutError::BadSignature => write!(f, "webhook signature is invalid"),
+        }
+    }
+}
+
+impl From<reqwest::Error> for CheckoutError {
+    fn from(e: reqwest::Error) -> Self {
+        CheckoutError::Request(e)
+    }
+}
+
+//the parts of a checkout session we use
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct CheckoutSession {
+    pub(crate) id: String,
+    pub(crate) url: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct CheckoutProvider {
+    //None when online payments aren't configured
+    config: Option<std::sync::Arc<CheckoutConfig>>,
+    client: reqwest::Client,
+}
+
+impl CheckoutProvider {
+    pub(crate) fn new(config: Option<CheckoutConfig>) -> Self {
+        CheckoutProvider {
+            config: config.map(std::sync::Arc::new),
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("reqwest client builds"),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    //a hosted page for paying one amount, the invoice_id comes back in the webhook
+    pub(crate) async fn create_session(
+        &self,
+        invoice_id: &str,
+        description: &str,
+        amount: Decimal,
+        currency: &Currency,
+        email: Option<&str>,
+        //the client comes back here once they've paid or given up
+        site_url: &str,
+    ) -> Result<CheckoutSession, CheckoutError> {
+        let Some(config) = &self.config else {
+            return Err(CheckoutError::Disabled);
+        };
+        let mut form = vec![
+            ("mode", "payment".to_string()),
+            (
+                "success_url",
+                format!(
+                    "{}/payment/success?session_id={{CHECKOUT_SESSION_ID}}",
+                    site_url
+                ),
+            ),
+            ("cancel_url", format!("{}/payment/cancelled", site_url)),
+            ("client_reference_id", invoice_id.to_string()),
+            ("metadata[invoice_id]", invoice_id.to_string()),
+            ("line_items[0][quantity]", "1".to_string()),
+            (
+                "line_items[0][price_data][currency]",
+                currency.code.to_lowercase(),
+            ),
+            (
+                "line_items[0][price_data][unit_amount]",
+                currency
+                    .to_minor_units(amount)
+                    .expect("amount fits in i64")
+                    .to_string(),
+            ),
+            (
+                "line_items[0][price_data][product_data][name]",
+                description.to_string(),
+            ),
+        ];
+        if let Some(email) = email {
+            form.push(("customer_email", email.to_string()));
+        }
+        let response = self
+            .client
+            .post(format!("{}/v1/checkout/sessions", config.api_url))
+            .basic_auth(&config.secret_key, None::<&str>)
+            .form(&form)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(CheckoutError::Rejected(status, response.text().await?));
+        }
+        Ok(response.json().await?)
+    }
+
+    pub(crate) fn verify_webhook(&self, header: &str, payload: &[u8]) -> Result<(), CheckoutError> {
+        let Some(config) = &self.config else {
+            return Err(CheckoutError::Disabled);
+        };
+        verify_signature(
+            &config.webhook_secret,
+            header,
+            payload,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+//checks a "t=timestamp,v1=signature" header, the signature is an HMAC-SHA256 of "timestamp.payload"
+pub(crate) fn verify_signature(
+    secret: &str,
+    header: &str,
+    payload: &[u8],
+    now: i64,
+) -> Result<(), CheckoutError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.extend(decode_hex(value)),
+            _ => {}
+        }
+    }
+    let Some(timestamp) = timestamp else {
+        return Err(CheckoutError::BadSignature);
+    };
+    if (now - timestamp).abs() > WEBHOOK_TOLERANCE_SECONDS {
+        return Err(CheckoutError::BadSignature);
+    }
+    //there can be more than one v1 signature while the secret is being rolled
+    let valid = signatures.iter().any(|signature| {
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload);
+        mac.verify_slice(signature).is_ok()
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(CheckoutError::BadSignature)
+    }
+}
+
+fn checkout_error(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    (status, Json(ApiResponse { message }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CheckoutLink {
+    session_id: String,
+    url: String,
+    amount: Decimal,
+}
+
+//creates a checkout link for what's left to pay on an invoice
+pub(crate) async fn create_checkout_link(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+) -> Result<Json<CheckoutLink>, (StatusCode, Json<ApiResponse>)> {
+    if !state.checkout.is_enabled() {
+        return Err(checkout_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Online payments aren't configured".to_string(),
+        ));
+    }
+    let internal_error = |e: sqlx::Error| {
+        checkout_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error creating checkout link: {}", e),
+        )
+    };
+    let invoice = sqlx::query!(
+        r#"SELECT i.invoice_number, i.amount_total, i.issued_at, i.voided_at, i.currency, c.email AS "email?"
+        FROM main.invoices i
+        LEFT JOIN main.clients c ON c.client_id = i.client_id
+        WHERE i.invoice_id = $1"#,
+        invoice_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(internal_error)?
+    .ok_or(checkout_error(
+        StatusCode::NOT_FOUND,
+        "Invoice not found".to_string(),
+    ))?;
+    if invoice.issued_at.is_none() || invoice.voided_at.is_some() {
+        return Err(checkout_error(
+            StatusCode::CONFLICT,
+            "Only issued invoices that aren't void can be paid online".to_string(),
+        ));
+    }
+    let payments = invoice_payments(&state.db_pool, &invoice_id)
+        .await
+        .map_err(internal_error)?;
+    let due = balance_due(
+        invoice.amount_total.unwrap_or_default(),
+        amount_paid(&payments),
+    );
+    if due <= Decimal::ZERO {
+        return Err(checkout_error(
+            StatusCode::CONFLICT,
+            "This invoice is already paid".to_string(),
+        ));
+    }
+
+    let currency = currency_or_default(&invoice.currency);
+    let session = state
+        .checkout
+        .create_session(
+            &invoice_id,
+            &format!("Invoice #{}", invoice.invoice_number),
+            due,
+            currency,
+            invoice.email.as_deref(),
+            &state.self_service.site_url,
+        )
+        .await
+        .map_err(|e| {
+            println!("Error creating checkout session: {}", e);
+            checkout_error(
+                StatusCode::BAD_GATEWAY,
+                "Error creating checkout link".to_string(),
+            )
+        })?;
+    sqlx::query!(
+        "INSERT INTO main.checkout_sessions (session_id, invoice_id, amount, url, currency) VALUES ($1, $2, $3, $4, $5)",
+        session.id,
+        invoice_id,
+        due,
+        session.url,
+        currency.code,
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(internal_error)?;
+    Ok(Json(CheckoutLink {
+        session_id: session.id,
+        url: session.url,
+        amount: due,
+    }))
+}
+
+#[derive(Deserialize)]
+struct WebhookEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: WebhookData,
+}
+
+#[derive(Deserialize)]
+struct WebhookData {
+    object: CompletedSession,
+}
+
+//the checkout session a checkout.session.* event is about
+#[derive(Deserialize)]
+struct CompletedSession {
+    id: String,
+    #[serde(default)]
+    payment_status: Option<String>,
+    #[serde(default)]
+    amount_total: Option<i64>,
+    #[serde(default)]
+    payment_intent: Option<String>,
+}
+
+//records the payment for a paid checkout session, a session that was already recorded is skipped
+async fn complete_session(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    session: &CompletedSession,
+) -> Result<(), sqlx::Error> {
+    let completed = sqlx::query!(
+        r#"UPDATE main.checkout_sessions SET completed_at = $1
+        WHERE session_id = $2 AND completed_at IS NULL
+        RETURNING invoice_id, amount, currency"#,
+        OffsetDateTime::now_utc(),
+        session.id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    let Some(completed) = completed else {
+        println!(
+            "checkout session {} is unknown or already recorded",
+            session.id
+        );
+        return Ok(());
+    };
+    let invoice = sqlx::query!(
+        "SELECT invoice_number, booking_id, voided_at FROM main.invoices WHERE invoice_id = $1",
+        completed.invoice_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    //the invoice was voided while the client was paying, the charge has to be refunded by hand
+    if invoice.voided_at.is_some() {
+        println!(
+            "checkout session {} was paid for void invoice #{}, not recording the payment, refund it",
+            session.id, invoice.invoice_number
+        );
+        if let Some(booking_id) = &invoice.booking_id {
+            record_activity(
+                &mut **tx,
+                booking_id,
+                ActivityKind::PaymentRecorded,
+                &format!(
+                    "Paid online on void invoice #{}, the payment wasn't recorded and needs refunding",
+                    invoice.invoice_number
+                ),
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+    //what was actually charged, falling back to what the session was created for
+    let currency = currency_or_default(&completed.currency);
+    let amount = session
+        .amount_total
+        .map(|minor_units| currency.amount_from_minor_units(minor_units))
+        .unwrap_or(completed.amount);
+    insert_payment(
+        tx,
+        &completed.invoice_id,
+        &NewPayment {
+            amount,
+            method: Some("card".to_string()),
+            paid_at: None,
+            reference: Some(
+                session
+                    .payment_intent
+                    .clone()
+                    .unwrap_or_else(|| session.id.clone()),
+            ),
+            notes: Some("Paid online".to_string()),
+        },
+    )
+    .await?;
+    if let Some(booking_id) = &invoice.booking_id {
+        record_activity(
+            &mut **tx,
+            booking_id,
+            ActivityKind::PaymentRecorded,
+            &format!(
+                "{} paid online on invoice #{}",
+                Money::new(currency.code, None).format(amount),
+                invoice.invoice_number
+            ),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+//payment provider webhook, an error status makes the provider retry the event later
+pub(crate) async fn payment_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let signature = headers
+        .get("Stripe-Signature")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if let Err(e) = state.checkout.verify_webhook(signature, &body) {
+        println!("Rejected payment webhook: {}", e);
+        return StatusCode::BAD_REQUEST;
+    }
+    //other event types have a different object, only the envelope is needed to skip them
+    let event: WebhookEvent = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(_) => return StatusCode::OK,
+    };
+    if !matches!(
+        event.kind.as_str(),
+        "checkout.session.completed" | "checkout.session.async_payment_succeeded"
+    ) || event.data.object.payment_status.as_deref() != Some("paid")
+    {
+        return StatusCode::OK;
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = state.db_pool.begin().await?;
+        //recorded in the same transaction as the payment, so a failed attempt can be retried
+        let first_delivery = sqlx::query!(
+            "INSERT INTO main.payment_events (event_id, kind) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            event.id,
+            event.kind,
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            == 1;
+        if first_delivery {
+            complete_session(&mut tx, &event.data.object).await?;
+        }
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            println!("Error handling payment webhook {}: {}", event.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("t={},v1={}", timestamp, signature)
+    }
+
+    #[test]
+    fn verifies_webhook_signatures() {
+        let payload = br#"{"id":"evt_1","type":"checkout.session.completed"}"#;
+        let now = 1_760_000_000;
+        let header = sign("whsec_test", now, payload);
+        assert!(verify_signature("whsec_test", &header, payload, now + 10).is_ok());
+        //wrong secret, changed body, or too old
+        assert!(verify_signature("whsec_other", &header, payload, now).is_err());
+        assert!(verify_signature("whsec_test", &header, b"{}", now).is_err());
+        assert!(verify_signature("whsec_test", &header, payload, now + 600).is_err());
+    }
+
+    #[tokio::test]
+    async fn creates_sessions_against_a_local_api() {
+        use axum::Form;
+        use axum::routing::post;
+        use std::collections::HashMap;
+        use tokio::net::TcpListener;
+        use tokio::sync::mpsc;
+
+        //stands in for the provider, hands back what it was sent
+        let (sent, mut received) = mpsc::unbounded_channel();
+        let app = axum::Router::new().route(
+            "/v1/checkout/sessions",
+            post(
+                move |headers: HeaderMap, Form(form): Form<HashMap<String, String>>| async move {
+                    sent.send((headers, form)).unwrap();
+                    Json(serde_json::json!({
+                        "id": "cs_test_1",
+                        "url": "https://checkout.example.com/c/pay/cs_test_1",
+                        "object": "checkout.session"
+                    }))
+                },
+            ),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let provider = CheckoutProvider::new(Some(CheckoutConfig {
+            secret_key: "sk_test".into(),
+            webhook_secret: "whsec_test".into(),
+            api_url,
+        }));
+        let session = provider
+            .create_session(
+                "inv_1",
+                "Invoice #12",
+                Decimal::new(12550, 2),
+                currency_or_default("USD"),
+                Some("client@example.com"),
+                "https://studio.example.com",
+            )
+            .await
+            .unwrap();
+        assert_eq!(session.id, "cs_test_1");
+        assert_eq!(session.url, "https://checkout.example.com/c/pay/cs_test_1");
+
+        let (headers, form) = received.recv().await.unwrap();
+        //basic auth with the secret key as the username
+        assert_eq!(headers["authorization"], "Basic c2tfdGVzdDo=");
+        assert_eq!(form["mode"], "payment");
+        assert_eq!(form["client_reference_id"], "inv_1");
+        assert_eq!(form["metadata[invoice_id]"], "inv_1");
+        assert_eq!(form["line_items[0][price_data][currency]"], "usd");
+        assert_eq!(form["line_items[0][price_data][unit_amount]"], "12550");
+        assert_eq!(
+            form["line_items[0][price_data][product_data][name]"],
+            "Invoice #12"
+        );
+        assert_eq!(form["customer_email"], "client@example.com");
+        assert_eq!(
+            form["success_url"],
+            "https://studio.example.com/payment/success?session_id={CHECKOUT_SESSION_ID}"
+        );
+        assert_eq!(
+            form["cancel_url"],
+            "https://studio.example.com/payment/cancelled"
+        );
+    }
+}
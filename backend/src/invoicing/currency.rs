@@ -0,0 +1,272 @@
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::http::StatusCode;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+//invoices without a currency are billed in this
+pub(crate) const DEFAULT_CURRENCY: &str = "USD";
+
+//an ISO 4217 currency the studio can bill in
+#[derive(Debug, PartialEq)]
+pub(crate) struct Currency {
+    pub(crate) code: &'static str,
+    //digits after the decimal point, 2 for cents, 0 for yen
+    pub(crate) minor_units: u32,
+    pub(crate) symbol: &'static str,
+}
+
+pub(crate) const CURRENCIES: [Currency; 10] = [
+    Currency {
+        code: "USD",
+        minor_units: 2,
+        symbol: "$",
+    },
+    Currency {
+        code: "CAD",
+        minor_units: 2,
+        symbol: "CA$",
+    },
+    Currency {
+        code: "AUD",
+        minor_units: 2,
+        symbol: "A$",
+    },
+    Currency {
+        code: "NZD",
+        minor_units: 2,
+        symbol: "NZ$",
+    },
+    Currency {
+        code: "MXN",
+        minor_units: 2,
+        symbol: "MX$",
+    },
+    Currency {
+        code: "EUR",
+        minor_units: 2,
+        symbol: "€",
+    },
+    Currency {
+        code: "GBP",
+        minor_units: 2,
+        symbol: "£",
+    },
+    Currency {
+        code: "CHF",
+        minor_units: 2,
+        symbol: "CHF",
+    },
+    Currency {
+        code: "SEK",
+        minor_units: 2,
+        symbol: "kr",
+    },
+    Currency {
+        code: "JPY",
+        minor_units: 0,
+        symbol: "¥",
+    },
+];
+
+//case insensitive, None for a currency we don't bill in
+pub(crate) fn find_currency(code: &str) -> Option<&'static Currency> {
+    CURRENCIES
+        .iter()
+        .find(|currency| currency.code.eq_ignore_ascii_case(code.trim()))
+}
+
+//the currency stored on an invoice, falling back to the default for anything unknown
+pub(crate) fn currency_or_default(code: &str) -> &'static Currency {
+    find_currency(code).unwrap_or(&CURRENCIES[0])
+}
+
+//the currency a new invoice or quote asked for, the default when none was given
+pub(crate) fn billing_currency(
+    code: Option<&str>,
+) -> Result<&'static Currency, (StatusCode, Json<ApiResponse>)> {
+    match code {
+        None => Ok(currency_or_default(DEFAULT_CURRENCY)),
+        Some(code) => find_currency(code).ok_or_else(|| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse {
+                    message: format!("Unsupported currency: {}", code),
+                }),
+            )
+        }),
+    }
+}
+
+impl Currency {
+    //rounded to the smallest unit the currency has
+    pub(crate) fn round(&self, amount: Decimal) -> Decimal {
+        amount.round_dp_with_strategy(self.minor_units, RoundingStrategy::MidpointAwayFromZero)
+    }
+
+    //whether an amount can be paid exactly, $1.005 or ¥10.5 can't
+    pub(crate) fn is_exact(&self, amount: Decimal) -> bool {
+        self.round(amount) == amount
+    }
+
+    //cents for dollars, yen for yen
+    pub(crate) fn to_minor_units(&self, amount: Decimal) -> Option<i64> {
+        use rust_decimal::prelude::ToPrimitive;
+        (self.round(amount) * Decimal::from(10i64.pow(self.minor_units))).to_i64()
+    }
+
+    pub(crate) fn amount_from_minor_units(&self, amount: i64) -> Decimal {
+        Decimal::new(amount, self.minor_units)
+    }
+}
+
+//how numbers are written where the client lives
+#[derive(Debug, PartialEq)]
+pub(crate) struct NumberFormat {
+    pub(crate) group: &'static str,
+    pub(crate) decimal: &'static str,
+    //"$1,234.50" or "1.234,50 €"
+    pub(crate) symbol_first: bool,
+}
+
+const ENGLISH: NumberFormat = NumberFormat {
+    group: ",",
+    decimal: ".",
+    symbol_first: true,
+};
+const CONTINENTAL: NumberFormat = NumberFormat {
+    group: ".",
+    decimal: ",",
+    symbol_first: false,
+};
+//French, Nordic and others, grouped with a narrow no-break space
+const SPACED: NumberFormat = NumberFormat {
+    group: "\u{202f}",
+    decimal: ",",
+    symbol_first: false,
+};
+const SWISS: NumberFormat = NumberFormat {
+    group: "’",
+    decimal: ".",
+    symbol_first: true,
+};
+
+//the number format for a client's country, anywhere not listed writes numbers the US way.
+//clients store the country name from the country picker, ISO codes are matched too
+pub(crate) fn number_format(country: Option<&str>) -> &'static NumberFormat {
+    match country.unwrap_or_default().trim().to_lowercase().as_str() {
+        "germany" | "austria" | "italy" | "spain" | "netherlands" | "belgium" | "portugal"
+        | "denmark" | "brazil" | "argentina" | "indonesia" | "turkey" | "türkiye" | "de" | "at"
+        | "it" | "es" | "nl" | "be" | "pt" | "dk" | "br" | "ar" | "id" | "tr" => &CONTINENTAL,
+        "france" | "sweden" | "norway" | "finland" | "poland" | "czechia" | "czech republic"
+        | "slovakia" | "hungary" | "russian federation" | "russia" | "ukraine" | "fr" | "se"
+        | "no" | "fi" | "pl" | "cz" | "sk" | "hu" | "ru" | "ua" => &SPACED,
+        "switzerland" | "liechtenstein" | "ch" | "li" => &SWISS,
+        _ => &ENGLISH,
+    }
+}
+
+//an amount rounded for the currency and written the way the client reads numbers
+pub(crate) fn format_money(amount: Decimal, currency: &Currency, format: &NumberFormat) -> String {
+    let amount = currency.round(amount);
+    let digits = format!("{:.*}", currency.minor_units as usize, amount.abs());
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push_str(format.group);
+        }
+        grouped.push(digit);
+    }
+    if !fraction.is_empty() {
+        grouped.push_str(format.decimal);
+        grouped.push_str(fraction);
+    }
+    let sign = if amount.is_sign_negative() && !amount.is_zero() {
+        "-"
+    } else {
+        ""
+    };
+    if format.symbol_first {
+        format!("{}{}{}", sign, currency.symbol, grouped)
+    } else {
+        format!("{}{} {}", sign, grouped, currency.symbol)
+    }
+}
+
+//formats every amount on one document
+pub(crate) struct Money {
+    pub(crate) currency: &'static Currency,
+    pub(crate) format: &'static NumberFormat,
+}
+
+impl Money {
+    pub(crate) fn new(currency: &str, country: Option<&str>) -> Self {
+        Money {
+            currency: currency_or_default(currency),
+            format: number_format(country),
+        }
+    }
+
+    pub(crate) fn format(&self, amount: Decimal) -> String {
+        format_money(amount, self.currency, self.format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_for_currency_and_country() {
+        let usd = find_currency("usd").unwrap();
+        let eur = find_currency("EUR").unwrap();
+        let jpy = find_currency("JPY").unwrap();
+        assert_eq!(
+            format_money(
+                Decimal::new(1234505, 3),
+                usd,
+                number_format(Some("United States"))
+            ),
+            "$1,234.51"
+        );
+        assert_eq!(
+            format_money(
+                Decimal::new(123456789, 2),
+                eur,
+                number_format(Some("Germany"))
+            ),
+            "1.234.567,89 €"
+        );
+        assert_eq!(
+            format_money(Decimal::new(12345, 1), jpy, number_format(None)),
+            "¥1,235"
+        );
+        assert_eq!(
+            format_money(
+                Decimal::new(-50, 0),
+                usd,
+                number_format(Some("United States"))
+            ),
+            "-$50.00"
+        );
+    }
+
+    #[test]
+    fn matches_country_names_and_codes() {
+        assert_eq!(number_format(Some("France")), &SPACED);
+        assert_eq!(number_format(Some("Switzerland")), &SWISS);
+        assert_eq!(number_format(Some(" germany ")), &CONTINENTAL);
+        assert_eq!(number_format(Some("DE")), &CONTINENTAL);
+        assert_eq!(number_format(Some("United Kingdom")), &ENGLISH);
+    }
+
+    #[test]
+    fn converts_minor_units() {
+        let usd = find_currency("USD").unwrap();
+        let jpy = find_currency("JPY").unwrap();
+        assert_eq!(usd.to_minor_units(Decimal::new(12345, 2)), Some(12345));
+        assert_eq!(jpy.to_minor_units(Decimal::from(500)), Some(500));
+        assert_eq!(jpy.amount_from_minor_units(500), Decimal::from(500));
+        assert!(!jpy.is_exact(Decimal::new(105, 1)));
+    }
+}
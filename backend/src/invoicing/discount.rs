@@ -0,0 +1,516 @@
+use crate::AppState;
+use crate::invoicing::currency::{Currency, billing_currency};
+use crate::invoicing::invoice::{ApiResponse, NewInvoiceItem};
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+
+//a percent or fixed amount off a line, an invoice or from a coupon
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Discount {
+    pub(crate) kind: DiscountKind,
+    pub(crate) value: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DiscountKind {
+    Percent,
+    Fixed,
+}
+
+impl DiscountKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            DiscountKind::Percent => "percent",
+            DiscountKind::Fixed => "fixed",
+        }
+    }
+}
+
+impl Discount {
+    //from the discount_kind and discount_value columns
+    pub(crate) fn from_columns(kind: Option<&str>, value: Option<Decimal>) -> Option<Self> {
+        let kind = match kind? {
+            "percent" => DiscountKind::Percent,
+            "fixed" => DiscountKind::Fixed,
+            _ => return None,
+        };
+        Some(Discount {
+            kind,
+            value: value?,
+        })
+    }
+
+    //the amount taken off, rounded for the currency and never more than the amount itself
+    pub(crate) fn amount_off(&self, amount: Decimal, currency: &Currency) -> Decimal {
+        let off = match self.kind {
+            DiscountKind::Percent => currency.round(amount * self.value / Decimal::from(100)),
+            DiscountKind::Fixed => currency.round(self.value),
+        };
+        off.min(amount).max(Decimal::ZERO)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), &'static str> {
+        if self.value.is_sign_negative() {
+            return Err("Discounts can't be negative");
+        }
+        if self.kind == DiscountKind::Percent && self.value > Decimal::from(100) {
+            return Err("A percent discount can't be more than 100");
+        }
+        Ok(())
+    }
+}
+
+//a line's price after its discount
+pub(crate) fn line_total(
+    unit_price: Decimal,
+    quantity: i32,
+    discount: Option<&Discount>,
+    currency: &Currency,
+) -> Decimal {
+    let amount = currency.round(unit_price * Decimal::from(quantity));
+    amount
+        - discount.map_or(Decimal::ZERO, |discount| {
+            discount.amount_off(amount, currency)
+        })
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Totals {
+    //after line discounts
+    pub(crate) subtotal: Decimal,
+    //invoice discount and coupon together
+    pub(crate) discount: Decimal,
+    //what tax is charged on, the taxable lines less their share of the invoice discount
+    pub(crate) taxable: Decimal,
+}
+
+//applies line discounts, then the invoice discount, then the coupon on what's left. late fee lines
+//are added after, so they're never discounted
+pub(crate) fn totals(
+    items: &[NewInvoiceItem],
+    invoice_discounts: &[&Discount],
+    currency: &Currency,
+) -> Totals {
+    let mut subtotal = Decimal::ZERO;
+    //what the invoice discount and coupon come off
+    let mut discountable = Decimal::ZERO;
+    let mut taxable = Decimal::ZERO;
+    let mut taxable_fees = Decimal::ZERO;
+    for item in items {
+        let total = line_total(
+            item.unit_price,
+            item.quantity,
+            item.discount.as_ref(),
+            currency,
+        );
+        subtotal += total;
+        match (item.late_fee, item.taxable) {
+            (true, true) => taxable_fees += total,
+            (true, false) => {}
+            (false, taxable_line) => {
+                discountable += total;
+                if taxable_line {
+                    taxable += total;
+                }
+            }
+        }
+    }
+    let mut discount = Decimal::ZERO;
+    for invoice_discount in invoice_discounts {
+        discount += invoice_discount.amount_off(discountable - discount, currency);
+    }
+    //spread the invoice discount across the lines so untaxed lines get their share
+    if discountable > Decimal::ZERO {
+        taxable = currency.round(taxable * (discountable - discount) / discountable);
+    }
+    Totals {
+        subtotal,
+        discount,
+        taxable: taxable + taxable_fees,
+    }
+}
+
+pub(crate) fn check_line_discounts(items: &[NewInvoiceItem]) -> Result<(), &'static str> {
+    items
+        .iter()
+        .filter_map(|item| item.discount.as_ref())
+        .try_for_each(Discount::check)
+}
+
+//the invoice discount and coupon on an invoice being saved
+pub(crate) struct InvoiceDiscounts {
+    pub(crate) discount: Option<Discount>,
+    pub(crate) coupon_code: Option<String>,
+    coupon: Option<Discount>,
+}
+
+impl InvoiceDiscounts {
+    //in the order totals applies them
+    pub(crate) fn in_order(&self) -> Vec<&Discount> {
+        self.discount.iter().chain(self.coupon.iter()).collect()
+    }
+}
+
+//checks the line and invoice discounts and redeems the coupon for an invoice being created or edited
+pub(crate) async fn invoice_discounts(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+    items: &[NewInvoiceItem],
+    discount: Option<Discount>,
+    coupon_code: Option<&str>,
+    currency: &Currency,
+) -> Result<InvoiceDiscounts, (StatusCode, Json<ApiResponse>)> {
+    let checked = check_line_discounts(items).and_then(|_| match &discount {
+        Some(discount) => discount.check(),
+        None => Ok(()),
+    });
+    if let Err(message) = checked {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                message: message.to_string(),
+            }),
+        ));
+    }
+    let coupon_code = coupon_code
+        .map(normalize_code)
+        .filter(|code| !code.is_empty());
+    let coupon = match &coupon_code {
+        Some(code) => Some(redeem_coupon(tx, code, invoice_id, currency).await?),
+        None => None,
+    };
+    Ok(InvoiceDiscounts {
+        discount,
+        coupon_code,
+        coupon,
+    })
+}
+
+//a reusable code clients can be given
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Coupon {
+    pub(crate) code: String,
+    pub(crate) discount_kind: String,
+    pub(crate) discount_value: Decimal,
+    //None for unlimited
+    pub(crate) max_uses: Option<i32>,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub(crate) expires_at: Option<OffsetDateTime>,
+    pub(crate) active: bool,
+    //what a fixed coupon is in, None for percent coupons
+    pub(crate) currency: Option<String>,
+    //invoices that aren't void using it
+    pub(crate) times_used: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCoupon {
+    code: String,
+    discount: Discount,
+    max_uses: Option<i32>,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    expires_at: Option<OffsetDateTime>,
+    #[serde(default = "default_active")]
+    active: bool,
+    //ISO 4217 code of a fixed discount, defaults to USD. ignored for percent coupons
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+pub(crate) fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+//looks up a coupon for an invoice and checks it can still be used, the coupon is locked until the
+//transaction ends so two invoices can't both take its last use. an invoice keeps a coupon it already has,
+//but a fixed coupon never applies to an invoice in another currency
+pub(crate) async fn redeem_coupon(
+    tx: &mut Transaction<'_, Postgres>,
+    code: &str,
+    invoice_id: &str,
+    currency: &Currency,
+) -> Result<Discount, (StatusCode, Json<ApiResponse>)> {
+    let rejected = |message: &str| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                message: message.to_string(),
+            }),
+        )
+    };
+    let internal_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error checking coupon: {}", e),
+            }),
+        )
+    };
+    let coupon = sqlx::query!(
+        r#"SELECT discount_kind, discount_value, max_uses, expires_at, active, currency
+        FROM main.coupons WHERE code = $1 FOR UPDATE"#,
+        code
+    )
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or_else(|| rejected("Unknown coupon code"))?;
+    if let Some(coupon_currency) = &coupon.currency
+        && *coupon_currency != currency.code
+    {
+        return Err(rejected(&format!(
+            "This coupon can only be used on {} invoices",
+            coupon_currency
+        )));
+    }
+    let already_applied = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM main.invoices WHERE invoice_id = $1 AND coupon_code = $2) AS "exists!""#,
+        invoice_id,
+        code
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+    if !already_applied {
+        if !coupon.active {
+            return Err(rejected("This coupon is no longer available"));
+        }
+        if coupon
+            .expires_at
+            .is_some_and(|expires_at| OffsetDateTime::now_utc() > expires_at)
+        {
+            return Err(rejected("This coupon has expired"));
+        }
+        if let Some(max_uses) = coupon.max_uses {
+            let times_used = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!" FROM main.invoices WHERE coupon_code = $1 AND voided_at IS NULL"#,
+                code
+            )
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(internal_error)?;
+            if times_used >= i64::from(max_uses) {
+                return Err(rejected("This coupon has been used up"));
+            }
+        }
+    }
+    Discount::from_columns(Some(&coupon.discount_kind), Some(coupon.discount_value))
+        .ok_or_else(|| rejected("Unknown coupon code"))
+}
+
+pub async fn get_coupons(State(state): State<AppState>) -> Result<Json<Vec<Coupon>>, StatusCode> {
+    sqlx::query_as!(
+        Coupon,
+        r#"SELECT c.code, c.discount_kind, c.discount_value, c.max_uses, c.expires_at, c.active, c.currency,
+            (SELECT COUNT(*) FROM main.invoices i WHERE i.coupon_code = c.code AND i.voided_at IS NULL) AS "times_used!"
+        FROM main.coupons c ORDER BY c.created_at DESC"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        println!("Error getting coupons: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+//adds a coupon or replaces one with the same code, invoices that already used it keep their discount
+pub async fn save_coupon(
+    State(state): State<AppState>,
+    Json(payload): Json<NewCoupon>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let code = normalize_code(&payload.code);
+    let mut errors = ValidationErrors::new();
+    if code.is_empty() {
+        errors.add("code", "Code is required");
+    }
+    if let Err(message) = payload.discount.check() {
+        errors.add("discount", message);
+    }
+    if payload.max_uses.is_some_and(|max_uses| max_uses < 1) {
+        errors.add("max_uses", "Max uses must be at least 1");
+    }
+    let currency = match payload.discount.kind {
+        DiscountKind::Percent => None,
+        DiscountKind::Fixed => match billing_currency(payload.currency.as_deref()) {
+            Ok(currency) => Some(currency.code),
+            Err(_) => {
+                errors.add("currency", "Unsupported currency");
+                None
+            }
+        },
+    };
+    errors.finish(()).map_err(|errors| errors.into_response())?;
+    sqlx::query!(
+        r#"INSERT INTO main.coupons (code, discount_kind, discount_value, max_uses, expires_at, active, currency)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (code) DO UPDATE SET discount_kind = $2, discount_value = $3, max_uses = $4,
+            expires_at = $5, active = $6, currency = $7"#,
+        code,
+        payload.discount.kind.as_str(),
+        payload.discount.value,
+        payload.max_uses,
+        payload.expires_at,
+        payload.active,
+        currency,
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error saving coupon: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: format!("Coupon {} saved", code),
+        }),
+    ))
+}
+
+pub async fn delete_coupon(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let deleted = sqlx::query!(
+        "DELETE FROM main.coupons WHERE code = $1",
+        normalize_code(&code)
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error deleting coupon: {}", e),
+            }),
+        )
+    })?;
+    if deleted.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "Coupon not found".to_string(),
+            }),
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoicing::currency::CURRENCIES;
+
+    fn item(
+        unit_price: i64,
+        quantity: i32,
+        taxable: bool,
+        discount: Option<Discount>,
+    ) -> NewInvoiceItem {
+        NewInvoiceItem {
+            description: "item".to_string(),
+            quantity,
+            unit_price: Decimal::from(unit_price),
+            taxable,
+            discount,
+            late_fee: false,
+        }
+    }
+
+    #[test]
+    fn applies_line_then_invoice_discounts() {
+        let items = [
+            //$100 less 10%
+            item(
+                50,
+                2,
+                true,
+                Some(Discount {
+                    kind: DiscountKind::Percent,
+                    value: Decimal::from(10),
+                }),
+            ),
+            //$100, not taxed
+            item(100, 1, false, None),
+        ];
+        let invoice_discount = Discount {
+            kind: DiscountKind::Fixed,
+            value: Decimal::from(19),
+        };
+        let coupon = Discount {
+            kind: DiscountKind::Percent,
+            value: Decimal::from(10),
+        };
+        let totals = totals(&items, &[&invoice_discount, &coupon], &CURRENCIES[0]);
+        assert_eq!(totals.subtotal, Decimal::from(190));
+        //$19 off, then 10% of the $171 left
+        assert_eq!(totals.discount, Decimal::new(3610, 2));
+        //the taxed line is 90 of the 190, so it takes that share of the discount
+        assert_eq!(totals.taxable, Decimal::new(7290, 2));
+    }
+
+    #[test]
+    fn fixed_discount_never_goes_below_zero() {
+        let discount = Discount {
+            kind: DiscountKind::Fixed,
+            value: Decimal::from(50),
+        };
+        assert_eq!(
+            line_total(Decimal::from(20), 1, Some(&discount), &CURRENCIES[0]),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn rounds_fixed_discounts_for_the_currency() {
+        let discount = Discount {
+            kind: DiscountKind::Fixed,
+            value: Decimal::new(12345, 3),
+        };
+        //$12.345 off is $12.35
+        assert_eq!(
+            discount.amount_off(Decimal::from(100), &CURRENCIES[0]),
+            Decimal::new(1235, 2)
+        );
+        //no fractional yen
+        let jpy = crate::invoicing::currency::find_currency("JPY").unwrap();
+        assert_eq!(
+            discount.amount_off(Decimal::from(100), jpy),
+            Decimal::from(12)
+        );
+    }
+
+    #[test]
+    fn leaves_late_fees_out_of_invoice_discounts() {
+        let mut fee = item(30, 1, false, None);
+        fee.late_fee = true;
+        let items = [item(100, 1, true, None), fee];
+        let half_off = Discount {
+            kind: DiscountKind::Percent,
+            value: Decimal::from(50),
+        };
+        let totals = totals(&items, &[&half_off], &CURRENCIES[0]);
+        assert_eq!(totals.subtotal, Decimal::from(130));
+        //half of the $100, none of the fee
+        assert_eq!(totals.discount, Decimal::from(50));
+        assert_eq!(totals.taxable, Decimal::from(50));
+    }
+}
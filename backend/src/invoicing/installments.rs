@@ -0,0 +1,410 @@
+use crate::AppState;
+use crate::invoicing::currency::{Money, currency_or_default};
+use crate::invoicing::invoice::ApiResponse;
+use crate::invoicing::payments::{amount_paid, invoice_payments};
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use time::OffsetDateTime;
+
+//one due amount in an invoice's payment plan
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Installment {
+    pub(crate) installment_id: i64,
+    pub(crate) invoice_id: String,
+    //"Retainer", "Final payment"
+    pub(crate) label: String,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) due_date: OffsetDateTime,
+    pub(crate) amount: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewInstallment {
+    label: String,
+    #[serde(with = "time::serde::iso8601")]
+    due_date: OffsetDateTime,
+    amount: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InstallmentSchedule {
+    //replaces the whole schedule, an empty list removes it
+    installments: Vec<NewInstallment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InstallmentState {
+    Paid,
+    PartiallyPaid,
+    Upcoming,
+    //past its due date and not fully paid
+    Overdue,
+}
+
+impl InstallmentState {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            InstallmentState::Paid => "paid",
+            InstallmentState::PartiallyPaid => "partially_paid",
+            InstallmentState::Upcoming => "upcoming",
+            InstallmentState::Overdue => "overdue",
+        }
+    }
+}
+
+//an installment with what's been paid towards it
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct InstallmentStatus {
+    #[serde(flatten)]
+    pub(crate) installment: Installment,
+    pub(crate) amount_paid: Decimal,
+    pub(crate) balance: Decimal,
+    pub(crate) status: InstallmentState,
+}
+
+//an invoice's installments, earliest due first
+pub(crate) async fn invoice_installments<'e>(
+    executor: impl PgExecutor<'e>,
+    invoice_id: &str,
+) -> Result<Vec<Installment>, sqlx::Error> {
+    sqlx::query_as!(
+        Installment,
+        r#"SELECT installment_id, invoice_id, label, due_date, amount
+        FROM main.installments WHERE invoice_id = $1 ORDER BY due_date, installment_id"#,
+        invoice_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+//applies everything paid on the invoice to its installments, earliest due first, so a deposit
+//covers the retainer before it counts towards the final payment
+pub(crate) fn installment_statuses(
+    installments: Vec<Installment>,
+    paid: Decimal,
+    now: OffsetDateTime,
+) -> Vec<InstallmentStatus> {
+    let mut remaining = paid.max(Decimal::ZERO);
+    installments
+        .into_iter()
+        .map(|installment| {
+            let amount_paid = remaining.min(installment.amount);
+            remaining -= amount_paid;
+            let balance = installment.amount - amount_paid;
+            let status = if balance <= Decimal::ZERO {
+                InstallmentState::Paid
+            } else if installment.due_date < now {
+                InstallmentState::Overdue
+            } else if amount_paid > Decimal::ZERO {
+                InstallmentState::PartiallyPaid
+            } else {
+                InstallmentState::Upcoming
+            };
+            InstallmentStatus {
+                installment,
+                amount_paid,
+                balance,
+                status,
+            }
+        })
+        .collect()
+}
+
+//keeps a schedule adding up to the invoice after its total changes, the last installment takes
+//the difference. Err when that would leave it at zero or less
+pub(crate) async fn fit_installments(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+) -> Result<(), (StatusCode, Json<ApiResponse>)> {
+    let internal_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error updating installments: {}", e),
+            }),
+        )
+    };
+    let installments = invoice_installments(&mut **tx, invoice_id)
+        .await
+        .map_err(internal_error)?;
+    let Some(last) = installments.last() else {
+        return Ok(());
+    };
+    let total = sqlx::query_scalar!(
+        "SELECT amount_total FROM main.invoices WHERE invoice_id = $1",
+        invoice_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(internal_error)?
+    .unwrap_or_default();
+    let scheduled: Decimal = installments
+        .iter()
+        .map(|installment| installment.amount)
+        .sum();
+    if scheduled == total {
+        return Ok(());
+    }
+    let last_amount = last.amount + total - scheduled;
+    if last_amount <= Decimal::ZERO {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                message: "The new total is less than the installments before the last one, update the installment schedule first".to_string(),
+            }),
+        ));
+    }
+    sqlx::query!(
+        "UPDATE main.installments SET amount = $1 WHERE installment_id = $2",
+        last_amount,
+        last.installment_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(internal_error)?;
+    Ok(())
+}
+
+pub(crate) async fn get_installments(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+) -> Result<Json<Vec<InstallmentStatus>>, StatusCode> {
+    let internal_error = |e: sqlx::Error| {
+        println!("Error getting installments: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let installments = invoice_installments(&state.db_pool, &invoice_id)
+        .await
+        .map_err(internal_error)?;
+    let payments = invoice_payments(&state.db_pool, &invoice_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(installment_statuses(
+        installments,
+        amount_paid(&payments),
+        OffsetDateTime::now_utc(),
+    )))
+}
+
+//sets the payment plan for an invoice, the installments have to add up to its total
+pub(crate) async fn set_installments(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+    Json(payload): Json<InstallmentSchedule>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    let rejected = |message: String| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse { message }),
+        )
+    };
+    let internal_error = |e: sqlx::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error saving installments: {}", e),
+            }),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let invoice = sqlx::query!(
+        r#"SELECT amount_total, voided_at, currency FROM main.invoices
+        WHERE invoice_id = $1 FOR UPDATE"#,
+        invoice_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ApiResponse {
+            message: "Invoice not found".to_string(),
+        }),
+    ))?;
+    if invoice.voided_at.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                message: "A voided invoice can't have a payment plan".to_string(),
+            }),
+        ));
+    }
+    let currency = currency_or_default(&invoice.currency);
+    for installment in &payload.installments {
+        if installment.label.trim().is_empty() {
+            return Err(rejected("Every installment needs a label".to_string()));
+        }
+        if installment.amount <= Decimal::ZERO || !currency.is_exact(installment.amount) {
+            return Err(rejected(format!(
+                "{} must be more than zero and a whole amount of {}",
+                installment.label, currency.code
+            )));
+        }
+    }
+    let total = invoice.amount_total.unwrap_or_default();
+    let scheduled: Decimal = payload
+        .installments
+        .iter()
+        .map(|installment| installment.amount)
+        .sum();
+    if !payload.installments.is_empty() && scheduled != total {
+        let money = Money::new(currency.code, None);
+        return Err(rejected(format!(
+            "Installments add up to {} but the invoice total is {}",
+            money.format(scheduled),
+            money.format(total)
+        )));
+    }
+
+    sqlx::query!(
+        "DELETE FROM main.installments WHERE invoice_id = $1",
+        invoice_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    for installment in &payload.installments {
+        sqlx::query!(
+            "INSERT INTO main.installments (invoice_id, label, due_date, amount) VALUES ($1, $2, $3, $4)",
+            invoice_id,
+            installment.label.trim(),
+            installment.due_date,
+            installment.amount,
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(internal_error)?;
+    }
+    tx.commit().await.map_err(internal_error)?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Installments saved".to_string(),
+        }),
+    ))
+}
+
+//an installment past due on an issued invoice
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OverdueInstallment {
+    pub(crate) invoice_number: i64,
+    pub(crate) client_id: Option<String>,
+    pub(crate) currency: String,
+    #[serde(flatten)]
+    pub(crate) installment: InstallmentStatus,
+}
+
+//every overdue installment on invoices that are issued, not void and not fully paid
+pub(crate) async fn find_overdue_installments(
+    client: &sqlx::PgPool,
+    now: OffsetDateTime,
+) -> Result<Vec<OverdueInstallment>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT ins.installment_id, ins.invoice_id, ins.label, ins.due_date, ins.amount,
+            i.invoice_number, i.client_id, i.currency,
+            (SELECT COALESCE(SUM(p.amount), 0) FROM main.payments p WHERE p.invoice_id = i.invoice_id) AS "paid!"
+        FROM main.installments ins
+        JOIN main.invoices i ON i.invoice_id = ins.invoice_id
+        WHERE i.issued_at IS NOT NULL AND i.voided_at IS NULL AND NOT i.payment_completed
+            AND EXISTS (SELECT 1 FROM main.installments due
+                WHERE due.invoice_id = i.invoice_id AND due.due_date < $1)
+        ORDER BY i.invoice_number, ins.due_date, ins.installment_id"#,
+        now
+    )
+    .fetch_all(client)
+    .await?;
+
+    //rows come grouped by invoice, each group is worked out like a single invoice's schedule
+    let mut overdue = Vec::new();
+    let mut rows = rows.into_iter().peekable();
+    while let Some(first) = rows.next() {
+        let mut group = vec![first];
+        while let Some(row) = rows.next_if(|row| row.invoice_id == group[0].invoice_id) {
+            group.push(row);
+        }
+        let invoice = &group[0];
+        let (invoice_number, client_id, currency, paid) = (
+            invoice.invoice_number,
+            invoice.client_id.clone(),
+            invoice.currency.clone(),
+            invoice.paid,
+        );
+        let installments = group
+            .into_iter()
+            .map(|row| Installment {
+                installment_id: row.installment_id,
+                invoice_id: row.invoice_id,
+                label: row.label,
+                due_date: row.due_date,
+                amount: row.amount,
+            })
+            .collect();
+        overdue.extend(
+            installment_statuses(installments, paid, now)
+                .into_iter()
+                .filter(|status| status.status == InstallmentState::Overdue)
+                .map(|installment| OverdueInstallment {
+                    invoice_number,
+                    client_id: client_id.clone(),
+                    currency: currency.clone(),
+                    installment,
+                }),
+        );
+    }
+    Ok(overdue)
+}
+
+pub(crate) async fn get_overdue_installments(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<OverdueInstallment>>, StatusCode> {
+    find_overdue_installments(&state.db_pool, OffsetDateTime::now_utc())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error getting overdue installments: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn installment(installment_id: i64, due_date: OffsetDateTime, amount: i64) -> Installment {
+        Installment {
+            installment_id,
+            invoice_id: "abc123".to_string(),
+            label: format!("Installment {}", installment_id),
+            due_date,
+            amount: Decimal::from(amount),
+        }
+    }
+
+    #[test]
+    fn payments_cover_the_earliest_installments_first() {
+        let now = datetime!(2026-06-15 12:00 UTC);
+        let schedule = vec![
+            installment(1, datetime!(2026-03-01 12:00 UTC), 500),
+            installment(2, datetime!(2026-06-01 12:00 UTC), 1000),
+            installment(3, datetime!(2026-09-01 12:00 UTC), 1000),
+        ];
+        let statuses = installment_statuses(schedule.clone(), Decimal::from(800), now);
+        assert_eq!(statuses[0].status, InstallmentState::Paid);
+        //the midpoint payment is past due with $300 of $1000 paid
+        assert_eq!(statuses[1].status, InstallmentState::Overdue);
+        assert_eq!(statuses[1].balance, Decimal::from(700));
+        assert_eq!(statuses[2].status, InstallmentState::Upcoming);
+
+        //partly paid but not due yet
+        let statuses = installment_statuses(schedule, Decimal::from(1800), now);
+        assert_eq!(statuses[1].status, InstallmentState::Paid);
+        assert_eq!(statuses[2].status, InstallmentState::PartiallyPaid);
+        assert_eq!(statuses[2].amount_paid, Decimal::from(300));
+    }
+}
@@ -1,23 +1,34 @@
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::catalogue::{LineItemPreset, preset_line_items};
 use crate::clientele::{Client, client_exists};
+use crate::invoicing::currency::{billing_currency, currency_or_default};
+use crate::invoicing::discount::{Discount, Totals, invoice_discounts, totals};
+use crate::invoicing::installments::{
+    InstallmentStatus, fit_installments, installment_statuses, invoice_installments,
+};
+use crate::invoicing::payments::{
+    Payment, amount_paid, balance_due, invoice_payments, sync_payment_status,
+};
+use crate::invoicing::tax::{Tax, calculate_tax};
 use crate::{AppState, booking, clientele, invoicing};
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::{Json, debug_handler};
 use rust_decimal::Decimal;
-use rust_decimal::prelude::Zero;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::{Postgres, Transaction};
 use time::OffsetDateTime;
 
-#[derive(Serialize, Deserialize)]
-pub struct InvoiceID {
-    invoice_id: String,
-}
 #[derive(Serialize, Deserialize)]
 pub struct ReturnFullInvoice {
     pub(crate) invoice: Invoice,
     pub(crate) invoice_items: Vec<InvoiceItem>,
     pub(crate) client: Client,
+    pub(crate) payments: Vec<Payment>,
+    pub(crate) amount_paid: Decimal,
+    pub(crate) balance_due: Decimal,
+    //the payment plan, empty when the invoice is due in one payment
+    pub(crate) installments: Vec<InstallmentStatus>,
 }
 #[derive(Serialize, Deserialize)]
 pub(crate) struct InvoiceItem {
@@ -26,12 +37,37 @@ pub(crate) struct InvoiceItem {
     pub(crate) description: String,
     pub(crate) quantity: i32,
     pub(crate) unit_price: Decimal,
+    pub(crate) taxable: bool,
+    //percent or fixed, see InvoiceItem::discount
+    pub(crate) discount_kind: Option<String>,
+    pub(crate) discount_value: Option<Decimal>,
+    //added by the overdue sweep, see NewInvoiceItem::late_fee
+    pub(crate) late_fee: bool,
+}
+
+impl InvoiceItem {
+    pub(crate) fn discount(&self) -> Option<Discount> {
+        Discount::from_columns(self.discount_kind.as_deref(), self.discount_value)
+    }
 }
 #[derive(Serialize, Deserialize)]
-struct NewInvoiceItem {
-    description: String,
-    quantity: i32,
-    unit_price: Decimal,
+pub(crate) struct NewInvoiceItem {
+    pub(crate) description: String,
+    pub(crate) quantity: i32,
+    pub(crate) unit_price: Decimal,
+    //lines are taxed unless marked otherwise, prints are exempt in some states
+    #[serde(default = "default_taxable")]
+    pub(crate) taxable: bool,
+    #[serde(default)]
+    pub(crate) discount: Option<Discount>,
+    //a late fee or interest line. these aren't discounted by the invoice discount or coupon
+    //and interest isn't charged on them
+    #[serde(default)]
+    pub(crate) late_fee: bool,
+}
+
+pub(crate) fn default_taxable() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize)]
@@ -41,24 +77,49 @@ pub struct ApiResponse {
 
 #[derive(Serialize, Deserialize)]
 pub struct NewInvoiceInfo {
-    client_id: Option<String>,
-    booking_id: Option<String>,
-    invoice_items: Vec<NewInvoiceItem>,
-    amount_tax: Option<Decimal>,
-    notes: Option<String>,
+    pub(crate) client_id: Option<String>,
+    pub(crate) booking_id: Option<String>,
+    pub(crate) invoice_items: Vec<NewInvoiceItem>,
+    //catalogue items added as extra lines
+    #[serde(default)]
+    pub(crate) presets: Vec<LineItemPreset>,
+    //off the whole invoice, after line discounts
+    #[serde(default)]
+    pub(crate) discount: Option<Discount>,
+    #[serde(default)]
+    pub(crate) coupon_code: Option<String>,
+    //overrides the tax calculated from the client's address, null or left out calculates it
+    #[serde(default)]
+    pub(crate) amount_tax: Option<Decimal>,
+    pub(crate) notes: Option<String>,
     #[serde(with = "time::serde::iso8601")]
-    due_date: OffsetDateTime,
-    address_street: Option<String>,
-    address_city: Option<String>,
-    address_state: Option<StateCountry>,
-    address_zip: Option<String>,
-    address_country: StateCountry,
+    pub(crate) due_date: OffsetDateTime,
+    //issue right away instead of saving a draft
+    #[serde(default)]
+    pub(crate) issue: bool,
+    //ISO 4217 code, defaults to USD
+    #[serde(default)]
+    pub(crate) currency: Option<String>,
+    pub(crate) address_street: Option<String>,
+    pub(crate) address_city: Option<String>,
+    pub(crate) address_state: Option<StateCountry>,
+    pub(crate) address_zip: Option<String>,
+    pub(crate) address_country: StateCountry,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct EditInvoiceInfo {
     client_id: String,
     invoice_items: Vec<NewInvoiceItem>,
+    //left out keeps the current discount, null removes it
+    #[serde(default, deserialize_with = "present")]
+    discount: Option<Option<Discount>>,
+    //an invoice keeps its coupon on edit even if it has since expired or been used up.
+    //left out keeps the current coupon, null removes it
+    #[serde(default, deserialize_with = "present")]
+    coupon_code: Option<Option<String>>,
+    //overrides the tax calculated from the client's address, null or left out calculates it
+    #[serde(default)]
     amount_tax: Option<Decimal>,
     notes: Option<String>,
     #[serde(with = "time::serde::iso8601")]
@@ -68,9 +129,19 @@ pub struct EditInvoiceInfo {
     address_state: Option<StateCountry>,
     address_zip: Option<String>,
     address_country: StateCountry,
-    payment_completed: bool,
-    #[serde(default, with = "time::serde::iso8601::option")]
-    paid_at: Option<OffsetDateTime>,
+    //only while there are no payments, defaults to keeping the current one
+    #[serde(default)]
+    currency: Option<String>,
+    //payment_completed and paid_at come from the payments ledger, record a payment instead
+}
+
+//tells a field that was left out (None) apart from one sent as null (Some(None))
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
 }
 
 //Invoice from POSTGRES database
@@ -92,6 +163,36 @@ pub struct Invoice {
     #[serde(default, with = "time::serde::iso8601::option")]
     pub(crate) paid_at: Option<OffsetDateTime>,
     pub(crate) invoice_number: i64,
+    //None while the invoice is a draft
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub(crate) issued_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub(crate) voided_at: Option<OffsetDateTime>,
+    pub(crate) void_reason: Option<String>,
+    //the rate amount_tax was calculated with, None when it was entered by hand
+    pub(crate) tax_rate: Option<Decimal>,
+    //the invoice discount, see Invoice::discount
+    pub(crate) discount_kind: Option<String>,
+    pub(crate) discount_value: Option<Decimal>,
+    pub(crate) coupon_code: Option<String>,
+    //the invoice discount and coupon together, line discounts are already out of the subtotal
+    pub(crate) amount_discount: Decimal,
+    //ISO 4217 code every amount on the invoice is in
+    pub(crate) currency: String,
+    //when the overdue sweep found it past due, None once it's paid or no longer due
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub(crate) overdue_at: Option<OffsetDateTime>,
+    pub(crate) reminders_paused: bool,
+    pub(crate) reminders_sent: i32,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub(crate) last_reminder_at: Option<OffsetDateTime>,
+    pub(crate) late_fees_applied: i32,
+}
+
+impl Invoice {
+    pub(crate) fn discount(&self) -> Option<Discount> {
+        Discount::from_columns(self.discount_kind.as_deref(), self.discount_value)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -112,45 +213,33 @@ pub async fn create_invoice(
         )
     })?;
 
-    let client = &state.db_pool;
+    insert_invoice(&mut tx, &state.db_pool, payload).await?;
+
+    tx.commit().await.map_err(|_e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: "Error creating Invoice items into database".to_string(),
+            }),
+        )
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: "New Invoice Successfully Created".to_string(),
+        }),
+    ))
+}
+
+//creates an invoice and its items inside an open transaction, returning the new invoice_id.
+//shared by the create handler and accepted quotes
+pub(crate) async fn insert_invoice(
+    tx: &mut Transaction<'_, Postgres>,
+    client: &sqlx::PgPool,
+    payload: NewInvoiceInfo,
+) -> Result<String, (StatusCode, Json<ApiResponse>)> {
     println!("creating invoice");
-    // find or create client
-    let client_id: String = match (payload.client_id, &payload.booking_id) {
-        (Some(id), _) => {
-            let client_exists = client_exists(&id, &client).await.unwrap();
-            if (!client_exists) {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(ApiResponse {
-                        message: "ERROR: Client_ID could not be found".to_string(),
-                    }),
-                ));
-            }
-            id
-        }
-        (None, Some(booking_id)) => {
-            let booking_exists = booking::booking_exists(&booking_id, &client).await.unwrap();
-            if (!booking_exists) {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(ApiResponse {
-                        message: "ERROR: Booking_ID could not be found".to_string(),
-                    }),
-                ));
-            }
-            clientele::create_client_from_booking(&booking_id, &client)
-                .await
-                .unwrap()
-        }
-        (None, None) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(ApiResponse {
-                    message: "must provide either client_id or booking_id!".to_string(),
-                }),
-            ));
-        }
-    };
+    let client_id = billed_client(payload.client_id, payload.booking_id.as_deref(), client).await?;
     //if a new billing address is provided, update the client's address
     if payload.address_street.is_some()
         || payload.address_city.is_some()
@@ -178,38 +267,90 @@ pub async fn create_invoice(
         })?;
     }
 
+    let currency = billing_currency(payload.currency.as_deref())?;
+    let mut invoice_items = payload.invoice_items;
+    invoice_items.extend(preset_line_items(client, &payload.presets, currency).await?);
+
     let new_invoice_id = booking::generate_id(&client).await;
 
     //CALCULATE TOTALS
 
-    let mut subtotal = Decimal::zero();
-    //calculate subtotal
-    for item in &payload.invoice_items {
-        subtotal += item.unit_price * Decimal::from(item.quantity);
-    }
+    let discounts = invoice_discounts(
+        tx,
+        &new_invoice_id,
+        &invoice_items,
+        payload.discount,
+        payload.coupon_code.as_deref(),
+        currency,
+    )
+    .await?;
+    let totals = totals(&invoice_items, &discounts.in_order(), currency);
+    //tax from the billing address saved above
+    let tax = calculate_tax(
+        client,
+        &client_id,
+        totals.taxable,
+        payload.amount_tax,
+        currency,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error calculating tax: {}", e),
+            }),
+        )
+    })?;
 
     let _new_invoice = sqlx::query_scalar!(
-        "INSERT INTO main.invoices (invoice_id, client_id, created_at, amount_subtotal, amount_tax, notes, booking_id,due_date) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        "INSERT INTO main.invoices (invoice_id, client_id, created_at, amount_subtotal, amount_tax, notes, booking_id,due_date, issued_at, tax_rate, discount_kind, discount_value, coupon_code, amount_discount, currency) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
         new_invoice_id,
         client_id,
         OffsetDateTime::now_utc(),
-        subtotal,
-        payload.amount_tax.unwrap_or(Decimal::zero()),
+        totals.subtotal,
+        tax.amount,
         payload.notes,
         payload.booking_id,
         payload.due_date,
+        payload.issue.then(OffsetDateTime::now_utc),
+        tax.rate,
+        discounts.discount.as_ref().map(|discount| discount.kind.as_str()),
+        discounts.discount.as_ref().map(|discount| discount.value),
+        discounts.coupon_code,
+        totals.discount,
+        currency.code,
     )
-        .fetch_one(&mut *tx)
+        .execute(&mut **tx)
         .await.map_err(|e| (
         StatusCode::INTERNAL_SERVER_ERROR,
         Json(ApiResponse {
             message: format!("Error creating Invoice: {}", e).to_string(),
         }),
-    ));
+    ))?;
+
+    //show the new invoice on the booking's timeline
+    if let Some(booking_id) = &payload.booking_id {
+        record_activity(
+            &mut **tx,
+            booking_id,
+            ActivityKind::InvoiceLinked,
+            &format!("Invoice {} created", new_invoice_id),
+        )
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    message: format!("Error linking Invoice to booking: {}", e).to_string(),
+                }),
+            )
+        })?;
+    }
 
     //add invoice items
-    for item in payload.invoice_items {
-        create_invoice_item(&mut tx, &new_invoice_id, item, client)
+    for item in invoice_items {
+        create_invoice_item(tx, &new_invoice_id, item, client)
             .await
             .map_err(|e| {
                 (
@@ -221,21 +362,52 @@ pub async fn create_invoice(
                 )
             })?;
     }
-
-    tx.commit().await.map_err(|_e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                message: "Error creating Invoice items into database".to_string(),
-            }),
-        )
-    })?;
-    Ok((
-        StatusCode::CREATED,
-        Json(ApiResponse {
-            message: "New Invoice Successfully Created".to_string(),
-        }),
-    ))
+    Ok(new_invoice_id)
+}
+//the client a document is billed to, created from the booking when only a booking_id is given
+pub(crate) async fn billed_client(
+    client_id: Option<String>,
+    booking_id: Option<&str>,
+    client: &sqlx::PgPool,
+) -> Result<String, (StatusCode, Json<ApiResponse>)> {
+    // find or create client
+    let client_id: String = match (client_id, booking_id) {
+        (Some(id), _) => {
+            let client_exists = client_exists(&id, &client).await.unwrap();
+            if (!client_exists) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse {
+                        message: "ERROR: Client_ID could not be found".to_string(),
+                    }),
+                ));
+            }
+            id
+        }
+        (None, Some(booking_id)) => {
+            let booking_exists = booking::booking_exists(&booking_id, &client).await.unwrap();
+            if (!booking_exists) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiResponse {
+                        message: "ERROR: Booking_ID could not be found".to_string(),
+                    }),
+                ));
+            }
+            clientele::create_client_from_booking(&booking_id, &client)
+                .await
+                .unwrap()
+        }
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse {
+                    message: "must provide either client_id or booking_id!".to_string(),
+                }),
+            ));
+        }
+    };
+    Ok(client_id)
 }
 async fn create_invoice_item(
     tx: &mut Transaction<'_, Postgres>,
@@ -246,12 +418,16 @@ async fn create_invoice_item(
     let new_invoice_item_id = booking::generate_id(&client).await;
     println!("new invoice item id: {}", new_invoice_item_id);
     let _new_invoice_item = sqlx::query!(
-        "INSERT INTO main.invoice_items (invoice_id, invoice_item_id, description, quantity, unit_price ) VALUES ($1::character varying, $2::character varying, $3::text, $4::integer, $5::numeric)",
+        "INSERT INTO main.invoice_items (invoice_id, invoice_item_id, description, quantity, unit_price, taxable, discount_kind, discount_value, late_fee) VALUES ($1::character varying, $2::character varying, $3::text, $4::integer, $5::numeric, $6, $7, $8, $9)",
         invoice_id,
         new_invoice_item_id,
         item.description,
         item.quantity,
-        item.unit_price
+        item.unit_price,
+        item.taxable,
+        item.discount.as_ref().map(|discount| discount.kind.as_str()),
+        item.discount.as_ref().map(|discount| discount.value),
+        item.late_fee,
     )
         .execute(&mut **tx)
         .await?;
@@ -296,10 +472,23 @@ pub async fn view_invoice(
     .fetch_one(&db_client)
     .await
     .map_err(|_| StatusCode::NOT_FOUND)?;
+    let payments = invoice_payments(&db_client, &invoice_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let amount_paid = amount_paid(&payments);
+    let balance_due = balance_due(invoice.amount_total.unwrap_or_default(), amount_paid);
+    let installments = invoice_installments(&db_client, &invoice_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let installments = installment_statuses(installments, amount_paid, OffsetDateTime::now_utc());
     let full_invoice = ReturnFullInvoice {
         invoice,
         invoice_items,
         client,
+        payments,
+        amount_paid,
+        balance_due,
+        installments,
     };
     Ok(Json(full_invoice))
 }
@@ -471,6 +660,66 @@ pub(crate) async fn edit_invoice(
             }),
         )
     })?;
+    //issued and voided invoices are kept as they were sent
+    let current = sqlx::query!(
+        "SELECT issued_at, voided_at, currency, discount_kind, discount_value, coupon_code
+        FROM main.invoices WHERE invoice_id = $1 FOR UPDATE",
+        invoice_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error editing Invoice: {}", e),
+            }),
+        )
+    })?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ApiResponse {
+            message: "Invoice not found".to_string(),
+        }),
+    ))?;
+    if current.voided_at.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                message: "A voided invoice can't be edited".to_string(),
+            }),
+        ));
+    }
+    if current.issued_at.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse {
+                message: "Issued invoices can't be edited, void it instead".to_string(),
+            }),
+        ));
+    }
+    let currency = billing_currency(Some(
+        payload.currency.as_deref().unwrap_or(&current.currency),
+    ))?;
+    if currency.code != current.currency {
+        let payments = invoice_payments(&mut *tx, &invoice_id).await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    message: format!("Error editing Invoice: {}", e),
+                }),
+            )
+        })?;
+        if !payments.is_empty() {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ApiResponse {
+                    message: "The currency can't be changed after payments are recorded"
+                        .to_string(),
+                }),
+            ));
+        }
+    }
 
     let client = &state.db_pool;
 
@@ -502,38 +751,19 @@ pub(crate) async fn edit_invoice(
     }
 
     //CALCULATE TOTALS
-    let mut subtotal = Decimal::zero();
-    //calculate subtotal
-    for item in &payload.invoice_items {
-        subtotal += item.unit_price * Decimal::from(item.quantity);
-    }
-    //UPDATE INVOICE
-    let _edit_invoice = sqlx::query_scalar!(
-        r#"UPDATE main.invoices SET amount_subtotal = $1, notes = $2,due_date = $3, payment_completed = $4,
-        paid_at = CASE
-            WHEN $4 = FALSE THEN NULL
-            ELSE $5::timestamptz
-        END, amount_tax = $6
-        WHERE invoice_id=$7
-        "#,
-        subtotal,
-        payload.notes,
-        payload.due_date,
-        payload.payment_completed,
-        payload.paid_at,
-        payload.amount_tax.unwrap_or(Decimal::zero()),
-        invoice_id
-    )
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse {
-                message: format!("Error editing Invoice: {}", e).to_string(),
-            }),
-        )
+    let discount = payload.discount.unwrap_or_else(|| {
+        Discount::from_columns(current.discount_kind.as_deref(), current.discount_value)
     });
+    let coupon_code = payload.coupon_code.unwrap_or(current.coupon_code);
+    let discounts = invoice_discounts(
+        &mut tx,
+        &invoice_id,
+        &payload.invoice_items,
+        discount,
+        coupon_code.as_deref(),
+        currency,
+    )
+    .await?;
     //REMOVE ALL OLD INVOICE ITEMS
     remove_all_invoice_items(&mut tx, &invoice_id)
         .await
@@ -560,6 +790,77 @@ pub(crate) async fn edit_invoice(
                 )
             })?;
     }
+    //UPDATE INVOICE
+    sqlx::query!(
+        r#"UPDATE main.invoices SET notes = $1, due_date = $2, discount_kind = $3, discount_value = $4,
+            coupon_code = $5, currency = $6
+        WHERE invoice_id = $7
+        "#,
+        payload.notes,
+        payload.due_date,
+        discounts.discount.as_ref().map(|discount| discount.kind.as_str()),
+        discounts.discount.as_ref().map(|discount| discount.value),
+        discounts.coupon_code,
+        currency.code,
+        invoice_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error editing Invoice: {}", e).to_string(),
+            }),
+        )
+    })?;
+    let totals = saved_totals(&mut tx, &invoice_id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error calculating totals: {}", e),
+            }),
+        )
+    })?;
+    let tax = calculate_tax(
+        client,
+        &payload.client_id,
+        totals.taxable,
+        payload.amount_tax,
+        currency,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error calculating tax: {}", e),
+            }),
+        )
+    })?;
+    save_amounts(&mut tx, &invoice_id, &totals, Some(&tax))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    message: format!("Error editing Invoice: {}", e),
+                }),
+            )
+        })?;
+    //a payment plan has to keep adding up to the new total
+    fit_installments(&mut tx, &invoice_id).await?;
+    //a new total can make the invoice paid or unpaid
+    sync_payment_status(&mut tx, &invoice_id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse {
+                    message: format!("Error updating payment status: {}", e),
+                }),
+            )
+        })?;
 
     tx.commit().await.map_err(|e| {
         (
@@ -576,15 +877,218 @@ pub(crate) async fn edit_invoice(
         }),
     ))
 }
-pub(crate) async fn delete_invoice(
+#[derive(Serialize, Deserialize)]
+pub struct VoidInvoice {
+    reason: String,
+}
+
+fn invoice_error(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    (status, Json(ApiResponse { message }))
+}
+
+//marks a draft as sent to the client, from then on it can only be voided
+pub(crate) async fn issue_invoice(
     State(state): State<AppState>,
-    Json(payload): Json<InvoiceID>,
-) -> Result<StatusCode, StatusCode> {
-    //TODO delete the invoice and all invoice_items
+    Path(invoice_id): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    let issued = sqlx::query!(
+        "UPDATE main.invoices SET issued_at = $1 WHERE invoice_id = $2 AND issued_at IS NULL AND voided_at IS NULL",
+        OffsetDateTime::now_utc(),
+        invoice_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        invoice_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error issuing invoice: {}", e),
+        )
+    })?;
+    if issued.rows_affected() == 0 {
+        return Err(invoice_error(
+            StatusCode::CONFLICT,
+            "Invoice not found or already issued".to_string(),
+        ));
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Invoice issued".to_string(),
+        }),
+    ))
+}
+
+//cancels an issued invoice but keeps it on record, voided invoices don't count as revenue
+pub(crate) async fn void_invoice(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+    Json(payload): Json<VoidInvoice>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(invoice_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "A reason is required to void an invoice".to_string(),
+        ));
+    }
+    let internal_error = |e: sqlx::Error| {
+        invoice_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error voiding invoice: {}", e),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let voided = sqlx::query!(
+        r#"UPDATE main.invoices SET voided_at = $1, void_reason = $2
+        WHERE invoice_id = $3 AND issued_at IS NOT NULL AND voided_at IS NULL
+        RETURNING invoice_number, booking_id"#,
+        OffsetDateTime::now_utc(),
+        reason,
+        invoice_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or(invoice_error(
+        StatusCode::CONFLICT,
+        "Only issued invoices that aren't already void can be voided, delete drafts instead"
+            .to_string(),
+    ))?;
+    if let Some(booking_id) = &voided.booking_id {
+        record_activity(
+            &mut *tx,
+            booking_id,
+            ActivityKind::InvoiceVoided,
+            &format!("Invoice #{} voided: {}", voided.invoice_number, reason),
+        )
+        .await
+        .map_err(internal_error)?;
+    }
+    tx.commit().await.map_err(internal_error)?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Invoice voided".to_string(),
+        }),
+    ))
+}
 
+//only drafts can be deleted, issued invoices have to be voided
+pub(crate) async fn delete_invoice(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let internal_error = |e: sqlx::Error| {
+        invoice_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error deleting invoice: {}", e),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let issued_at = sqlx::query_scalar!(
+        "SELECT issued_at FROM main.invoices WHERE invoice_id = $1 FOR UPDATE",
+        invoice_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or(invoice_error(
+        StatusCode::NOT_FOUND,
+        "Invoice not found".to_string(),
+    ))?;
+    if issued_at.is_some() {
+        return Err(invoice_error(
+            StatusCode::CONFLICT,
+            "Issued invoices can't be deleted, void it instead".to_string(),
+        ));
+    }
+    remove_all_invoice_items(&mut tx, &invoice_id)
+        .await
+        .map_err(internal_error)?;
+    sqlx::query!(
+        "DELETE FROM main.installments WHERE invoice_id = $1",
+        invoice_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    sqlx::query!(
+        "DELETE FROM main.invoices WHERE invoice_id = $1",
+        invoice_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
     Ok(StatusCode::OK)
 }
 
+//the totals of an invoice's saved line items with its saved invoice discount and coupon.
+//shared by edit_invoice and the overdue sweep so late fees are totalled the same way
+pub(crate) async fn saved_totals(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+) -> Result<Totals, sqlx::Error> {
+    let invoice = sqlx::query!(
+        r#"SELECT i.currency, i.discount_kind, i.discount_value,
+            c.discount_kind AS "coupon_kind?", c.discount_value AS "coupon_value?"
+        FROM main.invoices i
+        LEFT JOIN main.coupons c ON c.code = i.coupon_code
+        WHERE i.invoice_id = $1"#,
+        invoice_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    let items: Vec<NewInvoiceItem> = sqlx::query!(
+        r#"SELECT description, quantity, unit_price, taxable, discount_kind, discount_value, late_fee
+        FROM main.invoice_items WHERE invoice_id = $1"#,
+        invoice_id
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .map(|item| NewInvoiceItem {
+        description: item.description,
+        quantity: item.quantity,
+        unit_price: item.unit_price,
+        taxable: item.taxable,
+        discount: Discount::from_columns(item.discount_kind.as_deref(), item.discount_value),
+        late_fee: item.late_fee,
+    })
+    .collect();
+    let discount = Discount::from_columns(invoice.discount_kind.as_deref(), invoice.discount_value);
+    let coupon = Discount::from_columns(invoice.coupon_kind.as_deref(), invoice.coupon_value);
+    Ok(totals(
+        &items,
+        &discount.iter().chain(coupon.iter()).collect::<Vec<_>>(),
+        currency_or_default(&invoice.currency),
+    ))
+}
+
+//writes totals to an invoice, None keeps the tax it has
+pub(crate) async fn save_amounts(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+    totals: &Totals,
+    tax: Option<&Tax>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE main.invoices SET amount_subtotal = $1, amount_discount = $2,
+            amount_tax = CASE WHEN $3 THEN $4 ELSE amount_tax END,
+            tax_rate = CASE WHEN $3 THEN $5 ELSE tax_rate END
+        WHERE invoice_id = $6"#,
+        totals.subtotal,
+        totals.discount,
+        tax.is_some(),
+        tax.map(|tax| tax.amount),
+        tax.and_then(|tax| tax.rate),
+        invoice_id,
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
 async fn remove_all_invoice_items(
     tx: &mut Transaction<'_, Postgres>,
     invoice_id: &String,
@@ -598,3 +1102,99 @@ async fn remove_all_invoice_items(
     .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoicing::currency::currency_or_default;
+    use crate::invoicing::tax::tax_for;
+
+    fn usd_tax(amount_tax: Option<Decimal>) -> Tax {
+        //8% on $100
+        tax_for(
+            amount_tax,
+            Some(Decimal::from(8)),
+            Decimal::from(100),
+            currency_or_default("USD"),
+        )
+    }
+
+    #[test]
+    fn calculates_tax_when_the_pages_leave_it_out() {
+        //what the create page sends with the tax field left blank
+        let created: NewInvoiceInfo = serde_json::from_value(serde_json::json!({
+            "client_id": "client_1",
+            "invoice_items": [{"description": "Portrait session", "quantity": 1, "unit_price": 100}],
+            "due_date": "2026-11-01T00:00:00Z",
+            "issue": true,
+            "address_country": {"value": "United States", "label": "United States"}
+        }))
+        .unwrap();
+        assert_eq!(created.amount_tax, None);
+        assert_eq!(
+            usd_tax(created.amount_tax),
+            Tax {
+                amount: Decimal::from(8),
+                rate: Some(Decimal::from(8)),
+            }
+        );
+
+        //what the edit page sends back for an invoice whose tax came from its address
+        let edited: EditInvoiceInfo = serde_json::from_value(serde_json::json!({
+            "client_id": "client_1",
+            "invoice_items": [{
+                "invoice_id": "inv_1",
+                "invoice_item_id": "item_1",
+                "description": "Portrait session",
+                "quantity": 1,
+                "unit_price": "100",
+                "taxable": true
+            }],
+            "notes": "Thanks!",
+            "due_date": "2026-11-01T00:00:00Z",
+            "address_country": {"value": "United States", "label": "United States"}
+        }))
+        .unwrap();
+        assert_eq!(edited.amount_tax, None);
+        //and leaves the discount and coupon alone
+        assert_eq!(edited.discount, None);
+        assert_eq!(edited.coupon_code, None);
+        assert_eq!(usd_tax(edited.amount_tax).rate, Some(Decimal::from(8)));
+
+        //an amount entered by hand still overrides, including no tax at all
+        assert_eq!(
+            usd_tax(Some(Decimal::ZERO)),
+            Tax {
+                amount: Decimal::ZERO,
+                rate: None,
+            }
+        );
+    }
+
+    #[test]
+    fn edits_only_clear_discounts_sent_as_null() {
+        let edited: EditInvoiceInfo = serde_json::from_value(serde_json::json!({
+            "client_id": "client_1",
+            "invoice_items": [{
+                "description": "Portrait session",
+                "quantity": 1,
+                "unit_price": "100",
+                "discount": {"kind": "percent", "value": "10"}
+            }],
+            "discount": null,
+            "coupon_code": "SPRING",
+            "due_date": "2026-11-01T00:00:00Z",
+            "address_country": {"value": "United States", "label": "United States"}
+        }))
+        .unwrap();
+        assert_eq!(edited.discount, Some(None));
+        assert_eq!(edited.coupon_code, Some(Some("SPRING".to_string())));
+        assert_eq!(
+            edited.invoice_items[0].discount,
+            Some(Discount {
+                kind: crate::invoicing::discount::DiscountKind::Percent,
+                value: Decimal::from(10),
+            })
+        );
+    }
+}
@@ -1,4 +1,8 @@
 use crate::AppState;
+use crate::booking::schedule::client_timezone;
+use crate::clientele::Client;
+use crate::invoicing::currency::Money;
+use crate::invoicing::discount::{Discount, DiscountKind, line_total};
 use crate::invoicing::invoice;
 use crate::invoicing::invoice::ReturnFullInvoice;
 use axum::Json;
@@ -8,6 +12,8 @@ use chrono::prelude::*;
 use chrono::{DateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use http::StatusCode;
+use rust_decimal::Decimal;
+use time::OffsetDateTime;
 use typst::foundations::{Array, Datetime, Dict, Value, array, dict};
 use typst_as_lib::TypstEngine;
 use typst_as_lib::typst_kit_options::TypstKitFontOptions;
@@ -62,63 +68,78 @@ pub(crate) async fn generate_pdf(
         invoice,
         client,
         invoice_items,
+        payments,
+        amount_paid,
+        balance_due,
+        installments,
     }) = invoice;
-    let iana_name = client.timezone.unwrap_or("America/New_York".to_string());
-    let timezone: Tz = iana_name.parse().unwrap();
-    let mut typst_due_date: Option<Datetime> = None;
-    if invoice.due_date.is_some() {
-        let due_date = invoice.due_date.unwrap();
-        let secs = due_date.unix_timestamp();
-        let nanos = due_date.nanosecond();
-
-        let dt: DateTime<Utc> = Utc
-            .timestamp_opt(secs, nanos)
-            .single()
-            .expect("invalid timestamp");
-        //timezone adjusted
-        let tz_adjusted_dt = dt.with_timezone(&timezone);
-
-        // Create Typst Datetime
-        typst_due_date = Datetime::from_ymd(
-            tz_adjusted_dt.year(),
-            tz_adjusted_dt.month() as u8,
-            tz_adjusted_dt.day() as u8,
-        )
-    }
+    let timezone = client_timezone(client.timezone.as_deref());
+    //amounts are written in the invoice's currency the way the client's country writes numbers
+    let money = Money::new(&invoice.currency, client.address_country.as_deref());
     //prepare invoice items for Typst
-    let mut invoice_items_array = Array::new();
-    for item in invoice_items {
-        let mut temp_dict = Dict::new();
-        temp_dict.insert("description".into(), Value::Str(item.description.into()));
-        temp_dict.insert("quantity".into(), Value::Int(item.quantity.into()));
-        temp_dict.insert(
-            "unit_price".into(),
-            Value::Str(item.unit_price.to_string().into()),
-        );
-        invoice_items_array.push(Value::Dict(temp_dict));
+    let invoice_items_array = typst_line_items(
+        invoice_items.iter().map(|item| {
+            (
+                item.description.as_str(),
+                item.quantity,
+                item.unit_price,
+                item.taxable,
+                item.discount(),
+            )
+        }),
+        &money,
+    );
+    let mut payments_array = Array::new();
+    for payment in payments {
+        payments_array.push(Value::Dict(dict! {
+            "amount" => money.format(payment.amount),
+            "method" => payment.method,
+            "paid_at" => typst_date(payment.paid_at, timezone),
+            "reference" => payment.reference
+        }));
+    }
+    //the payment plan, earliest due first
+    let mut installments_array = Array::new();
+    for status in installments {
+        installments_array.push(Value::Dict(dict! {
+            "label" => status.installment.label,
+            "due_date" => typst_date(status.installment.due_date, timezone),
+            "amount" => money.format(status.installment.amount),
+            "amount_paid" => money.format(status.amount_paid),
+            "balance" => money.format(status.balance),
+            "status" => status.status.as_str()
+        }));
     }
 
     let input_data = dict! {
+        //the template switches its headings on this, quotes render with "quote"
+        "document" => "invoice",
         "invoice" => dict! {
             "invoice_number" => invoice.invoice_number,
-            "due_date" => typst_due_date,
-            "amount_subtotal" => invoice.amount_subtotal.unwrap_or(0.into()).to_string(),
-            "amount_tax"=> invoice.amount_tax.unwrap_or(0.into()).to_string(),
-            "amount_total"=> invoice.amount_total.unwrap_or(0.into()).to_string(),
-            "notes" => invoice.notes
+            "due_date" => invoice.due_date.and_then(|due_date| typst_date(due_date, timezone)),
+            "amount_subtotal" => money.format(invoice.amount_subtotal.unwrap_or(0.into())),
+            //the invoice discount and coupon together, taken off the subtotal before tax
+            "amount_discount" => money.format(invoice.amount_discount),
+            "discount" => typst_discount(invoice.discount().as_ref(), &money),
+            "coupon_code" => invoice.coupon_code.clone(),
+            "amount_tax"=> money.format(invoice.amount_tax.unwrap_or(0.into())),
+            //None when the tax was entered by hand
+            "tax_rate" => invoice.tax_rate.map(|rate| rate.to_string()),
+            "amount_total"=> money.format(invoice.amount_total.unwrap_or(0.into())),
+            "notes" => invoice.notes,
+            "draft" => invoice.issued_at.is_none(),
+            //the template stamps the invoice VOID when this is set
+            "void" => invoice.voided_at.is_some(),
+            "void_reason" => invoice.void_reason,
+            "voided_at" => invoice.voided_at.and_then(|voided_at| typst_date(voided_at, timezone))
         },
         "invoice_items" => invoice_items_array,
-         "client" => dict! {
-            "first_name" => client.first_name,
-            "last_name" => client.last_name,
-            "email" => client.email,
-            "phone" => client.phone,
-            "address_street" => client.address_street,
-            "address_city" => client.address_city,
-            "address_state" => client.address_state,
-            "address_zip" => client.address_zip,
-            "address_country" => client.address_country
-        },
+        "payments" => payments_array,
+        "amount_paid" => money.format(amount_paid),
+        "balance_due" => money.format(balance_due),
+        "installments" => installments_array,
+        "currency" => money.currency.code,
+        "client" => typst_client(client),
     };
 
     /* let doc = template
@@ -126,22 +147,116 @@ pub(crate) async fn generate_pdf(
     .compile_with_input(input_data)
     .output
     .expect("typst::compile() returned an error!");*/
-    let pdf = typst_bake::document!("template.typ")
+    pdf_response(render_pdf(input_data), "report.pdf")
+    /*  fs::write(OUTPUT, pdf).expect("Could not write pdf.");
+    println!("Wrote pdf to {}", OUTPUT);*/
+}
+
+//renders the shared template with the given inputs
+pub(crate) fn render_pdf(input_data: Dict) -> Vec<u8> {
+    typst_bake::document!("template.typ")
         .with_inputs(input_data)
         .to_pdf()
-        .unwrap();
+        .unwrap()
 
     // Create pdf
     //let options = Default::default();
     //let pdf = typst_pdf::pdf(&doc, &options).expect("Could not generate pdf.");
+}
 
+pub(crate) fn pdf_response(pdf: Vec<u8>, file_name: &str) -> Response {
     Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "application/pdf")
         // let the browser display it inline; use "attachment" to force download
-        .header("Content-Disposition", "inline; filename=\"report.pdf\"")
+        .header(
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", file_name),
+        )
         .body(axum::body::Body::from(pdf))
         .unwrap()
-    /*  fs::write(OUTPUT, pdf).expect("Could not write pdf.");
-    println!("Wrote pdf to {}", OUTPUT);*/
+}
+
+//a date as the client sees it, Typst has no timezones
+pub(crate) fn typst_date(at: OffsetDateTime, timezone: Tz) -> Option<Datetime> {
+    let secs = at.unix_timestamp();
+    let nanos = at.nanosecond();
+
+    let dt: DateTime<Utc> = Utc
+        .timestamp_opt(secs, nanos)
+        .single()
+        .expect("invalid timestamp");
+    //timezone adjusted
+    let tz_adjusted_dt = dt.with_timezone(&timezone);
+
+    // Create Typst Datetime
+    Datetime::from_ymd(
+        tz_adjusted_dt.year(),
+        tz_adjusted_dt.month() as u8,
+        tz_adjusted_dt.day() as u8,
+    )
+}
+
+//line items as (description, quantity, unit_price, taxable, discount), amounts formatted with money
+pub(crate) fn typst_line_items<'a>(
+    items: impl Iterator<Item = (&'a str, i32, Decimal, bool, Option<Discount>)>,
+    money: &Money,
+) -> Array {
+    let mut items_array = Array::new();
+    for (description, quantity, unit_price, taxable, discount) in items {
+        let mut temp_dict = Dict::new();
+        temp_dict.insert("description".into(), Value::Str(description.into()));
+        temp_dict.insert("quantity".into(), Value::Int(quantity.into()));
+        temp_dict.insert(
+            "unit_price".into(),
+            Value::Str(money.format(unit_price).into()),
+        );
+        temp_dict.insert("taxable".into(), Value::Bool(taxable));
+        //what the line comes to after its discount
+        temp_dict.insert(
+            "total".into(),
+            Value::Str(
+                money
+                    .format(line_total(
+                        unit_price,
+                        quantity,
+                        discount.as_ref(),
+                        money.currency,
+                    ))
+                    .into(),
+            ),
+        );
+        temp_dict.insert("discount".into(), typst_discount(discount.as_ref(), money));
+        items_array.push(Value::Dict(temp_dict));
+    }
+    items_array
+}
+
+//a discount as {kind, value}, none when there isn't one. a fixed value is formatted as money,
+//a percent is just the number
+pub(crate) fn typst_discount(discount: Option<&Discount>, money: &Money) -> Value {
+    match discount {
+        Some(discount) => Value::Dict(dict! {
+            "kind" => discount.kind.as_str(),
+            "value" => match discount.kind {
+                DiscountKind::Percent => discount.value.normalize().to_string(),
+                DiscountKind::Fixed => money.format(discount.value),
+            }
+        }),
+        None => Value::None,
+    }
+}
+
+pub(crate) fn typst_client(client: Client) -> Dict {
+    dict! {
+        "first_name" => client.first_name,
+        "last_name" => client.last_name,
+        "email" => client.email,
+        "phone" => client.phone,
+        "address_street" => client.address_street,
+        "address_city" => client.address_city,
+        "address_state" => client.address_state,
+        "address_zip" => client.address_zip,
+        "address_country" => client.address_country
+    }
 }
@@ -1,2 +1,11 @@
+pub mod checkout;
+pub mod currency;
+pub mod discount;
+pub mod installments;
 pub mod invoice;
 pub mod invoice_generation;
+pub mod overdue;
+pub mod payments;
+pub mod quote;
+pub mod revenue;
+pub mod tax;
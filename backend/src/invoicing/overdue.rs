@@ -0,0 +1,721 @@
+use crate::AppState;
+use crate::booking;
+use crate::booking::schedule::local_time;
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::email::OutgoingEmail;
+use crate::invoicing::currency::{Currency, Money};
+use crate::invoicing::installments::{
+    InstallmentState, find_overdue_installments, fit_installments, installment_statuses,
+    invoice_installments,
+};
+use crate::invoicing::invoice::{ApiResponse, save_amounts, saved_totals};
+use crate::invoicing::payments::{balance_due, sync_payment_status};
+use crate::invoicing::tax::{Tax, tax_on};
+use crate::jobs::{JobKind, cancel_jobs, schedule_job};
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::env;
+use time::{Duration, OffsetDateTime};
+
+//there's only ever one sweep queued, this is its scheduled_jobs.reference_id
+const SWEEP_REFERENCE: &str = "invoices";
+
+//what an overdue invoice is charged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LateFeePolicy {
+    //a flat amount in the invoice's currency, charged once
+    Fixed(Decimal),
+    //a percent of the balance due, charged once
+    Percent(Decimal),
+    //a percent of the balance due for every 30 days it stays unpaid
+    MonthlyInterest(Decimal),
+}
+
+impl LateFeePolicy {
+    //"fixed:25", "percent:5" or "interest:1.5"
+    fn parse(policy: &str) -> Option<Self> {
+        let (kind, value) = policy.split_once(':')?;
+        let value: Decimal = value.trim().parse().ok()?;
+        if value <= Decimal::ZERO {
+            return None;
+        }
+        match kind.trim() {
+            "fixed" => Some(LateFeePolicy::Fixed(value)),
+            "percent" => Some(LateFeePolicy::Percent(value)),
+            "interest" => Some(LateFeePolicy::MonthlyInterest(value)),
+            _ => None,
+        }
+    }
+
+    //the fee on what's left to pay, rounded for the currency
+    pub(crate) fn fee(&self, balance: Decimal, currency: &Currency) -> Decimal {
+        match self {
+            LateFeePolicy::Fixed(amount) => currency.round(*amount),
+            LateFeePolicy::Percent(rate) | LateFeePolicy::MonthlyInterest(rate) => {
+                currency.round(balance * rate / Decimal::from(100))
+            }
+        }
+    }
+
+    //the line item the fee is added as
+    fn description(&self, balance: Decimal, money: &Money) -> String {
+        match self {
+            LateFeePolicy::Fixed(_) => "Late fee".to_string(),
+            LateFeePolicy::Percent(rate) => format!(
+                "Late fee ({}% of {} overdue)",
+                rate.normalize(),
+                money.format(balance)
+            ),
+            LateFeePolicy::MonthlyInterest(rate) => format!(
+                "Interest ({}% of {} overdue)",
+                rate.normalize(),
+                money.format(balance)
+            ),
+        }
+    }
+}
+
+//when overdue reminders go out and what late fee is charged
+#[derive(Clone)]
+pub(crate) struct OverdueConfig {
+    //how long after an invoice goes overdue each reminder is sent, later ones are firmer
+    pub(crate) reminder_after: Vec<Duration>,
+    pub(crate) late_fee: Option<LateFeePolicy>,
+    //grace period before the first late fee
+    pub(crate) late_fee_after: Duration,
+    pub(crate) sweep_every: Duration,
+}
+
+impl OverdueConfig {
+    //OVERDUE_REMINDER_DAYS is a comma separated list like "1,7,14", empty disables reminders.
+    //LATE_FEE_POLICY is "fixed:25", "percent:5" or "interest:1.5", empty for no late fees
+    pub(crate) fn from_env() -> Self {
+        let days = env::var("OVERDUE_REMINDER_DAYS").unwrap_or_else(|_| "1,7,14".into());
+        let late_fee = env::var("LATE_FEE_POLICY").unwrap_or_default();
+        let late_fee_after = env::var("LATE_FEE_AFTER_DAYS").unwrap_or_else(|_| "14".into());
+        let sweep_every = env::var("OVERDUE_SWEEP_MINUTES").unwrap_or_else(|_| "60".into());
+        OverdueConfig {
+            reminder_after: days
+                .split(',')
+                .map(str::trim)
+                .filter(|days| !days.is_empty())
+                .map(|days| {
+                    Duration::days(
+                        days.parse()
+                            .expect("OVERDUE_REMINDER_DAYS must be a list of days"),
+                    )
+                })
+                .collect(),
+            late_fee: match late_fee.trim() {
+                "" => None,
+                policy => Some(LateFeePolicy::parse(policy).expect(
+                    "LATE_FEE_POLICY must be fixed:<amount>, percent:<rate> or interest:<rate>",
+                )),
+            },
+            late_fee_after: Duration::days(
+                late_fee_after
+                    .parse()
+                    .expect("LATE_FEE_AFTER_DAYS must be a number of days"),
+            ),
+            sweep_every: Duration::minutes(
+                sweep_every
+                    .parse()
+                    .expect("OVERDUE_SWEEP_MINUTES must be a number of minutes"),
+            ),
+        }
+    }
+}
+
+//whether the next reminder is due. after the first, each one also waits out the gap between its
+//stage and the one before since the last reminder went out, so a server that was down doesn't
+//send every stage on back to back sweeps
+fn reminder_due(
+    config: &OverdueConfig,
+    overdue_at: OffsetDateTime,
+    reminders_sent: i32,
+    last_reminder_at: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> bool {
+    let Some(sent) = usize::try_from(reminders_sent).ok() else {
+        return false;
+    };
+    let Some(after) = config.reminder_after.get(sent) else {
+        return false;
+    };
+    let gap = match sent
+        .checked_sub(1)
+        .and_then(|previous| config.reminder_after.get(previous))
+    {
+        Some(previous) => *after - *previous,
+        None => Duration::ZERO,
+    };
+    now >= overdue_at + *after && last_reminder_at.is_none_or(|last| now >= last + gap)
+}
+
+//how many late fees should have been charged by now
+fn late_fees_due(
+    policy: LateFeePolicy,
+    late_fee_after: Duration,
+    overdue_at: OffsetDateTime,
+    now: OffsetDateTime,
+) -> i32 {
+    let start = overdue_at + late_fee_after;
+    if now < start {
+        return 0;
+    }
+    match policy {
+        LateFeePolicy::MonthlyInterest(_) => 1 + ((now - start).whole_days() / 30) as i32,
+        LateFeePolicy::Fixed(_) | LateFeePolicy::Percent(_) => 1,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ReminderTone {
+    Friendly,
+    Firm,
+    Final,
+}
+
+//the first reminder is friendly and the last one is a final notice
+fn reminder_tone(stage: usize, stages: usize) -> ReminderTone {
+    if stages > 1 && stage + 1 == stages {
+        ReminderTone::Final
+    } else if stage == 0 {
+        ReminderTone::Friendly
+    } else {
+        ReminderTone::Firm
+    }
+}
+
+//queues the first sweep, called once at startup. a sweep left over from before a restart is replaced
+pub(crate) async fn start_overdue_sweeps(client: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    cancel_jobs(client, JobKind::OverdueSweep, SWEEP_REFERENCE).await?;
+    schedule_job(
+        client,
+        JobKind::OverdueSweep,
+        SWEEP_REFERENCE,
+        OffsetDateTime::now_utc(),
+        serde_json::json!({}),
+    )
+    .await
+}
+
+//run by the job scheduler. problems with one invoice are logged and the rest carry on,
+//the next sweep is queued either way so a failed sweep isn't retried on top of it
+pub(crate) async fn run_overdue_sweep(state: &AppState) -> Result<(), String> {
+    let now = OffsetDateTime::now_utc();
+    if let Err(e) = sweep(state, now).await {
+        println!("Error running overdue sweep: {}", e);
+    }
+    schedule_job(
+        &state.db_pool,
+        JobKind::OverdueSweep,
+        SWEEP_REFERENCE,
+        now + state.overdue.sweep_every,
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn sweep(state: &AppState, now: OffsetDateTime) -> Result<(), sqlx::Error> {
+    mark_overdue(&state.db_pool, now).await?;
+    if let Some(policy) = state.overdue.late_fee {
+        for invoice in overdue_invoices(&state.db_pool).await? {
+            charge_late_fees(state, &invoice, policy, now)
+                .await
+                .unwrap_or_else(|e| {
+                    println!(
+                        "Error charging late fee on invoice #{}: {}",
+                        invoice.invoice_number, e
+                    )
+                });
+        }
+    }
+    //reminders go out after fees so they show the new balance
+    for invoice in overdue_invoices(&state.db_pool).await? {
+        if invoice.reminders_paused
+            || !reminder_due(
+                &state.overdue,
+                invoice.overdue_at,
+                invoice.reminders_sent,
+                invoice.last_reminder_at,
+                now,
+            )
+        {
+            continue;
+        }
+        send_overdue_reminder(state, &invoice, now)
+            .await
+            .unwrap_or_else(|e| {
+                println!(
+                    "Error sending reminder for invoice #{}: {}",
+                    invoice.invoice_number, e
+                )
+            });
+    }
+    Ok(())
+}
+
+//sets overdue_at on issued, unpaid invoices past their due date and clears it from ones that
+//have since been paid, voided or given a later date. an invoice with a payment plan goes overdue
+//with its first missed installment instead of its due date
+async fn mark_overdue(client: &sqlx::PgPool, now: OffsetDateTime) -> Result<(), sqlx::Error> {
+    let mut late_plans: Vec<String> = find_overdue_installments(client, now)
+        .await?
+        .into_iter()
+        .map(|overdue| overdue.installment.installment.invoice_id)
+        .collect();
+    late_plans.dedup();
+    //reminders and fees start over each time an invoice goes overdue
+    sqlx::query!(
+        r#"UPDATE main.invoices i SET overdue_at = $1, reminders_sent = 0, last_reminder_at = NULL,
+            late_fees_applied = 0
+        WHERE i.overdue_at IS NULL AND i.issued_at IS NOT NULL AND i.voided_at IS NULL
+            AND NOT i.payment_completed
+            AND (i.invoice_id = ANY($2) OR (i.due_date < $1 AND NOT EXISTS
+                (SELECT 1 FROM main.installments ins WHERE ins.invoice_id = i.invoice_id)))"#,
+        now,
+        &late_plans,
+    )
+    .execute(client)
+    .await?;
+    sqlx::query!(
+        r#"UPDATE main.invoices i SET overdue_at = NULL
+        WHERE i.overdue_at IS NOT NULL AND NOT (i.issued_at IS NOT NULL AND i.voided_at IS NULL
+            AND NOT i.payment_completed
+            AND (i.invoice_id = ANY($2) OR (COALESCE(i.due_date < $1, false) AND NOT EXISTS
+                (SELECT 1 FROM main.installments ins WHERE ins.invoice_id = i.invoice_id))))"#,
+        now,
+        &late_plans,
+    )
+    .execute(client)
+    .await?;
+    Ok(())
+}
+
+//an overdue invoice and who to remind about it
+#[derive(Serialize, Deserialize)]
+pub(crate) struct OverdueInvoice {
+    pub(crate) invoice_id: String,
+    pub(crate) invoice_number: i64,
+    pub(crate) booking_id: Option<String>,
+    pub(crate) client_id: Option<String>,
+    pub(crate) first_name: Option<String>,
+    pub(crate) email: Option<String>,
+    pub(crate) timezone: Option<String>,
+    pub(crate) address_country: Option<String>,
+    pub(crate) currency: String,
+    pub(crate) amount_total: Option<Decimal>,
+    pub(crate) amount_paid: Decimal,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub(crate) due_date: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) overdue_at: OffsetDateTime,
+    pub(crate) reminders_paused: bool,
+    pub(crate) reminders_sent: i32,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub(crate) last_reminder_at: Option<OffsetDateTime>,
+    pub(crate) late_fees_applied: i32,
+}
+
+async fn overdue_invoices(client: &sqlx::PgPool) -> Result<Vec<OverdueInvoice>, sqlx::Error> {
+    sqlx::query_as!(
+        OverdueInvoice,
+        r#"SELECT i.invoice_id, i.invoice_number, i.booking_id, i.client_id,
+            c.first_name AS "first_name?", c.email AS "email?", c.timezone, c.address_country,
+            i.currency, i.amount_total,
+            (SELECT COALESCE(SUM(p.amount), 0) FROM main.payments p WHERE p.invoice_id = i.invoice_id) AS "amount_paid!",
+            i.due_date, i.overdue_at AS "overdue_at!", i.reminders_paused, i.reminders_sent,
+            i.last_reminder_at, i.late_fees_applied
+        FROM main.invoices i
+        LEFT JOIN main.clients c ON c.client_id = i.client_id
+        WHERE i.overdue_at IS NOT NULL
+        ORDER BY i.overdue_at, i.invoice_number"#
+    )
+    .fetch_all(client)
+    .await
+}
+
+//adds any late fees the policy says are owed as line items and updates the invoice totals
+async fn charge_late_fees(
+    state: &AppState,
+    invoice: &OverdueInvoice,
+    policy: LateFeePolicy,
+    now: OffsetDateTime,
+) -> Result<(), String> {
+    //one fee per sweep, interest that's months behind catches up a month at a time
+    if invoice.late_fees_applied
+        >= late_fees_due(
+            policy,
+            state.overdue.late_fee_after,
+            invoice.overdue_at,
+            now,
+        )
+    {
+        return Ok(());
+    }
+    let mut tx = state.db_pool.begin().await.map_err(|e| e.to_string())?;
+    //recheck under the lock in case an edit or payment landed since the invoice was read
+    let locked = sqlx::query!(
+        r#"SELECT late_fees_applied, overdue_at, amount_total, payment_completed, tax_rate,
+            (SELECT COALESCE(SUM(it.unit_price * it.quantity), 0) FROM main.invoice_items it
+                WHERE it.invoice_id = i.invoice_id AND it.late_fee) AS "fees!"
+        FROM main.invoices i WHERE invoice_id = $1 FOR UPDATE"#,
+        invoice.invoice_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    if locked.late_fees_applied != invoice.late_fees_applied
+        || locked.overdue_at != Some(invoice.overdue_at)
+        || locked.payment_completed
+    {
+        return Ok(());
+    }
+    let money = Money::new(&invoice.currency, invoice.address_country.as_deref());
+    //on a payment plan only the missed installments are overdue, not the whole balance
+    let installments = invoice_installments(&mut *tx, &invoice.invoice_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    //fees already charged aren't charged on again, payments go to the invoice itself first
+    let principal = balance_due(
+        locked.amount_total.unwrap_or_default() - locked.fees,
+        invoice.amount_paid,
+    );
+    let balance = if installments.is_empty() {
+        principal
+    } else {
+        installment_statuses(installments, invoice.amount_paid, now)
+            .iter()
+            .filter(|status| status.status == InstallmentState::Overdue)
+            .map(|status| status.balance)
+            .sum::<Decimal>()
+            .min(principal)
+    };
+    let fee = policy.fee(balance, money.currency);
+    if fee <= Decimal::ZERO {
+        return Ok(());
+    }
+    let description = policy.description(balance, &money);
+    //late fees aren't taxed
+    sqlx::query!(
+        r#"INSERT INTO main.invoice_items (invoice_id, invoice_item_id, description, quantity, unit_price, taxable, late_fee)
+        VALUES ($1, $2, $3, 1, $4, false, true)"#,
+        invoice.invoice_id,
+        booking::generate_id(&state.db_pool).await,
+        description,
+        fee,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let totals = saved_totals(&mut tx, &invoice.invoice_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    //tax entered by hand is kept as it was
+    let tax = locked.tax_rate.map(|rate| Tax {
+        amount: tax_on(totals.taxable, rate, money.currency),
+        rate: Some(rate),
+    });
+    save_amounts(&mut tx, &invoice.invoice_id, &totals, tax.as_ref())
+        .await
+        .map_err(|e| e.to_string())?;
+    sqlx::query!(
+        "UPDATE main.invoices SET late_fees_applied = late_fees_applied + 1 WHERE invoice_id = $1",
+        invoice.invoice_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    fit_installments(&mut tx, &invoice.invoice_id)
+        .await
+        .map_err(|(_, Json(response))| response.message)?;
+    sync_payment_status(&mut tx, &invoice.invoice_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(booking_id) = &invoice.booking_id {
+        record_activity(
+            &mut *tx,
+            booking_id,
+            ActivityKind::LateFeeCharged,
+            &format!(
+                "{} of {} added to invoice #{}",
+                description,
+                money.format(fee),
+                invoice.invoice_number
+            ),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())
+}
+
+async fn send_overdue_reminder(
+    state: &AppState,
+    invoice: &OverdueInvoice,
+    now: OffsetDateTime,
+) -> Result<(), String> {
+    let Some(email) = invoice.email.clone() else {
+        return Ok(());
+    };
+    let money = Money::new(&invoice.currency, invoice.address_country.as_deref());
+    let timezone = invoice.timezone.as_deref();
+    let balance = balance_due(
+        invoice.amount_total.unwrap_or_default(),
+        invoice.amount_paid,
+    );
+    let stage = invoice.reminders_sent as usize;
+
+    //a payment plan lists what's been missed, otherwise the invoice's own due date
+    let installments = invoice_installments(&state.db_pool, &invoice.invoice_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut missed = String::new();
+    for status in installment_statuses(installments, invoice.amount_paid, now) {
+        if status.status == InstallmentState::Overdue {
+            missed.push_str(&format!(
+                "- {}: {} was due {}\n",
+                status.installment.label,
+                money.format(status.balance),
+                local_time(status.installment.due_date, timezone),
+            ));
+        }
+    }
+    if missed.is_empty() {
+        missed = match invoice.due_date {
+            Some(due_date) => format!("It was due {}.\n", local_time(due_date, timezone)),
+            None => String::new(),
+        };
+    }
+
+    let (subject, opening, closing) = match reminder_tone(stage, state.overdue.reminder_after.len())
+    {
+        ReminderTone::Friendly => (
+            format!("Reminder: invoice #{} is past due", invoice.invoice_number),
+            "Just a friendly reminder that we haven't received payment for",
+            "If you've already paid, thank you and please ignore this email.",
+        ),
+        ReminderTone::Firm => (
+            format!("Overdue: invoice #{}", invoice.invoice_number),
+            "We still haven't received payment for",
+            "Please pay as soon as you can, or reply to this email if there's a problem with the invoice.",
+        ),
+        ReminderTone::Final => (
+            format!(
+                "Final notice: invoice #{} is overdue",
+                invoice.invoice_number
+            ),
+            "This is a final notice that payment is still outstanding for",
+            "Please pay right away or contact us to arrange payment.",
+        ),
+    };
+    let reminder = OutgoingEmail {
+        to: email,
+        reply_to: state.mailer.admin_address(),
+        subject,
+        body: format!(
+            "Hi {},\n\n\
+            {} invoice #{}.\n\
+            {}\n\
+            Balance due: {}\n\n\
+            {}\n",
+            invoice.first_name.as_deref().unwrap_or("there"),
+            opening,
+            invoice.invoice_number,
+            missed,
+            money.format(balance),
+            closing,
+        ),
+    };
+    let detail = format!("\"{}\" sent to {}", reminder.subject, reminder.to);
+    //claim the stage first so two sweeps can't both send it
+    let claimed = sqlx::query!(
+        r#"UPDATE main.invoices SET reminders_sent = reminders_sent + 1, last_reminder_at = $1
+        WHERE invoice_id = $2 AND reminders_sent = $3"#,
+        now,
+        invoice.invoice_id,
+        invoice.reminders_sent,
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if claimed.rows_affected() == 0 {
+        return Ok(());
+    }
+    if let Err(e) = state.mailer.send(reminder).await {
+        //give the stage back so the next sweep tries again
+        sqlx::query!(
+            r#"UPDATE main.invoices SET reminders_sent = $1, last_reminder_at = $2
+            WHERE invoice_id = $3 AND reminders_sent = $4 AND last_reminder_at = $5"#,
+            invoice.reminders_sent,
+            invoice.last_reminder_at,
+            invoice.invoice_id,
+            invoice.reminders_sent + 1,
+            now,
+        )
+        .execute(&state.db_pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        return Err(e.to_string());
+    }
+    if let Some(booking_id) = &invoice.booking_id {
+        record_activity(&state.db_pool, booking_id, ActivityKind::EmailSent, &detail)
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_overdue_invoices(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<OverdueInvoice>>, StatusCode> {
+    overdue_invoices(&state.db_pool)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            println!("Error getting overdue invoices: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PauseReminders {
+    paused: bool,
+}
+
+//stops or restarts overdue reminders for one invoice, late fees still apply
+pub(crate) async fn pause_reminders(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+    Json(payload): Json<PauseReminders>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    let updated = sqlx::query!(
+        "UPDATE main.invoices SET reminders_paused = $1 WHERE invoice_id = $2",
+        payload.paused,
+        invoice_id
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error updating reminders: {}", e),
+            }),
+        )
+    })?;
+    if updated.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "Invoice not found".to_string(),
+            }),
+        ));
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: if payload.paused {
+                "Reminders paused".to_string()
+            } else {
+                "Reminders resumed".to_string()
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoicing::currency::CURRENCIES;
+    use time::macros::datetime;
+
+    #[test]
+    fn schedules_reminders_and_interest() {
+        let config = OverdueConfig {
+            reminder_after: vec![Duration::days(1), Duration::days(7), Duration::days(14)],
+            late_fee: LateFeePolicy::parse("interest:1.5"),
+            late_fee_after: Duration::days(14),
+            sweep_every: Duration::hours(1),
+        };
+        let overdue_at = datetime!(2026-05-01 09:00 UTC);
+        assert!(!reminder_due(
+            &config,
+            overdue_at,
+            0,
+            None,
+            datetime!(2026-05-01 12:00 UTC)
+        ));
+        assert!(reminder_due(
+            &config,
+            overdue_at,
+            0,
+            None,
+            datetime!(2026-05-02 09:00 UTC)
+        ));
+        //the second reminder waits for day 7
+        assert!(!reminder_due(
+            &config,
+            overdue_at,
+            1,
+            Some(datetime!(2026-05-02 09:00 UTC)),
+            datetime!(2026-05-03 09:00 UTC)
+        ));
+        assert!(!reminder_due(
+            &config,
+            overdue_at,
+            3,
+            Some(datetime!(2026-05-15 09:00 UTC)),
+            datetime!(2026-09-01 09:00 UTC)
+        ));
+        //after downtime the first reminder went out on day 20, the second waits 6 more days
+        //and the final one 7 after that instead of going out on the next sweeps
+        let late = Some(datetime!(2026-05-21 09:00 UTC));
+        assert!(!reminder_due(
+            &config,
+            overdue_at,
+            1,
+            late,
+            datetime!(2026-05-21 10:00 UTC)
+        ));
+        assert!(reminder_due(
+            &config,
+            overdue_at,
+            1,
+            late,
+            datetime!(2026-05-27 09:00 UTC)
+        ));
+        assert!(!reminder_due(
+            &config,
+            overdue_at,
+            2,
+            Some(datetime!(2026-05-27 09:00 UTC)),
+            datetime!(2026-05-27 10:00 UTC)
+        ));
+        assert_eq!(reminder_tone(0, 3), ReminderTone::Friendly);
+        assert_eq!(reminder_tone(1, 3), ReminderTone::Firm);
+        assert_eq!(reminder_tone(2, 3), ReminderTone::Final);
+
+        let policy = config.late_fee.unwrap();
+        assert_eq!(policy, LateFeePolicy::MonthlyInterest(Decimal::new(15, 1)));
+        let due = |now| late_fees_due(policy, config.late_fee_after, overdue_at, now);
+        assert_eq!(due(datetime!(2026-05-10 09:00 UTC)), 0);
+        assert_eq!(due(datetime!(2026-05-15 09:00 UTC)), 1);
+        assert_eq!(due(datetime!(2026-07-15 09:00 UTC)), 3);
+        //1.5% of $1234.56
+        assert_eq!(
+            policy.fee(Decimal::new(123456, 2), &CURRENCIES[0]),
+            Decimal::new(1852, 2)
+        );
+        assert_eq!(
+            LateFeePolicy::parse("fixed:25"),
+            Some(LateFeePolicy::Fixed(Decimal::from(25)))
+        );
+        assert_eq!(LateFeePolicy::parse("percent:-5"), None);
+    }
+}
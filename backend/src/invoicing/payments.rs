@@ -0,0 +1,256 @@
+use crate::AppState;
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::invoicing::currency::Money;
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use time::OffsetDateTime;
+
+//a payment received against an invoice
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Payment {
+    pub(crate) payment_id: i64,
+    pub(crate) invoice_id: String,
+    pub(crate) amount: Decimal,
+    pub(crate) method: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) paid_at: OffsetDateTime,
+    //check number, transaction id, etc.
+    pub(crate) reference: Option<String>,
+    pub(crate) notes: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewPayment {
+    pub(crate) amount: Decimal,
+    pub(crate) method: Option<String>,
+    //defaults to now
+    #[serde(default, with = "time::serde::iso8601::option")]
+    pub(crate) paid_at: Option<OffsetDateTime>,
+    pub(crate) reference: Option<String>,
+    pub(crate) notes: Option<String>,
+}
+
+fn payment_error(status: StatusCode, message: String) -> (StatusCode, Json<ApiResponse>) {
+    (status, Json(ApiResponse { message }))
+}
+
+//an invoice's payments, oldest first
+pub(crate) async fn invoice_payments<'e>(
+    executor: impl PgExecutor<'e>,
+    invoice_id: &str,
+) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as!(
+        Payment,
+        r#"SELECT payment_id, invoice_id, amount, method, paid_at, reference, notes
+        FROM main.payments WHERE invoice_id = $1 ORDER BY paid_at, payment_id"#,
+        invoice_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub(crate) fn amount_paid(payments: &[Payment]) -> Decimal {
+    payments.iter().map(|payment| payment.amount).sum()
+}
+
+//what's left to pay, never below zero
+pub(crate) fn balance_due(total: Decimal, paid: Decimal) -> Decimal {
+    (total - paid).max(Decimal::ZERO)
+}
+
+//updates payment_completed, paid_at and payment_method on an invoice from its payments,
+//call after anything that changes the payments or the invoice total
+pub(crate) async fn sync_payment_status(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE main.invoices i SET
+            payment_completed = p.paid > 0 AND p.paid >= COALESCE(i.amount_total, 0),
+            paid_at = CASE WHEN p.paid > 0 AND p.paid >= COALESCE(i.amount_total, 0) THEN p.last_paid_at END,
+            payment_method = COALESCE(p.last_method, i.payment_method)
+        FROM (
+            SELECT COALESCE(SUM(amount), 0) AS paid, MAX(paid_at) AS last_paid_at,
+                (SELECT method FROM main.payments WHERE invoice_id = $1 AND method IS NOT NULL
+                    ORDER BY paid_at DESC, payment_id DESC LIMIT 1) AS last_method
+            FROM main.payments WHERE invoice_id = $1
+        ) p
+        WHERE i.invoice_id = $1"#,
+        invoice_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+//adds a payment to the ledger and updates the invoice's paid status
+pub(crate) async fn insert_payment(
+    tx: &mut Transaction<'_, Postgres>,
+    invoice_id: &str,
+    payment: &NewPayment,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO main.payments (invoice_id, amount, method, paid_at, reference, notes)
+        VALUES ($1, $2, $3, $4, $5, $6)"#,
+        invoice_id,
+        payment.amount,
+        payment.method,
+        payment.paid_at.unwrap_or_else(OffsetDateTime::now_utc),
+        payment.reference,
+        payment.notes,
+    )
+    .execute(&mut **tx)
+    .await?;
+    sync_payment_status(tx, invoice_id).await
+}
+
+//records a deposit, part payment or full payment against an issued invoice
+pub(crate) async fn record_payment(
+    State(state): State<AppState>,
+    Path(invoice_id): Path<String>,
+    Json(payload): Json<NewPayment>,
+) -> Result<(StatusCode, Json<ApiResponse>), (StatusCode, Json<ApiResponse>)> {
+    if payload.amount <= Decimal::ZERO {
+        return Err(payment_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Payment amount must be more than zero".to_string(),
+        ));
+    }
+    let internal_error = |e: sqlx::Error| {
+        payment_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error recording payment: {}", e),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let invoice = sqlx::query!(
+        r#"SELECT invoice_number, booking_id, amount_total, issued_at, voided_at, currency
+        FROM main.invoices WHERE invoice_id = $1 FOR UPDATE"#,
+        invoice_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal_error)?
+    .ok_or(payment_error(
+        StatusCode::NOT_FOUND,
+        "Invoice not found".to_string(),
+    ))?;
+    if invoice.voided_at.is_some() {
+        return Err(payment_error(
+            StatusCode::CONFLICT,
+            "Can't record a payment on a voided invoice".to_string(),
+        ));
+    }
+    if invoice.issued_at.is_none() {
+        return Err(payment_error(
+            StatusCode::CONFLICT,
+            "Issue the invoice before recording payments".to_string(),
+        ));
+    }
+    let money = Money::new(&invoice.currency, None);
+    if !money.currency.is_exact(payload.amount) {
+        return Err(payment_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "{} amounts can't have more than {} decimal places",
+                money.currency.code, money.currency.minor_units
+            ),
+        ));
+    }
+    let paid = amount_paid(
+        &invoice_payments(&mut *tx, &invoice_id)
+            .await
+            .map_err(internal_error)?,
+    );
+    let due = balance_due(invoice.amount_total.unwrap_or_default(), paid);
+    if payload.amount > due {
+        return Err(payment_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "Payment is more than the balance due of {}",
+                money.format(due)
+            ),
+        ));
+    }
+
+    insert_payment(&mut tx, &invoice_id, &payload)
+        .await
+        .map_err(internal_error)?;
+    if let Some(booking_id) = &invoice.booking_id {
+        record_activity(
+            &mut *tx,
+            booking_id,
+            ActivityKind::PaymentRecorded,
+            &format!(
+                "{} paid on invoice #{}, {} left to pay",
+                money.format(payload.amount),
+                invoice.invoice_number,
+                money.format(due - payload.amount)
+            ),
+        )
+        .await
+        .map_err(internal_error)?;
+    }
+    tx.commit().await.map_err(internal_error)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: "Payment recorded".to_string(),
+        }),
+    ))
+}
+
+//removes a payment entered by mistake
+pub(crate) async fn delete_payment(
+    State(state): State<AppState>,
+    Path((invoice_id, payment_id)): Path<(String, i64)>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let internal_error = |e: sqlx::Error| {
+        payment_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Error deleting payment: {}", e),
+        )
+    };
+    let mut tx = state.db_pool.begin().await.map_err(internal_error)?;
+    let deleted = sqlx::query!(
+        "DELETE FROM main.payments WHERE invoice_id = $1 AND payment_id = $2",
+        invoice_id,
+        payment_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(internal_error)?;
+    if deleted.rows_affected() == 0 {
+        return Err(payment_error(
+            StatusCode::NOT_FOUND,
+            "Payment not found".to_string(),
+        ));
+    }
+    sync_payment_status(&mut tx, &invoice_id)
+        .await
+        .map_err(internal_error)?;
+    tx.commit().await.map_err(internal_error)?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_due_never_goes_negative() {
+        assert_eq!(
+            balance_due(Decimal::from(250), Decimal::from(100)),
+            Decimal::from(150)
+        );
+        assert_eq!(
+            balance_due(Decimal::from(250), Decimal::from(300)),
+            Decimal::ZERO
+        );
+    }
+}
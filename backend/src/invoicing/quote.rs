@@ -0,0 +1,808 @@
+use crate::AppState;
+use crate::booking;
+use crate::booking::schedule::{client_timezone, local_time};
+use crate::booking::timeline::{ActivityKind, record_activity};
+use crate::catalogue::{LineItemPreset, preset_line_items};
+use crate::clientele::Client;
+use crate::email::OutgoingEmail;
+use crate::invoicing::currency::{Money, billing_currency, currency_or_default};
+use crate::invoicing::discount::{Discount, check_line_discounts, totals};
+use crate::invoicing::invoice::{
+    ApiResponse, NewInvoiceInfo, NewInvoiceItem, StateCountry, billed_client, insert_invoice,
+};
+use crate::invoicing::invoice_generation::{
+    pdf_response, render_pdf, typst_client, typst_date, typst_line_items,
+};
+use crate::invoicing::tax::calculate_tax;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use typst::foundations::dict;
+
+//purpose the accept/decline link tokens are signed for
+const QUOTE_LINK: &str = "quote";
+//how long a quote stays open when no expiry is given
+const DEFAULT_VALID_FOR: Duration = Duration::days(30);
+//when the invoice an accepted quote turns into is due
+const INVOICE_DUE_AFTER: Duration = Duration::days(14);
+
+//Quote from POSTGRES database
+#[derive(Serialize, Deserialize)]
+pub struct Quote {
+    pub(crate) quote_id: String,
+    pub(crate) quote_number: i64,
+    pub(crate) client_id: String,
+    pub(crate) booking_id: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) created_at: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub(crate) expires_at: OffsetDateTime,
+    pub(crate) amount_subtotal: Decimal,
+    pub(crate) amount_tax: Decimal,
+    pub(crate) amount_total: Option<Decimal>,
+    pub(crate) notes: Option<String>,
+    //draft, sent, accepted or declined. views show "expired" for a sent quote past its expiry
+    pub(crate) status: String,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub(crate) sent_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::iso8601::option")]
+    pub(crate) responded_at: Option<OffsetDateTime>,
+    pub(crate) decline_reason: Option<String>,
+    pub(crate) invoice_id: Option<String>,
+    pub(crate) currency: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct QuoteItem {
+    quote_id: String,
+    quote_item_id: String,
+    pub(crate) description: String,
+    pub(crate) quantity: i32,
+    pub(crate) unit_price: Decimal,
+    pub(crate) taxable: bool,
+    pub(crate) discount_kind: Option<String>,
+    pub(crate) discount_value: Option<Decimal>,
+}
+
+impl QuoteItem {
+    pub(crate) fn discount(&self) -> Option<Discount> {
+        Discount::from_columns(self.discount_kind.as_deref(), self.discount_value)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReturnFullQuote {
+    pub(crate) quote: Quote,
+    pub(crate) quote_items: Vec<QuoteItem>,
+    pub(crate) client: Client,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewQuoteInfo {
+    client_id: Option<String>,
+    booking_id: Option<String>,
+    quote_items: Vec<NewInvoiceItem>,
+    //catalogue items added as extra lines
+    #[serde(default)]
+    presets: Vec<LineItemPreset>,
+    //overrides the tax calculated from the client's address
+    amount_tax: Option<Decimal>,
+    notes: Option<String>,
+    #[serde(default, with = "time::serde::iso8601::option")]
+    expires_at: Option<OffsetDateTime>,
+    //ISO 4217 code, defaults to USD. the invoice an accepted quote becomes is in the same currency
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EditQuoteInfo {
+    quote_items: Vec<NewInvoiceItem>,
+    //overrides the tax calculated from the client's address
+    amount_tax: Option<Decimal>,
+    notes: Option<String>,
+    #[serde(with = "time::serde::iso8601")]
+    expires_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeclineQuote {
+    reason: Option<String>,
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ApiResponse {
+            message: message.to_string(),
+        }),
+    )
+        .into_response()
+}
+
+//whether the client can still accept or decline
+fn respondable(quote: &Quote, now: OffsetDateTime) -> Result<(), (StatusCode, &'static str)> {
+    match quote.status.as_str() {
+        "accepted" => Err((StatusCode::CONFLICT, "This quote has already been accepted")),
+        "declined" => Err((StatusCode::CONFLICT, "This quote has already been declined")),
+        _ if now > quote.expires_at => Err((StatusCode::GONE, "This quote has expired")),
+        _ => Ok(()),
+    }
+}
+
+fn quote_link(state: &AppState, quote: &Quote) -> String {
+    let token = state
+        .link_signer
+        .sign(QUOTE_LINK, &quote.quote_id, quote.expires_at);
+    format!("{}/quote/{}", state.self_service.site_url, token)
+}
+
+async fn create_quote_items(
+    tx: &mut Transaction<'_, Postgres>,
+    quote_id: &str,
+    items: Vec<NewInvoiceItem>,
+    client: &sqlx::PgPool,
+) -> Result<(), sqlx::Error> {
+    for item in items {
+        let quote_item_id = booking::generate_id(client).await;
+        sqlx::query!(
+            r#"INSERT INTO main.quote_items (quote_item_id, quote_id, description, quantity, unit_price, taxable,
+                discount_kind, discount_value)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            quote_item_id,
+            quote_id,
+            item.description,
+            item.quantity,
+            item.unit_price,
+            item.taxable,
+            item.discount.as_ref().map(|discount| discount.kind.as_str()),
+            item.discount.as_ref().map(|discount| discount.value),
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn load_quote(client: &sqlx::PgPool, quote_id: &str) -> Result<ReturnFullQuote, Response> {
+    let internal = |e: sqlx::Error| {
+        println!("Error getting quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error getting quote")
+    };
+    let mut quote = sqlx::query_as!(
+        Quote,
+        "SELECT * FROM main.quotes WHERE quote_id = $1",
+        quote_id
+    )
+    .fetch_optional(client)
+    .await
+    .map_err(internal)?
+    .ok_or_else(|| error(StatusCode::NOT_FOUND, "ERROR: Quote_ID could not be found"))?;
+    if quote.status == "sent" && OffsetDateTime::now_utc() > quote.expires_at {
+        quote.status = "expired".to_string();
+    }
+    let quote_items = sqlx::query_as!(
+        QuoteItem,
+        "SELECT quote_id, quote_item_id, description, quantity, unit_price, taxable, discount_kind, discount_value FROM main.quote_items WHERE quote_id = $1",
+        quote_id
+    )
+    .fetch_all(client)
+    .await
+    .map_err(internal)?;
+    let client = sqlx::query_as!(
+        Client,
+        "SELECT * FROM main.clients WHERE client_id = $1",
+        quote.client_id
+    )
+    .fetch_one(client)
+    .await
+    .map_err(internal)?;
+    Ok(ReturnFullQuote {
+        quote,
+        quote_items,
+        client,
+    })
+}
+
+//admin drafts a quote for a client or booking, nothing is sent until send_quote
+pub async fn create_quote(
+    State(state): State<AppState>,
+    Json(payload): Json<NewQuoteInfo>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let client = &state.db_pool;
+    let client_id = billed_client(payload.client_id, payload.booking_id.as_deref(), client)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let currency =
+        billing_currency(payload.currency.as_deref()).map_err(IntoResponse::into_response)?;
+    let mut quote_items = payload.quote_items;
+    quote_items.extend(
+        preset_line_items(client, &payload.presets, currency)
+            .await
+            .map_err(IntoResponse::into_response)?,
+    );
+    let expires_at = payload
+        .expires_at
+        .unwrap_or_else(|| OffsetDateTime::now_utc() + DEFAULT_VALID_FOR);
+    if let Err(message) = check_line_discounts(&quote_items) {
+        return Err(error(StatusCode::UNPROCESSABLE_ENTITY, message));
+    }
+    //quotes only have line discounts, coupons are applied when invoicing
+    let totals = totals(&quote_items, &[], currency);
+    let tax = calculate_tax(
+        client,
+        &client_id,
+        totals.taxable,
+        payload.amount_tax,
+        currency,
+    )
+    .await
+    .map_err(|e| {
+        println!("Error calculating tax: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error creating quote")
+    })?;
+    let quote_id = booking::generate_id(client).await;
+
+    let mut tx = client.begin().await.map_err(|e| {
+        println!("Error creating quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error creating quote")
+    })?;
+    sqlx::query!(
+        r#"INSERT INTO main.quotes (quote_id, client_id, booking_id, expires_at, amount_subtotal, amount_tax, notes,
+            currency)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        quote_id,
+        client_id,
+        payload.booking_id,
+        expires_at,
+        totals.subtotal,
+        tax.amount,
+        payload.notes,
+        currency.code,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error creating quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error creating quote")
+    })?;
+    create_quote_items(&mut tx, &quote_id, quote_items, client)
+        .await
+        .map_err(|e| {
+            println!("Error creating quote items: {}", e);
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error creating quote items",
+            )
+        })?;
+    tx.commit().await.map_err(|e| {
+        println!("Error creating quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error creating quote")
+    })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse {
+            message: format!("New Quote {} Successfully Created", quote_id),
+        }),
+    ))
+}
+
+pub async fn view_quote(
+    State(state): State<AppState>,
+    Path(quote_id): Path<String>,
+) -> Result<Json<ReturnFullQuote>, Response> {
+    Ok(Json(load_quote(&state.db_pool, &quote_id).await?))
+}
+
+//changes the lines, tax, notes or expiry of a quote the client hasn't answered yet
+pub async fn edit_quote(
+    State(state): State<AppState>,
+    Path(quote_id): Path<String>,
+    Json(payload): Json<EditQuoteInfo>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let client = &state.db_pool;
+    let mut tx = client.begin().await.map_err(|e| {
+        println!("Error editing quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+    })?;
+    let current = sqlx::query!(
+        "SELECT client_id, currency FROM main.quotes WHERE quote_id = $1",
+        quote_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error editing quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+    })?
+    .ok_or_else(|| error(StatusCode::NOT_FOUND, "ERROR: Quote_ID could not be found"))?;
+    if let Err(message) = check_line_discounts(&payload.quote_items) {
+        return Err(error(StatusCode::UNPROCESSABLE_ENTITY, message));
+    }
+    let currency = currency_or_default(&current.currency);
+    let totals = totals(&payload.quote_items, &[], currency);
+    let tax = calculate_tax(
+        client,
+        &current.client_id,
+        totals.taxable,
+        payload.amount_tax,
+        currency,
+    )
+    .await
+    .map_err(|e| {
+        println!("Error calculating tax: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+    })?;
+    let updated = sqlx::query!(
+        r#"UPDATE main.quotes SET amount_subtotal = $1, amount_tax = $2, notes = $3, expires_at = $4
+        WHERE quote_id = $5 AND status IN ('draft', 'sent')"#,
+        totals.subtotal,
+        tax.amount,
+        payload.notes,
+        payload.expires_at,
+        quote_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error editing quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+    })?;
+    if updated.rows_affected() == 0 {
+        return Err(error(
+            StatusCode::CONFLICT,
+            "Quote not found or already answered by the client",
+        ));
+    }
+    sqlx::query!("DELETE FROM main.quote_items WHERE quote_id = $1", quote_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            println!("Error removing old quote items: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+        })?;
+    create_quote_items(&mut tx, &quote_id, payload.quote_items, client)
+        .await
+        .map_err(|e| {
+            println!("Error updating quote items: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+        })?;
+    tx.commit().await.map_err(|e| {
+        println!("Error editing quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error editing quote")
+    })?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Quote Successfully Updated".to_string(),
+        }),
+    ))
+}
+
+//emails the client a link to view, accept or decline the quote
+pub async fn send_quote(
+    State(state): State<AppState>,
+    Path(quote_id): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let ReturnFullQuote {
+        quote,
+        quote_items,
+        client,
+    } = load_quote(&state.db_pool, &quote_id).await?;
+    respondable(&quote, OffsetDateTime::now_utc())
+        .map_err(|(status, message)| error(status, message))?;
+
+    let money = Money::new(&quote.currency, client.address_country.as_deref());
+    let mut lines = String::new();
+    for item in &quote_items {
+        lines.push_str(&format!(
+            "- {} x{} @ {}\n",
+            item.description,
+            item.quantity,
+            money.format(item.unit_price)
+        ));
+    }
+    let email = OutgoingEmail {
+        to: client.email.clone(),
+        reply_to: state.mailer.admin_address(),
+        subject: format!("Your quote #{}", quote.quote_number),
+        body: format!(
+            "Hi {},\n\n\
+            Here's your quote #{}:\n\n{}\n\
+            Total: {}\n\n\
+            It's valid until {}. You can view, accept or decline it here:\n{}\n",
+            client.first_name,
+            quote.quote_number,
+            lines,
+            money.format(quote.amount_total.unwrap_or_default()),
+            local_time(quote.expires_at, client.timezone.as_deref()),
+            quote_link(&state, &quote),
+        ),
+    };
+    let detail = format!("Quote #{} sent to {}", quote.quote_number, email.to);
+    state.mailer.send(email).await.map_err(|e| {
+        println!("Error sending quote: {}", e);
+        error(StatusCode::BAD_GATEWAY, "Error sending quote email")
+    })?;
+
+    sqlx::query!(
+        "UPDATE main.quotes SET status = 'sent', sent_at = $1 WHERE quote_id = $2",
+        OffsetDateTime::now_utc(),
+        quote_id,
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        println!("Error updating quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error updating quote")
+    })?;
+    if let Some(booking_id) = &quote.booking_id {
+        record_activity(&state.db_pool, booking_id, ActivityKind::QuoteSent, &detail)
+            .await
+            .unwrap_or_else(|e| println!("Error recording booking activity: {}", e));
+    }
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Quote sent".to_string(),
+        }),
+    ))
+}
+
+//renders a quote through the invoice template
+fn quote_pdf(full_quote: ReturnFullQuote) -> Response {
+    let ReturnFullQuote {
+        quote,
+        quote_items,
+        client,
+    } = full_quote;
+    let timezone = client_timezone(client.timezone.as_deref());
+    let money = Money::new(&quote.currency, client.address_country.as_deref());
+    let expires_at = typst_date(quote.expires_at, timezone);
+    let input_data = dict! {
+        "document" => "quote",
+        //the template reads the number, totals and date from the invoice fields,
+        //for a quote the due date is when it expires
+        "invoice" => dict! {
+            "invoice_number" => quote.quote_number,
+            "due_date" => expires_at,
+            "amount_subtotal" => money.format(quote.amount_subtotal),
+            "amount_tax" => money.format(quote.amount_tax),
+            "amount_total" => money.format(quote.amount_total.unwrap_or_default()),
+            "notes" => quote.notes
+        },
+        "quote" => dict! {
+            "quote_number" => quote.quote_number,
+            "expires_at" => expires_at,
+            "status" => quote.status
+        },
+        "invoice_items" => typst_line_items(
+            quote_items
+                .iter()
+                .map(|item| {
+                    (
+                        item.description.as_str(),
+                        item.quantity,
+                        item.unit_price,
+                        item.taxable,
+                        item.discount(),
+                    )
+                }),
+            &money,
+        ),
+        "currency" => money.currency.code,
+        "client" => typst_client(client),
+    };
+    pdf_response(
+        render_pdf(input_data),
+        &format!("quote-{}.pdf", quote.quote_number),
+    )
+}
+
+pub async fn print_quote(
+    State(state): State<AppState>,
+    Path(quote_id): Path<String>,
+) -> Result<Response, Response> {
+    Ok(quote_pdf(load_quote(&state.db_pool, &quote_id).await?))
+}
+
+//looks up the quote a client link was issued for
+async fn quote_from_token(state: &AppState, token: &str) -> Result<ReturnFullQuote, Response> {
+    let quote_id = state
+        .link_signer
+        .verify(QUOTE_LINK, token)
+        .map_err(|_| error(StatusCode::NOT_FOUND, "This link is invalid or has expired"))?;
+    load_quote(&state.db_pool, &quote_id).await
+}
+
+pub async fn view_quote_by_token(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<ReturnFullQuote>, Response> {
+    Ok(Json(quote_from_token(&state, &token).await?))
+}
+
+pub async fn print_quote_by_token(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, Response> {
+    Ok(quote_pdf(quote_from_token(&state, &token).await?))
+}
+
+fn notify_admin(state: &AppState, quote: &Quote, client: &Client, subject: String, body: String) {
+    let Some(admin) = state.mailer.admin_address() else {
+        return;
+    };
+    let notification = OutgoingEmail {
+        to: admin,
+        reply_to: Some(client.email.clone()),
+        subject,
+        body,
+    };
+    let mailer = state.mailer.clone();
+    let client = state.db_pool.clone();
+    let booking_id = quote.booking_id.clone();
+    tokio::spawn(async move {
+        match booking_id {
+            Some(booking_id) => {
+                booking::send_booking_email(&mailer, &client, &booking_id, notification).await
+            }
+            None => mailer
+                .send(notification)
+                .await
+                .unwrap_or_else(|e| println!("Error sending quote notification: {}", e)),
+        }
+    });
+}
+
+//client accepts the quote, which turns it into an invoice
+pub async fn accept_quote(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let ReturnFullQuote {
+        quote,
+        quote_items,
+        client,
+    } = quote_from_token(&state, &token).await?;
+    let now = OffsetDateTime::now_utc();
+    respondable(&quote, now).map_err(|(status, message)| error(status, message))?;
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        println!("Error accepting quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error accepting quote")
+    })?;
+    //claim the quote first so a double click can't create two invoices. respondable ran before
+    //the transaction, so it's checked again here: only a sent quote that hasn't expired
+    let claimed = sqlx::query!(
+        r#"UPDATE main.quotes SET status = 'accepted', responded_at = $1
+        WHERE quote_id = $2 AND status = 'sent' AND expires_at > $1"#,
+        now,
+        quote.quote_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error accepting quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error accepting quote")
+    })?;
+    if claimed.rows_affected() == 0 {
+        return Err(error(
+            StatusCode::CONFLICT,
+            "This quote has already been answered or is no longer open",
+        ));
+    }
+
+    let invoice_id = insert_invoice(
+        &mut tx,
+        &state.db_pool,
+        NewInvoiceInfo {
+            client_id: Some(quote.client_id.clone()),
+            booking_id: quote.booking_id.clone(),
+            invoice_items: quote_items
+                .into_iter()
+                .map(|item| NewInvoiceItem {
+                    discount: item.discount(),
+                    description: item.description,
+                    quantity: item.quantity,
+                    unit_price: item.unit_price,
+                    taxable: item.taxable,
+                    late_fee: false,
+                })
+                .collect(),
+            presets: Vec::new(),
+            currency: Some(quote.currency.clone()),
+            discount: None,
+            coupon_code: None,
+            //keep the tax the client agreed to
+            amount_tax: Some(quote.amount_tax),
+            notes: quote.notes.clone(),
+            due_date: now + INVOICE_DUE_AFTER,
+            //the client already agreed to it
+            issue: true,
+            address_street: None,
+            address_city: None,
+            address_state: None,
+            address_zip: None,
+            address_country: StateCountry {
+                value: String::new(),
+                label: String::new(),
+            },
+        },
+    )
+    .await
+    .map_err(IntoResponse::into_response)?;
+
+    sqlx::query!(
+        "UPDATE main.quotes SET invoice_id = $1 WHERE quote_id = $2",
+        invoice_id,
+        quote.quote_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error linking invoice to quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error accepting quote")
+    })?;
+    if let Some(booking_id) = &quote.booking_id {
+        record_activity(
+            &mut *tx,
+            booking_id,
+            ActivityKind::QuoteAccepted,
+            &format!(
+                "Quote #{} accepted, invoice {} created",
+                quote.quote_number, invoice_id
+            ),
+        )
+        .await
+        .map_err(|e| {
+            println!("Error recording booking activity: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "Error accepting quote")
+        })?;
+    }
+    tx.commit().await.map_err(|e| {
+        println!("Error accepting quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error accepting quote")
+    })?;
+
+    notify_admin(
+        &state,
+        &quote,
+        &client,
+        format!("Quote #{} accepted", quote.quote_number),
+        format!(
+            "{} {} accepted quote #{} ({}). Invoice {} was created from it.\n",
+            client.first_name,
+            client.last_name,
+            quote.quote_number,
+            Money::new(&quote.currency, client.address_country.as_deref())
+                .format(quote.amount_total.unwrap_or_default()),
+            invoice_id
+        ),
+    );
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Quote accepted, thank you!".to_string(),
+        }),
+    ))
+}
+
+pub async fn decline_quote(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Json(payload): Json<DeclineQuote>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let ReturnFullQuote { quote, client, .. } = quote_from_token(&state, &token).await?;
+    let now = OffsetDateTime::now_utc();
+    respondable(&quote, now).map_err(|(status, message)| error(status, message))?;
+    let reason = payload
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    let mut tx = state.db_pool.begin().await.map_err(|e| {
+        println!("Error declining quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error declining quote")
+    })?;
+    let declined = sqlx::query!(
+        r#"UPDATE main.quotes SET status = 'declined', responded_at = $1, decline_reason = $2
+        WHERE quote_id = $3 AND status = 'sent' AND expires_at > $1"#,
+        now,
+        reason,
+        quote.quote_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        println!("Error declining quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error declining quote")
+    })?;
+    if declined.rows_affected() == 0 {
+        return Err(error(
+            StatusCode::CONFLICT,
+            "This quote has already been answered or is no longer open",
+        ));
+    }
+    let detail = match &reason {
+        Some(reason) => format!("Quote #{} declined: {}", quote.quote_number, reason),
+        None => format!("Quote #{} declined", quote.quote_number),
+    };
+    if let Some(booking_id) = &quote.booking_id {
+        record_activity(&mut *tx, booking_id, ActivityKind::QuoteDeclined, &detail)
+            .await
+            .map_err(|e| {
+                println!("Error recording booking activity: {}", e);
+                error(StatusCode::INTERNAL_SERVER_ERROR, "Error declining quote")
+            })?;
+    }
+    tx.commit().await.map_err(|e| {
+        println!("Error declining quote: {}", e);
+        error(StatusCode::INTERNAL_SERVER_ERROR, "Error declining quote")
+    })?;
+
+    notify_admin(
+        &state,
+        &quote,
+        &client,
+        format!("Quote #{} declined", quote.quote_number),
+        format!("{} {}: {}\n", client.first_name, client.last_name, detail),
+    );
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Quote declined".to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn quote(status: &str, expires_at: OffsetDateTime) -> Quote {
+        Quote {
+            quote_id: "abc123".to_string(),
+            quote_number: 1,
+            client_id: "def456".to_string(),
+            booking_id: None,
+            created_at: datetime!(2026-03-01 12:00 UTC),
+            expires_at,
+            amount_subtotal: Decimal::ZERO,
+            amount_tax: Decimal::ZERO,
+            amount_total: None,
+            notes: None,
+            status: status.to_string(),
+            sent_at: None,
+            responded_at: None,
+            decline_reason: None,
+            invoice_id: None,
+            currency: "USD".to_string(),
+        }
+    }
+
+    #[test]
+    fn only_open_quotes_can_be_answered() {
+        let now = datetime!(2026-03-10 12:00 UTC);
+        let expires_at = datetime!(2026-03-31 12:00 UTC);
+        assert!(respondable(&quote("sent", expires_at), now).is_ok());
+        assert_eq!(
+            respondable(&quote("accepted", expires_at), now)
+                .unwrap_err()
+                .0,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            respondable(&quote("sent", datetime!(2026-03-09 12:00 UTC)), now)
+                .unwrap_err()
+                .0,
+            StatusCode::GONE
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::AppState;
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct RevenueQuery {
+    year: Option<i32>,
+    //only used with a year
+    month: Option<i32>,
+}
+
+//totals for issued invoices in a period, drafts and voided invoices aren't revenue.
+//one per currency, amounts in different currencies are never added together
+#[derive(Serialize, Deserialize)]
+pub struct RevenueReport {
+    currency: String,
+    invoice_count: i64,
+    amount_invoiced: Decimal,
+    //from the payments ledger
+    amount_paid: Decimal,
+    amount_outstanding: Decimal,
+    //left out of the totals above
+    voided_count: i64,
+}
+
+pub(crate) async fn revenue_report(
+    State(state): State<AppState>,
+    Query(q): Query<RevenueQuery>,
+) -> Result<Json<Vec<RevenueReport>>, StatusCode> {
+    let report = sqlx::query_as!(
+        RevenueReport,
+        r#"SELECT
+            currency AS "currency!",
+            COUNT(*) FILTER (WHERE voided_at IS NULL) AS "invoice_count!",
+            COALESCE(SUM(amount_total) FILTER (WHERE voided_at IS NULL), 0) AS "amount_invoiced!",
+            COALESCE(SUM(paid) FILTER (WHERE voided_at IS NULL), 0) AS "amount_paid!",
+            COALESCE(SUM(GREATEST(amount_total - paid, 0)) FILTER (WHERE voided_at IS NULL), 0) AS "amount_outstanding!",
+            COUNT(*) FILTER (WHERE voided_at IS NOT NULL) AS "voided_count!"
+        FROM (
+            SELECT i.currency, i.amount_total, i.voided_at,
+                (SELECT COALESCE(SUM(amount), 0) FROM main.payments p WHERE p.invoice_id = i.invoice_id) AS paid
+            FROM main.invoices i
+            WHERE i.issued_at IS NOT NULL
+                AND ($1::integer IS NULL OR EXTRACT(YEAR FROM i.issued_at) = $1::integer)
+                AND (($2::integer IS NULL OR $1::integer IS NULL) OR EXTRACT(MONTH FROM i.issued_at) = $2::integer)
+        ) invoices
+        GROUP BY currency
+        ORDER BY currency"#,
+        q.year,
+        q.month,
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(|e| {
+        println!("Error getting revenue: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(report))
+}
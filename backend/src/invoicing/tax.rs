@@ -0,0 +1,230 @@
+use crate::AppState;
+use crate::invoicing::currency::Currency;
+use crate::invoicing::invoice::ApiResponse;
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+//sales tax for a state, or a whole country when state is empty
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct TaxRate {
+    pub(crate) country: String,
+    pub(crate) state: String,
+    //percent
+    pub(crate) rate: Decimal,
+    pub(crate) name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewTaxRate {
+    country: String,
+    #[serde(default)]
+    state: String,
+    rate: Decimal,
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TaxRateQuery {
+    country: String,
+    #[serde(default)]
+    state: String,
+}
+
+//the tax on an invoice or quote and the rate it came from, rate is None when the tax was entered by hand
+#[derive(Debug, PartialEq)]
+pub(crate) struct Tax {
+    pub(crate) amount: Decimal,
+    pub(crate) rate: Option<Decimal>,
+}
+
+//tax on the taxable amount from discount::totals, rounded for the currency
+pub(crate) fn tax_on(taxable: Decimal, rate: Decimal, currency: &Currency) -> Decimal {
+    currency.round(taxable * rate / Decimal::from(100))
+}
+
+//the rate for a state, falling back to the country wide rate
+pub(crate) async fn rate_for(
+    client: &sqlx::PgPool,
+    country: &str,
+    state: Option<&str>,
+) -> Result<Option<Decimal>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT rate FROM main.tax_rates
+        WHERE country = $1 AND (state = '' OR state = $2)
+        ORDER BY state DESC LIMIT 1"#,
+        country,
+        state.unwrap_or_default(),
+    )
+    .fetch_optional(client)
+    .await
+}
+
+//amount_tax when it was given by hand, otherwise the tax at rate. no rate means no tax
+pub(crate) fn tax_for(
+    amount_tax: Option<Decimal>,
+    rate: Option<Decimal>,
+    taxable: Decimal,
+    currency: &Currency,
+) -> Tax {
+    match (amount_tax, rate) {
+        (Some(amount), _) => Tax {
+            amount: currency.round(amount),
+            rate: None,
+        },
+        (None, Some(rate)) => Tax {
+            amount: tax_on(taxable, rate, currency),
+            rate: Some(rate),
+        },
+        (None, None) => Tax {
+            amount: Decimal::ZERO,
+            rate: None,
+        },
+    }
+}
+
+//the tax for a client's billing address, unless amount_tax was given by hand.
+//no address or no rate for it means no tax
+pub(crate) async fn calculate_tax(
+    client: &sqlx::PgPool,
+    client_id: &str,
+    taxable: Decimal,
+    amount_tax: Option<Decimal>,
+    currency: &Currency,
+) -> Result<Tax, sqlx::Error> {
+    if amount_tax.is_some() {
+        return Ok(tax_for(amount_tax, None, taxable, currency));
+    }
+    let address = sqlx::query!(
+        "SELECT address_state, address_country FROM main.clients WHERE client_id = $1",
+        client_id
+    )
+    .fetch_one(client)
+    .await?;
+    let rate = match address.address_country.as_deref() {
+        Some(country) if !country.is_empty() => {
+            rate_for(client, country, address.address_state.as_deref()).await?
+        }
+        _ => None,
+    };
+    Ok(tax_for(None, rate, taxable, currency))
+}
+
+pub async fn get_tax_rates(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<TaxRate>>, StatusCode> {
+    sqlx::query_as!(
+        TaxRate,
+        "SELECT country, state, rate, name FROM main.tax_rates ORDER BY country, state"
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        println!("Error getting tax rates: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+//adds or replaces the rate for a state or country, invoices already saved keep their tax
+pub async fn save_tax_rate(
+    State(state): State<AppState>,
+    Json(payload): Json<NewTaxRate>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let mut errors = ValidationErrors::new();
+    if payload.country.trim().is_empty() {
+        errors.add("country", "Country is required");
+    }
+    if payload.rate.is_sign_negative() || payload.rate > Decimal::from(100) {
+        errors.add("rate", "Rate must be a percent between 0 and 100");
+    }
+    errors.finish(()).map_err(|errors| errors.into_response())?;
+    sqlx::query!(
+        r#"INSERT INTO main.tax_rates (country, state, rate, name, updated_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (country, state) DO UPDATE SET rate = $3, name = $4, updated_at = $5"#,
+        payload.country.trim(),
+        payload.state.trim(),
+        payload.rate,
+        payload.name,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error saving tax rate: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Tax rate saved".to_string(),
+        }),
+    ))
+}
+
+pub async fn delete_tax_rate(
+    State(state): State<AppState>,
+    Query(q): Query<TaxRateQuery>,
+) -> Result<StatusCode, (StatusCode, Json<ApiResponse>)> {
+    let deleted = sqlx::query!(
+        "DELETE FROM main.tax_rates WHERE country = $1 AND state = $2",
+        q.country,
+        q.state
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error deleting tax rate: {}", e),
+            }),
+        )
+    })?;
+    if deleted.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "Tax rate not found".to_string(),
+            }),
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoicing::currency::{CURRENCIES, find_currency};
+
+    #[test]
+    fn rounds_tax_to_the_cent() {
+        //8.875% of $80
+        assert_eq!(
+            tax_on(Decimal::from(80), Decimal::new(8875, 3), &CURRENCIES[0]),
+            Decimal::new(710, 2)
+        );
+        //8.875% of $10.01 is 0.8883875
+        assert_eq!(
+            tax_on(Decimal::new(1001, 2), Decimal::new(8875, 3), &CURRENCIES[0]),
+            Decimal::new(89, 2)
+        );
+        //no fractional yen
+        let jpy = find_currency("JPY").unwrap();
+        assert_eq!(
+            tax_on(Decimal::from(1005), Decimal::from(10), jpy),
+            Decimal::from(101)
+        );
+    }
+}
@@ -0,0 +1,140 @@
+#let data = sys.inputs
+// quotes share this template, their number, totals and expiry come in the invoice fields
+#let is_quote = data.at("document", default: "invoice") == "quote"
+#let quote = data.at("quote", default: none)
+#let invoice = data.invoice
+#let client = data.client
+#let items = data.at("invoice_items", default: ())
+#let payments = data.at("payments", default: ())
+// the payment plan, earliest due first. empty when the invoice is due in one payment
+#let installments = data.at("installments", default: ())
+
+#let long_date(date) = if date != none { date.display("[month repr:long] [day], [year]") }
+// a discount from typst_discount, fixed values are already formatted as money
+#let discount_text(discount) = if discount != none {
+  if discount.kind == "percent" [#discount.value%] else [#discount.value]
+}
+#let filled(..values) = values.pos().filter(value => value != none and value != "")
+
+// voided invoices are stamped VOID and drafts DRAFT, quotes that can't be accepted by their status
+#let stamp = if is_quote {
+  if quote.status in ("draft", "declined", "expired") { upper(quote.status) }
+} else if invoice.at("void", default: false) {
+  "VOID"
+} else if invoice.at("draft", default: false) {
+  "DRAFT"
+}
+#set page(
+  paper: "us-letter",
+  margin: 2cm,
+  background: if stamp != none {
+    rotate(-30deg, text(size: 96pt, weight: "bold", fill: red.transparentize(70%), stamp))
+  },
+)
+#set text(size: 10pt)
+#set table(stroke: none, inset: (x: 4pt, y: 5pt))
+
+#grid(
+  columns: (1fr, auto),
+  if is_quote [= Quote #quote.quote_number] else [= Invoice #invoice.invoice_number],
+  align(right)[
+    #if is_quote [
+      *Valid until* #long_date(quote.expires_at) \
+      *Status* #quote.status
+    ] else if invoice.due_date != none [*Due* #long_date(invoice.due_date)]
+  ],
+)
+
+#if invoice.at("void", default: false) [
+  This invoice was voided#if invoice.voided_at != none [ on #long_date(invoice.voided_at)]#if invoice.void_reason != none [: #invoice.void_reason].
+]
+
+#v(1em)
+#if is_quote [*Prepared for*] else [*Bill to*] \
+#client.first_name #client.last_name \
+#for line in (
+  filled(client.address_street),
+  filled(client.address_city, client.address_state, client.address_zip),
+  filled(client.address_country),
+  filled(client.email),
+  filled(client.phone),
+) {
+  if line.len() > 0 [#line.join(", ") \ ]
+}
+
+#v(1em)
+#table(
+  columns: (1fr, auto, auto, auto, auto),
+  align: (left, right, right, right, right),
+  table.header([*Description*], [*Qty*], [*Unit price*], [*Discount*], [*Total*]),
+  table.hline(),
+  ..items.map(item => (
+    [#item.description#if not item.taxable [ #text(size: 8pt)[(not taxed)]]],
+    [#item.quantity],
+    [#item.unit_price],
+    discount_text(item.discount),
+    [#item.total],
+  )).flatten(),
+  table.hline(),
+)
+
+// the invoice discount and coupon come off the subtotal together, before tax
+#let discount = invoice.at("discount", default: none)
+#let coupon_code = invoice.at("coupon_code", default: none)
+#let tax_rate = invoice.at("tax_rate", default: none)
+#let discount_label = filled(
+  if discount != none [Discount #discount_text(discount)],
+  if coupon_code != none [Coupon #coupon_code],
+)
+#align(right, table(
+  columns: (auto, auto),
+  align: (left, right),
+  [Subtotal], [#invoice.amount_subtotal],
+  ..if discount_label.len() > 0 {
+    ([#discount_label.join(", ")], [−#invoice.amount_discount])
+  },
+  [Tax#if tax_rate != none [ (#tax_rate%)]], [#invoice.amount_tax],
+  table.hline(),
+  [*Total*], [*#invoice.amount_total*],
+  ..if payments.len() > 0 { ([Paid], [−#data.amount_paid]) },
+  // nothing is owed on a quote until it's accepted and invoiced
+  ..if not is_quote { ([*Balance due*], [*#data.balance_due*]) },
+))
+
+#if payments.len() > 0 [
+  == Payments
+  #table(
+    columns: (auto, 1fr, 1fr, auto),
+    align: (left, left, left, right),
+    table.header([*Date*], [*Method*], [*Reference*], [*Amount*]),
+    table.hline(),
+    ..payments.map(payment => (
+      long_date(payment.paid_at),
+      [#payment.method],
+      [#payment.reference],
+      [#payment.amount],
+    )).flatten(),
+  )
+]
+
+#if installments.len() > 0 [
+  == Payment schedule
+  #table(
+    columns: 6,
+    table.header([*Payment*], [*Due*], [*Amount*], [*Paid*], [*Balance*], [*Status*]),
+    table.hline(),
+    ..installments.map(installment => (
+      [#installment.label],
+      long_date(installment.due_date),
+      [#installment.amount],
+      [#installment.amount_paid],
+      [#installment.balance],
+      [#installment.status],
+    )).flatten()
+  )
+]
+
+#if invoice.notes != none and invoice.notes != "" [
+  == Notes
+  #invoice.notes
+]
@@ -0,0 +1,173 @@
+use crate::AppState;
+use crate::booking::reminders::send_session_reminder;
+use crate::invoicing::overdue::run_overdue_sweep;
+use serde_json::Value;
+use sqlx::PgExecutor;
+use time::{Duration, OffsetDateTime};
+
+//how often the scheduler looks for due jobs
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+//a job that's still locked after this is assumed to have crashed and is run again
+const LOCK_DURATION: Duration = Duration::minutes(5);
+//failed jobs are retried with a growing delay, then given up on
+const MAX_ATTEMPTS: i32 = 5;
+const RETRY_DELAY: Duration = Duration::minutes(10);
+
+//kinds of background work, stored in scheduled_jobs.kind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum JobKind {
+    SessionReminder,
+    //checks every invoice for overdue reminders and late fees, queues the next sweep when it runs
+    OverdueSweep,
+}
+
+impl JobKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::SessionReminder => "session_reminder",
+            JobKind::OverdueSweep => "overdue_sweep",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<JobKind> {
+        match kind {
+            "session_reminder" => Some(JobKind::SessionReminder),
+            "overdue_sweep" => Some(JobKind::OverdueSweep),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct Job {
+    pub(crate) job_id: i64,
+    pub(crate) kind: String,
+    pub(crate) reference_id: String,
+    pub(crate) payload: Value,
+    pub(crate) attempts: i32,
+}
+
+//queues a job to run at run_at, works with a pool or an open transaction
+pub(crate) async fn schedule_job<'e>(
+    executor: impl PgExecutor<'e>,
+    kind: JobKind,
+    reference_id: &str,
+    run_at: OffsetDateTime,
+    payload: Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"INSERT INTO main.scheduled_jobs (kind, reference_id, payload, run_at)
+        VALUES ($1, $2, $3, $4)"#,
+        kind.as_str(),
+        reference_id,
+        payload,
+        run_at,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+//drops every job of a kind that hasn't run yet for a reference
+pub(crate) async fn cancel_jobs<'e>(
+    executor: impl PgExecutor<'e>,
+    kind: JobKind,
+    reference_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM main.scheduled_jobs
+        WHERE kind = $1 AND reference_id = $2 AND completed_at IS NULL"#,
+        kind.as_str(),
+        reference_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+//locks the jobs that are due so another scheduler (or a restarted one) doesn't run them twice
+async fn claim_due_jobs(client: &sqlx::PgPool) -> Result<Vec<Job>, sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+    sqlx::query_as!(
+        Job,
+        r#"UPDATE main.scheduled_jobs SET locked_until = $1, attempts = attempts + 1
+        WHERE job_id IN (
+            SELECT job_id FROM main.scheduled_jobs
+            WHERE completed_at IS NULL AND run_at <= $2 AND attempts < $3
+                AND (locked_until IS NULL OR locked_until < $2)
+            ORDER BY run_at LIMIT 20
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING job_id, kind, reference_id, payload, attempts"#,
+        now + LOCK_DURATION,
+        now,
+        MAX_ATTEMPTS,
+    )
+    .fetch_all(client)
+    .await
+}
+
+async fn run_job(state: &AppState, job: &Job) -> Result<(), String> {
+    match JobKind::parse(&job.kind) {
+        Some(JobKind::SessionReminder) => {
+            send_session_reminder(state, &job.reference_id, &job.payload).await
+        }
+        Some(JobKind::OverdueSweep) => run_overdue_sweep(state).await,
+        None => Err(format!("unknown job kind: {}", job.kind)),
+    }
+}
+
+async fn finish_job(
+    client: &sqlx::PgPool,
+    job: &Job,
+    result: Result<(), String>,
+) -> Result<(), sqlx::Error> {
+    match result {
+        Ok(()) => {
+            sqlx::query!(
+                r#"UPDATE main.scheduled_jobs SET completed_at = $1, locked_until = NULL, last_error = NULL
+                WHERE job_id = $2"#,
+                OffsetDateTime::now_utc(),
+                job.job_id,
+            )
+            .execute(client)
+            .await?;
+        }
+        Err(e) => {
+            println!(
+                "{} job {} failed (attempt {}): {}",
+                job.kind, job.job_id, job.attempts, e
+            );
+            sqlx::query!(
+                r#"UPDATE main.scheduled_jobs SET run_at = $1, locked_until = NULL, last_error = $2
+                WHERE job_id = $3"#,
+                OffsetDateTime::now_utc() + RETRY_DELAY * job.attempts,
+                e,
+                job.job_id,
+            )
+            .execute(client)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+//runs due jobs forever, started once from main
+pub(crate) async fn run_scheduler(state: AppState) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let jobs = match claim_due_jobs(&state.db_pool).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                println!("Error getting scheduled jobs: {}", e);
+                continue;
+            }
+        };
+        for job in jobs {
+            let result = run_job(&state, &job).await;
+            finish_job(&state.db_pool, &job, result)
+                .await
+                .unwrap_or_else(|e| println!("Error updating scheduled job: {}", e));
+        }
+    }
+}
@@ -1,20 +1,48 @@
 extern crate core;
 mod auth;
+mod availability;
 mod booking;
+mod captcha;
+mod catalogue;
+mod client_ip;
 mod clientele;
+mod email;
 mod invoicing;
+mod jobs;
 mod photo_file_ops;
+mod pricing;
+mod rate_limit;
+mod signed_link;
+mod sms;
+mod sun;
+mod validation;
 
 use crate::auth::auth_gaurd;
+use crate::booking::reminders::ReminderConfig;
+use crate::booking::schedule::SelfServiceConfig;
+use crate::captcha::Captcha;
+use crate::client_ip::TrustedProxies;
+use crate::email::{EmailConfig, Mailer};
 use crate::invoicing::invoice::{create_invoice, edit_invoice, find_invoice, view_invoice};
 
+use crate::invoicing::checkout::{CheckoutConfig, CheckoutProvider};
 use crate::invoicing::invoice_generation::generate_pdf;
+use crate::invoicing::overdue::OverdueConfig;
+use crate::invoicing::{
+    checkout, discount, installments, invoice, overdue, payments, quote, revenue, tax,
+};
+use crate::rate_limit::RateLimiters;
+use crate::signed_link::LinkSigner;
+use crate::sms::{SmsConfig, SmsSender};
+use crate::sun::StudioLocation;
+use axum::extract::DefaultBodyLimit;
 use axum::http::{Method, StatusCode, header};
-use axum::{Router, middleware, routing::get, routing::post};
+use axum::{Router, middleware, routing::delete, routing::get, routing::post};
 use dotenvy::dotenv;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
 use std::env;
+use std::net::SocketAddr;
 use std::path::Path;
 use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
@@ -25,6 +53,56 @@ use tower_sessions_sqlx_store::PostgresStore;
 #[derive(Clone)]
 struct AppState {
     db_pool: Pool<Postgres>,
+    mailer: Mailer,
+    sms: SmsSender,
+    captcha: Captcha,
+    rate_limiters: RateLimiters,
+    trusted_proxies: TrustedProxies,
+    link_signer: LinkSigner,
+    self_service: SelfServiceConfig,
+    reminders: ReminderConfig,
+    studio: Option<StudioLocation>,
+    checkout: CheckoutProvider,
+    overdue: OverdueConfig,
+}
+
+//state for handler tests: the pool never connects unless a query runs, email is disabled
+#[cfg(test)]
+impl AppState {
+    pub(crate) fn for_tests(captcha: Captcha) -> Self {
+        AppState {
+            db_pool: PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/photography_test")
+                .unwrap(),
+            mailer: Mailer::new(EmailConfig {
+                host: None,
+                port: 1025,
+                security: email::SmtpSecurity::None,
+                username: None,
+                password: None,
+                from: "studio@example.com".into(),
+                admin: None,
+            })
+            .unwrap(),
+            sms: SmsSender::new(None),
+            captcha,
+            rate_limiters: RateLimiters::from_env(),
+            trusted_proxies: TrustedProxies::default(),
+            link_signer: LinkSigner::new(b"0123456789abcdef0123456789abcdef"),
+            self_service: SelfServiceConfig::from_env(),
+            reminders: ReminderConfig {
+                offsets: Vec::new(),
+            },
+            studio: None,
+            checkout: CheckoutProvider::new(None),
+            overdue: OverdueConfig {
+                reminder_after: Vec::new(),
+                late_fee: None,
+                late_fee_after: time::Duration::days(14),
+                sweep_every: time::Duration::hours(1),
+            },
+        }
+    }
 }
 
 #[tokio::main]
@@ -40,6 +118,50 @@ async fn main() {
         .await
         .unwrap();
 
+    //OUTGOING EMAIL (SMTP relay)
+    let mailer = Mailer::new(EmailConfig::from_env()).expect("Invalid email configuration");
+    //OUTGOING SMS (optional, Twilio compatible)
+    let sms = SmsSender::new(SmsConfig::from_env());
+    //ONLINE PAYMENTS (optional, Stripe compatible)
+    let checkout = CheckoutProvider::new(CheckoutConfig::from_env());
+
+    //ABUSE PROTECTION FOR PUBLIC ENDPOINTS
+    let captcha = Captcha::from_env();
+    let rate_limiters = RateLimiters::from_env();
+    let trusted_proxies = TrustedProxies::from_env();
+    //forget rate limit entries that have expired every 10 minutes
+    let expired_limiters = rate_limiters.clone();
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(600));
+        loop {
+            interval.tick().await;
+            expired_limiters.remove_expired();
+        }
+    });
+
+    //SIGNED LINKS CLIENTS USE TO MANAGE THEIR BOOKING
+    let link_signer = LinkSigner::from_env();
+    let self_service = SelfServiceConfig::from_env();
+    let reminders = ReminderConfig::from_env();
+    //OVERDUE INVOICE REMINDERS AND LATE FEES
+    let overdue = OverdueConfig::from_env();
+    //STUDIO LOCATION, for golden hour suggestions on open slots
+    let studio = StudioLocation::from_env();
+
+    //DELETE IMAGES UPLOADED WITH BOOKING REQUESTS THAT WERE NEVER SUBMITTED, every hour
+    let attachment_pool = postgres_pool.clone();
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match booking::attachments::remove_abandoned_attachments(&attachment_pool).await {
+                Ok(0) => {}
+                Ok(removed) => println!("Removed {} abandoned booking attachments", removed),
+                Err(e) => println!("Error removing abandoned booking attachments: {}", e),
+            }
+        }
+    });
+
     //STATIC FILE SERVING PATHS
     let images_path = Path::new("./server_files/hdr_images");
     let serve_images = ServeDir::new(images_path);
@@ -76,6 +198,12 @@ async fn main() {
         .await
         .expect("Failed to run migrations");
 
+    //APPLY DATABASE MIGRATIONS in ./migrations
+    sqlx::migrate!()
+        .run(&postgres_pool)
+        .await
+        .expect("Failed to run database migrations");
+
     //(should)CONTINUOUSLY DELETE EXPIRED SESSIONS every 6 hours
     tokio::task::spawn(
         session_store
@@ -86,8 +214,25 @@ async fn main() {
     //Axum Server
     let state = AppState {
         db_pool: postgres_pool,
+        mailer,
+        sms,
+        captcha,
+        rate_limiters,
+        trusted_proxies,
+        link_signer,
+        self_service,
+        reminders,
+        studio,
+        checkout,
+        overdue,
     };
 
+    //BACKGROUND JOBS (session reminders, overdue invoices), stored in the database so they survive restarts
+    overdue::start_overdue_sweeps(&state.db_pool)
+        .await
+        .unwrap_or_else(|e| println!("Error scheduling overdue invoice sweeps: {}", e));
+    tokio::task::spawn(jobs::run_scheduler(state.clone()));
+
     // 4. Create the session Layer
     let session_layer = SessionManagerLayer::new(session_store)
         .with_secure(is_prod) // <--- CRITICAL: Must be TRUE if using HTTPS
@@ -105,14 +250,125 @@ async fn main() {
         .route("/invoicing/find", get(find_invoice))
         .route("/invoicing/create", post(create_invoice))
         .route("/invoicing/print/{invoice_id}", get(generate_pdf))
+        .route(
+            "/invoicing/issue/{invoice_id}",
+            post(invoice::issue_invoice),
+        )
+        .route("/invoicing/void/{invoice_id}", post(invoice::void_invoice))
+        .route("/invoicing/{invoice_id}", delete(invoice::delete_invoice))
+        .route("/invoicing/revenue", get(revenue::revenue_report))
+        .route(
+            "/invoicing/tax_rates",
+            get(tax::get_tax_rates)
+                .post(tax::save_tax_rate)
+                .delete(tax::delete_tax_rate),
+        )
+        .route(
+            "/invoicing/coupons",
+            get(discount::get_coupons).post(discount::save_coupon),
+        )
+        .route("/invoicing/coupons/{code}", delete(discount::delete_coupon))
+        .route(
+            "/invoicing/payments/{invoice_id}",
+            post(payments::record_payment),
+        )
+        .route(
+            "/invoicing/checkout/{invoice_id}",
+            post(checkout::create_checkout_link),
+        )
+        .route(
+            "/invoicing/payments/{invoice_id}/{payment_id}",
+            delete(payments::delete_payment),
+        )
+        .route("/invoicing/overdue", get(overdue::get_overdue_invoices))
+        .route(
+            "/invoicing/reminders/{invoice_id}",
+            post(overdue::pause_reminders),
+        )
+        .route(
+            "/invoicing/installments/overdue",
+            get(installments::get_overdue_installments),
+        )
+        .route(
+            "/invoicing/installments/{invoice_id}",
+            get(installments::get_installments).post(installments::set_installments),
+        )
+        .route("/catalogue/all", get(catalogue::get_full_catalogue))
+        .route("/catalogue/create", post(catalogue::create_catalogue_item))
+        .route(
+            "/catalogue/edit/{service_id}",
+            post(catalogue::edit_catalogue_item),
+        )
+        .route(
+            "/catalogue/{service_id}",
+            delete(catalogue::delete_catalogue_item),
+        )
+        .route("/pricing/rules", get(pricing::get_pricing_rules))
+        .route(
+            "/pricing/rules/{category}",
+            post(pricing::save_pricing_rule),
+        )
+        .route("/quotes/create", post(quote::create_quote))
+        .route("/quotes/view/{quote_id}", get(quote::view_quote))
+        .route("/quotes/edit/{quote_id}", post(quote::edit_quote))
+        .route("/quotes/send/{quote_id}", post(quote::send_quote))
+        .route("/quotes/print/{quote_id}", get(quote::print_quote))
         //BOOKING ROUTES
         .route("/booking/get_pending", get(booking::get_pending_bookings))
         .route("/booking/view/{booking_id}", get(booking::view_booking))
         .route("/booking/find", get(booking::find_booking))
+        .route("/booking/admin_create", post(booking::admin_create_booking))
         .route(
             "/booking/change_completion/{booking_id}",
             post(booking::change_completion_status),
         )
+        .route(
+            "/booking/edit/{booking_id}",
+            post(booking::edit::edit_booking),
+        )
+        .route(
+            "/booking/cancel/{booking_id}",
+            post(booking::edit::change_cancellation_status),
+        )
+        .route(
+            "/booking/notes/{booking_id}",
+            post(booking::timeline::add_booking_note),
+        )
+        .route(
+            "/booking/timeline/{booking_id}",
+            get(booking::timeline::view_booking_timeline),
+        )
+        .route(
+            "/booking/questionnaires/{category}",
+            post(booking::questionnaire::save_questionnaire)
+                .delete(booking::questionnaire::delete_questionnaire),
+        )
+        .route(
+            "/booking/attachments/{booking_id}",
+            get(booking::attachments::list_booking_attachments),
+        )
+        .route(
+            "/booking/attachment/{attachment_id}",
+            get(booking::attachments::get_attachment),
+        )
+        .route("/booking/merge", post(booking::duplicates::merge_bookings))
+        .route(
+            "/booking/dismiss_duplicate/{booking_id}",
+            post(booking::duplicates::dismiss_duplicate),
+        )
+        .route(
+            "/booking/schedule/{booking_id}",
+            post(booking::schedule::schedule_booking),
+        )
+        //AVAILABILITY ROUTES
+        .route(
+            "/availability/create",
+            post(availability::create_availability_slot),
+        )
+        .route(
+            "/availability/{slot_id}",
+            delete(availability::delete_availability_slot),
+        )
         //CLIENTELE ROUTES
         .route("/clientele/find", get(clientele::find_client))
         .route("/clientele/view/{client_id}", get(clientele::view_client))
@@ -130,6 +386,37 @@ async fn main() {
             get(photo_file_ops::get_category_photos),
         )
         .route("/booking/create", post(booking::create_booking_request))
+        .route(
+            "/booking/attachments",
+            post(booking::attachments::upload_attachment).layer(DefaultBodyLimit::max(
+                //room for the multipart boundaries and headers around the image
+                booking::attachments::MAX_ATTACHMENT_BYTES + 64 * 1024,
+            )),
+        )
+        .route(
+            "/booking/questionnaires",
+            get(booking::questionnaire::get_questionnaires),
+        )
+        .route("/availability", get(availability::get_availability))
+        .route("/catalogue", get(catalogue::get_catalogue))
+        .route("/pricing/estimate", get(pricing::get_estimate))
+        .route("/payments/webhook", post(checkout::payment_webhook))
+        .route("/quote/{token}", get(quote::view_quote_by_token))
+        .route("/quote/{token}/pdf", get(quote::print_quote_by_token))
+        .route("/quote/{token}/accept", post(quote::accept_quote))
+        .route("/quote/{token}/decline", post(quote::decline_quote))
+        .route(
+            "/self_service/booking/{token}",
+            get(booking::schedule::view_self_service_booking),
+        )
+        .route(
+            "/self_service/booking/{token}/reschedule",
+            post(booking::schedule::self_service_reschedule),
+        )
+        .route(
+            "/self_service/booking/{token}/cancel",
+            post(booking::schedule::self_service_cancel),
+        )
         .layer(session_layer)
         .layer(cors)
         .nest_service("/photo", serve_images) // Static file route
@@ -138,5 +425,11 @@ async fn main() {
     // run server with hyper, listening globally on port xxxx
     let listener = tokio::net::TcpListener::bind("0.0.0.0:4272").await.unwrap();
     println!("Listening on http://{}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    //connect info is needed to find the client IP for rate limiting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
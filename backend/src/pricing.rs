@@ -0,0 +1,290 @@
+use crate::AppState;
+use crate::booking::BOOKING_CATEGORIES;
+use crate::invoicing::invoice::ApiResponse;
+use crate::validation::ValidationErrors;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+//limits on what the calculator will price, bigger jobs get a custom quote
+const MAX_HOURS: i32 = 24;
+const MAX_PHOTOS: i32 = 1000;
+
+//price ranges for a booking category. these are kept apart from the catalogue on purpose, the
+//catalogue holds the exact rates of each package that end up on quotes and invoices, while a rule
+//is the rough range advertised for a whole category before a package is picked
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct PricingRule {
+    pub(crate) category: String,
+    pub(crate) hourly_min: Decimal,
+    pub(crate) hourly_max: Decimal,
+    //edited photos each hour of shooting comes with
+    pub(crate) photos_per_hour: i32,
+    pub(crate) per_photo_min: Decimal,
+    pub(crate) per_photo_max: Decimal,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewPricingRule {
+    hourly_min: Decimal,
+    hourly_max: Decimal,
+    photos_per_hour: i32,
+    per_photo_min: Decimal,
+    per_photo_max: Decimal,
+}
+
+//what the prospective client picked in the calculator
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EstimateRequest {
+    category: String,
+    #[serde(default)]
+    hours: i32,
+    //final edited photos they want
+    #[serde(default)]
+    photos: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct Estimate {
+    category: String,
+    hours: i32,
+    photos: i32,
+    //photos covered by the hours
+    included_photos: i32,
+    //photos charged the per photo fee
+    extra_photos: i32,
+    low: Decimal,
+    high: Decimal,
+}
+
+//hours at the hourly range, and any photos past what the hours include at the per photo range
+pub(crate) fn calculate(rule: &PricingRule, hours: i32, photos: i32) -> Estimate {
+    let included_photos = photos.min(hours.saturating_mul(rule.photos_per_hour));
+    let extra_photos = photos - included_photos;
+    let hours_decimal = Decimal::from(hours);
+    let extra_decimal = Decimal::from(extra_photos);
+    Estimate {
+        category: rule.category.clone(),
+        hours,
+        photos,
+        included_photos,
+        extra_photos,
+        low: hours_decimal * rule.hourly_min + extra_decimal * rule.per_photo_min,
+        high: hours_decimal * rule.hourly_max + extra_decimal * rule.per_photo_max,
+    }
+}
+
+fn validate_request(request: &EstimateRequest) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+    if !BOOKING_CATEGORIES
+        .iter()
+        .any(|(value, _)| *value == request.category)
+    {
+        errors.add("category", "Unknown category");
+    }
+    if !(0..=MAX_HOURS).contains(&request.hours) {
+        errors.add(
+            "hours",
+            &format!("Hours must be between 0 and {}", MAX_HOURS),
+        );
+    }
+    if !(0..=MAX_PHOTOS).contains(&request.photos) {
+        errors.add(
+            "photos",
+            &format!("Photos must be between 0 and {}", MAX_PHOTOS),
+        );
+    }
+    if request.hours == 0 && request.photos == 0 {
+        errors.add("hours", "Pick a number of hours or edited photos");
+    }
+    errors.finish(())
+}
+
+//validates a calculator request and prices it with the category's current rule
+pub(crate) async fn estimate(
+    client: &sqlx::PgPool,
+    request: &EstimateRequest,
+) -> Result<Estimate, Response> {
+    validate_request(request).map_err(|errors| errors.into_response())?;
+    let rule = sqlx::query_as!(
+        PricingRule,
+        r#"SELECT category, hourly_min, hourly_max, photos_per_hour, per_photo_min, per_photo_max
+        FROM main.pricing_rules WHERE category = $1"#,
+        request.category
+    )
+    .fetch_optional(client)
+    .await
+    .map_err(|e| {
+        println!("Error getting pricing rule: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: "Error calculating estimate".to_string(),
+            }),
+        )
+            .into_response()
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse {
+                message: "There's no pricing for this category yet, please contact us".to_string(),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok(calculate(&rule, request.hours, request.photos))
+}
+
+//saves the estimate a booking request was submitted with
+pub(crate) async fn attach_estimate(
+    client: &sqlx::PgPool,
+    booking_id: &str,
+    estimate: &Estimate,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE main.booking_requests SET estimate = $1 WHERE booking_id = $2",
+        serde_json::to_value(estimate).expect("estimate serializes"),
+        booking_id,
+    )
+    .execute(client)
+    .await?;
+    Ok(())
+}
+
+//public price calculator
+pub async fn get_estimate(
+    State(state): State<AppState>,
+    Query(request): Query<EstimateRequest>,
+) -> Result<Json<Estimate>, Response> {
+    Ok(Json(estimate(&state.db_pool, &request).await?))
+}
+
+pub async fn get_pricing_rules(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PricingRule>>, StatusCode> {
+    sqlx::query_as!(
+        PricingRule,
+        r#"SELECT category, hourly_min, hourly_max, photos_per_hour, per_photo_min, per_photo_max
+        FROM main.pricing_rules ORDER BY category"#
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map(Json)
+    .map_err(|e| {
+        println!("Error getting pricing rules: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn save_pricing_rule(
+    State(state): State<AppState>,
+    Path(category): Path<String>,
+    Json(payload): Json<NewPricingRule>,
+) -> Result<(StatusCode, Json<ApiResponse>), Response> {
+    let mut errors = ValidationErrors::new();
+    if !BOOKING_CATEGORIES
+        .iter()
+        .any(|(value, _)| *value == category)
+    {
+        errors.add("category", "Unknown category");
+    }
+    if payload.hourly_min.is_sign_negative() || payload.hourly_min > payload.hourly_max {
+        errors.add(
+            "hourly_min",
+            "Hourly range must be positive and low to high",
+        );
+    }
+    if payload.per_photo_min.is_sign_negative() || payload.per_photo_min > payload.per_photo_max {
+        errors.add(
+            "per_photo_min",
+            "Per photo range must be positive and low to high",
+        );
+    }
+    if !(0..=MAX_PHOTOS).contains(&payload.photos_per_hour) {
+        errors.add(
+            "photos_per_hour",
+            &format!("Photos per hour must be between 0 and {}", MAX_PHOTOS),
+        );
+    }
+    errors.finish(()).map_err(|errors| errors.into_response())?;
+    sqlx::query!(
+        r#"INSERT INTO main.pricing_rules (category, hourly_min, hourly_max, photos_per_hour,
+            per_photo_min, per_photo_max, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (category) DO UPDATE SET hourly_min = $2, hourly_max = $3, photos_per_hour = $4,
+            per_photo_min = $5, per_photo_max = $6, updated_at = $7"#,
+        category,
+        payload.hourly_min,
+        payload.hourly_max,
+        payload.photos_per_hour,
+        payload.per_photo_min,
+        payload.per_photo_max,
+        OffsetDateTime::now_utc(),
+    )
+    .execute(&state.db_pool)
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ApiResponse {
+                message: format!("Error saving pricing rule: {}", e),
+            }),
+        )
+            .into_response()
+    })?;
+    Ok((
+        StatusCode::OK,
+        Json(ApiResponse {
+            message: "Pricing rule saved".to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_hours_and_extra_photos() {
+        let rule = PricingRule {
+            category: "portraiture".to_string(),
+            hourly_min: Decimal::from(25),
+            hourly_max: Decimal::from(60),
+            photos_per_hour: 4,
+            per_photo_min: Decimal::from(10),
+            per_photo_max: Decimal::from(30),
+        };
+        //2 hours include 8 photos, the other 2 are charged per photo
+        let estimate = calculate(&rule, 2, 10);
+        assert_eq!(estimate.included_photos, 8);
+        assert_eq!(estimate.extra_photos, 2);
+        assert_eq!(estimate.low, Decimal::from(70));
+        assert_eq!(estimate.high, Decimal::from(180));
+
+        //photos only
+        let estimate = calculate(&rule, 0, 5);
+        assert_eq!(estimate.extra_photos, 5);
+        assert_eq!(estimate.low, Decimal::from(50));
+        assert_eq!(estimate.high, Decimal::from(150));
+    }
+
+    #[test]
+    fn huge_photos_per_hour_includes_every_photo() {
+        let rule = PricingRule {
+            category: "event".to_string(),
+            hourly_min: Decimal::from(25),
+            hourly_max: Decimal::from(60),
+            photos_per_hour: i32::MAX,
+            per_photo_min: Decimal::from(10),
+            per_photo_max: Decimal::from(30),
+        };
+        let estimate = calculate(&rule, MAX_HOURS, MAX_PHOTOS);
+        assert_eq!(estimate.included_photos, MAX_PHOTOS);
+        assert_eq!(estimate.extra_photos, 0);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::invoicing::invoice::ApiResponse;
+use axum::Json;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+//at most max_requests within any window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RateLimit {
+    pub(crate) max_requests: usize,
+    pub(crate) window: Duration,
+}
+
+impl RateLimit {
+    //reads "<max requests>/<window in seconds>", e.g. RATE_LIMIT_LOGIN_PER_IP=10/900
+    fn from_env(name: &str, default: RateLimit) -> Self {
+        let Ok(value) = env::var(name) else {
+            return default;
+        };
+        let parsed = value.split_once('/').and_then(|(max, secs)| {
+            Some(RateLimit {
+                max_requests: max.trim().parse().ok()?,
+                window: Duration::from_secs(secs.trim().parse().ok()?),
+            })
+        });
+        parsed.unwrap_or_else(|| panic!("{} must look like <max requests>/<seconds>", name))
+    }
+}
+
+//returned when a key has used up its requests, becomes a 429 with a Retry-After header
+#[derive(Debug)]
+pub(crate) struct RateLimited {
+    pub(crate) retry_after: Duration,
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        //round up so clients never retry a moment too early
+        let seconds = self.retry_after.as_secs() + u64::from(self.retry_after.subsec_nanos() > 0);
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ApiResponse {
+                message: format!("Too many requests, try again in {} seconds", seconds),
+            }),
+        )
+            .into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        response
+    }
+}
+
+//sliding window limiter kept in memory, keyed by IP address, email, etc.
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    hits: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            hits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    //records a request for key, or says how long to wait if the limit is already reached
+    pub(crate) fn check(&self, key: &str) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        let key_hits = hits.entry(key.to_string()).or_default();
+        self.wait(key_hits, now)?;
+        key_hits.push_back(now);
+        Ok(())
+    }
+
+    //like check but doesn't count this request, for limiters that only count some outcomes (see record)
+    pub(crate) fn peek(&self, key: &str) -> Result<(), RateLimited> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        match hits.get_mut(key) {
+            Some(key_hits) => self.wait(key_hits, now),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn record(&self, key: &str) {
+        self.hits
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push_back(Instant::now());
+    }
+
+    //forgets everything counted for key
+    pub(crate) fn reset(&self, key: &str) {
+        self.hits.lock().unwrap().remove(key);
+    }
+
+    //drops hits that have left the window, Err if the ones left use up the limit
+    fn wait(&self, key_hits: &mut VecDeque<Instant>, now: Instant) -> Result<(), RateLimited> {
+        while key_hits
+            .front()
+            .is_some_and(|hit| now.duration_since(*hit) >= self.limit.window)
+        {
+            key_hits.pop_front();
+        }
+        if key_hits.len() >= self.limit.max_requests {
+            let oldest = key_hits.front().copied().unwrap_or(now);
+            return Err(RateLimited {
+                retry_after: self.limit.window.saturating_sub(now.duration_since(oldest)),
+            });
+        }
+        Ok(())
+    }
+
+    //forget keys that haven't been seen for a whole window
+    pub(crate) fn remove_expired(&self) {
+        let now = Instant::now();
+        self.hits.lock().unwrap().retain(|_, key_hits| {
+            key_hits
+                .back()
+                .is_some_and(|hit| now.duration_since(*hit) < self.limit.window)
+        });
+    }
+}
+
+//every limiter used by the public endpoints
+#[derive(Clone)]
+pub(crate) struct RateLimiters {
+    pub(crate) booking_per_ip: RateLimiter,
+    pub(crate) booking_per_email: RateLimiter,
+    pub(crate) attachment_per_ip: RateLimiter,
+    pub(crate) login_per_ip: RateLimiter,
+    //failed logins for one account from one address, keyed by login_key. successful logins
+    //aren't counted so nobody else can lock the admin out
+    pub(crate) login_failures: RateLimiter,
+}
+
+impl RateLimiters {
+    pub(crate) fn from_env() -> Self {
+        let limiter = |name: &str, max_requests: usize, window_secs: u64| {
+            RateLimiter::new(RateLimit::from_env(
+                name,
+                RateLimit {
+                    max_requests,
+                    window: Duration::from_secs(window_secs),
+                },
+            ))
+        };
+        RateLimiters {
+            booking_per_ip: limiter("RATE_LIMIT_BOOKING_PER_IP", 5, 60 * 60),
+            booking_per_email: limiter("RATE_LIMIT_BOOKING_PER_EMAIL", 3, 24 * 60 * 60),
+            attachment_per_ip: limiter("RATE_LIMIT_ATTACHMENT_PER_IP", 20, 60 * 60),
+            login_per_ip: limiter("RATE_LIMIT_LOGIN_PER_IP", 10, 15 * 60),
+            login_failures: limiter("RATE_LIMIT_LOGIN_FAILURES", 5, 15 * 60),
+        }
+    }
+
+    pub(crate) fn remove_expired(&self) {
+        self.booking_per_ip.remove_expired();
+        self.booking_per_email.remove_expired();
+        self.attachment_per_ip.remove_expired();
+        self.login_per_ip.remove_expired();
+        self.login_failures.remove_expired();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_key_separately() {
+        let limiter = RateLimiter::new(RateLimit {
+            max_requests: 2,
+            window: Duration::from_secs(60),
+        });
+        assert!(limiter.check("1.2.3.4").is_ok());
+        assert!(limiter.check("1.2.3.4").is_ok());
+        let limited = limiter.check("1.2.3.4").unwrap_err();
+        assert!(limited.retry_after > Duration::from_secs(59));
+        assert!(limiter.check("5.6.7.8").is_ok());
+    }
+
+    #[test]
+    fn peek_and_record_count_only_what_is_recorded() {
+        let limiter = RateLimiter::new(RateLimit {
+            max_requests: 2,
+            window: Duration::from_secs(60),
+        });
+        assert!(limiter.peek("key").is_ok());
+        assert!(limiter.peek("key").is_ok());
+        limiter.record("key");
+        limiter.record("key");
+        assert!(limiter.peek("key").is_err());
+        limiter.reset("key");
+        assert!(limiter.peek("key").is_ok());
+    }
+
+    #[test]
+    fn rate_limited_response_has_retry_after() {
+        let response = RateLimited {
+            retry_after: Duration::from_millis(1500),
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+}
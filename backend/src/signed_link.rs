@@ -0,0 +1,107 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use time::OffsetDateTime;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum LinkError {
+    Malformed,
+    BadSignature,
+    Expired,
+}
+
+//signs tokens for links sent to clients so they can act on a record without logging in.
+//tokens look like "<id>.<expires unix timestamp>.<signature>"
+#[derive(Clone)]
+pub(crate) struct LinkSigner {
+    secret: Vec<u8>,
+}
+
+impl LinkSigner {
+    pub(crate) fn new(secret: &[u8]) -> Self {
+        LinkSigner {
+            secret: secret.to_vec(),
+        }
+    }
+
+    pub(crate) fn from_env() -> Self {
+        let secret = env::var("LINK_SIGNING_SECRET").expect("LINK_SIGNING_SECRET not found");
+        if secret.len() < 32 {
+            panic!("LINK_SIGNING_SECRET must be at least 32 characters");
+        }
+        LinkSigner::new(secret.as_bytes())
+    }
+
+    //purpose keeps a token for one kind of link from being used on another
+    fn mac(&self, purpose: &str, id: &str, expires: i64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}:{}:{}", purpose, id, expires).as_bytes());
+        mac
+    }
+
+    pub(crate) fn sign(&self, purpose: &str, id: &str, expires_at: OffsetDateTime) -> String {
+        let expires = expires_at.unix_timestamp();
+        let signature = self.mac(purpose, id, expires).finalize().into_bytes();
+        format!("{}.{}.{}", id, expires, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    //returns the id the token was signed for
+    pub(crate) fn verify(&self, purpose: &str, token: &str) -> Result<String, LinkError> {
+        let mut parts = token.split('.');
+        let (Some(id), Some(expires), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(LinkError::Malformed);
+        };
+        let expires: i64 = expires.parse().map_err(|_| LinkError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| LinkError::Malformed)?;
+        self.mac(purpose, id, expires)
+            .verify_slice(&signature)
+            .map_err(|_| LinkError::BadSignature)?;
+        if OffsetDateTime::now_utc().unix_timestamp() > expires {
+            return Err(LinkError::Expired);
+        }
+        Ok(id.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn round_trips_and_rejects_tampering() {
+        let signer = LinkSigner::new(b"0123456789abcdef0123456789abcdef");
+        let expires = OffsetDateTime::now_utc() + Duration::days(1);
+        let token = signer.sign("booking", "aB3dE9", expires);
+        assert_eq!(signer.verify("booking", &token).unwrap(), "aB3dE9");
+
+        //same token can't be used for a different kind of link
+        assert_eq!(signer.verify("quote", &token), Err(LinkError::BadSignature));
+        let tampered = token.replacen("aB3dE9", "zzzzzz", 1);
+        assert_eq!(
+            signer.verify("booking", &tampered),
+            Err(LinkError::BadSignature)
+        );
+        assert_eq!(signer.verify("booking", "nope"), Err(LinkError::Malformed));
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let signer = LinkSigner::new(b"0123456789abcdef0123456789abcdef");
+        let token = signer.sign(
+            "booking",
+            "aB3dE9",
+            OffsetDateTime::now_utc() - Duration::minutes(1),
+        );
+        assert_eq!(signer.verify("booking", &token), Err(LinkError::Expired));
+    }
+}
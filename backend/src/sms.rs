@@ -0,0 +1,92 @@
+use reqwest::StatusCode;
+use std::env;
+use std::fmt;
+
+//Twilio (or any API that speaks its Messages format) credentials, loaded once at startup
+pub(crate) struct SmsConfig {
+    pub(crate) account_sid: String,
+    pub(crate) auth_token: String,
+    pub(crate) from_number: String,
+    pub(crate) api_url: String,
+}
+
+impl SmsConfig {
+    //SMS is optional, None unless TWILIO_ACCOUNT_SID, TWILIO_AUTH_TOKEN and SMS_FROM_NUMBER are all set
+    pub(crate) fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        Some(SmsConfig {
+            account_sid: var("TWILIO_ACCOUNT_SID")?,
+            auth_token: var("TWILIO_AUTH_TOKEN")?,
+            from_number: var("SMS_FROM_NUMBER")?,
+            api_url: var("TWILIO_API_URL")
+                .unwrap_or_else(|| "https://api.twilio.com/2010-04-01".into()),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum SmsError {
+    Request(reqwest::Error),
+    Rejected(StatusCode, String),
+}
+
+impl fmt::Display for SmsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmsError::Request(e) => write!(f, "could not reach SMS provider: {}", e),
+            SmsError::Rejected(status, body) => {
+                write!(f, "SMS provider rejected message ({}): {}", status, body)
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for SmsError {
+    fn from(e: reqwest::Error) -> Self {
+        SmsError::Request(e)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SmsSender {
+    //None when SMS isn't configured
+    config: Option<std::sync::Arc<SmsConfig>>,
+    client: reqwest::Client,
+}
+
+impl SmsSender {
+    pub(crate) fn new(config: Option<SmsConfig>) -> Self {
+        SmsSender {
+            config: config.map(std::sync::Arc::new),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    //to is an E.164 number like the ones stored on bookings
+    pub(crate) async fn send(&self, to: &str, body: &str) -> Result<(), SmsError> {
+        let Some(config) = &self.config else {
+            println!("SMS disabled, not sending to {}", to);
+            return Ok(());
+        };
+        let response = self
+            .client
+            .post(format!(
+                "{}/Accounts/{}/Messages.json",
+                config.api_url, config.account_sid
+            ))
+            .basic_auth(&config.account_sid, Some(&config.auth_token))
+            .form(&[("To", to), ("From", &config.from_number), ("Body", body)])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(SmsError::Rejected(status, response.text().await?));
+        }
+        println!("SMS sent to {}", to);
+        Ok(())
+    }
+}
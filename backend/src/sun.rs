@@ -0,0 +1,237 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::env;
+use time::OffsetDateTime;
+
+//sun elevations (degrees) photographers plan around
+const SUNRISE_ELEVATION: f64 = -0.833;
+//golden hour runs from -4 to 6 degrees, blue hour from -6 to -4
+const GOLDEN_HOUR_HIGH: f64 = 6.0;
+const GOLDEN_HOUR_LOW: f64 = -4.0;
+const BLUE_HOUR_LOW: f64 = -6.0;
+
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH_JULIAN: f64 = 2440587.5;
+
+//where the studio is, used for availability suggestions when there's no booking location
+#[derive(Clone)]
+pub(crate) struct StudioLocation {
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+    pub(crate) timezone: Tz,
+}
+
+impl StudioLocation {
+    //None unless STUDIO_LATITUDE and STUDIO_LONGITUDE are set
+    pub(crate) fn from_env() -> Option<Self> {
+        let coordinate = |name: &str| {
+            env::var(name).ok().map(|value| {
+                value
+                    .parse::<f64>()
+                    .expect("studio coordinates must be numbers")
+            })
+        };
+        Some(StudioLocation {
+            latitude: coordinate("STUDIO_LATITUDE")?,
+            longitude: coordinate("STUDIO_LONGITUDE")?,
+            timezone: env::var("STUDIO_TIMEZONE")
+                .ok()
+                .map(|timezone| {
+                    timezone
+                        .parse()
+                        .expect("STUDIO_TIMEZONE is not a valid timezone")
+                })
+                .unwrap_or(chrono_tz::America::New_York),
+        })
+    }
+}
+
+//a stretch of the day with a certain kind of light
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct LightWindow {
+    pub(crate) starts_at: String,
+    pub(crate) ends_at: String,
+    #[serde(skip)]
+    starts_unix: i64,
+    #[serde(skip)]
+    ends_unix: i64,
+}
+
+impl LightWindow {
+    fn overlaps(&self, starts_at: OffsetDateTime, ends_at: OffsetDateTime) -> bool {
+        starts_at.unix_timestamp() < self.ends_unix && ends_at.unix_timestamp() > self.starts_unix
+    }
+}
+
+//light for one day at one place, times are in the place's timezone.
+//a field is None when the sun never gets there that day (polar day or night)
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SunTimes {
+    pub(crate) date: String,
+    pub(crate) timezone: String,
+    pub(crate) sunrise: Option<String>,
+    pub(crate) solar_noon: String,
+    pub(crate) sunset: Option<String>,
+    pub(crate) morning_blue_hour: Option<LightWindow>,
+    pub(crate) morning_golden_hour: Option<LightWindow>,
+    pub(crate) evening_golden_hour: Option<LightWindow>,
+    pub(crate) evening_blue_hour: Option<LightWindow>,
+}
+
+impl SunTimes {
+    //whether a session overlaps either golden hour
+    pub(crate) fn in_golden_hour(
+        &self,
+        starts_at: OffsetDateTime,
+        ends_at: OffsetDateTime,
+    ) -> bool {
+        [&self.morning_golden_hour, &self.evening_golden_hour]
+            .into_iter()
+            .flatten()
+            .any(|window| window.overlaps(starts_at, ends_at))
+    }
+}
+
+//solar transit and declination for a day, from the NOAA sunrise equation
+struct SolarDay {
+    transit: f64,
+    declination: f64,
+}
+
+fn solar_day(date: NaiveDate, longitude: f64) -> SolarDay {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date");
+    let julian_noon = UNIX_EPOCH_JULIAN + (date - epoch).num_days() as f64 + 0.5;
+    let day = (julian_noon - J2000 + 0.0008).round();
+    let mean_solar_noon = day - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_noon).rem_euclid(360.0);
+    let anomaly_rad = anomaly.to_radians();
+    let center = 1.9148 * anomaly_rad.sin()
+        + 0.02 * (2.0 * anomaly_rad).sin()
+        + 0.0003 * (3.0 * anomaly_rad).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    SolarDay {
+        transit: J2000 + mean_solar_noon + 0.0053 * anomaly_rad.sin()
+            - 0.0069 * (2.0 * ecliptic_longitude).sin(),
+        declination: (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin(),
+    }
+}
+
+//julian dates when the sun crosses an elevation in the morning and evening
+fn crossing(day: &SolarDay, latitude: f64, elevation: f64) -> Option<(f64, f64)> {
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = (elevation.to_radians().sin() - latitude.sin() * day.declination.sin())
+        / (latitude.cos() * day.declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let offset = cos_hour_angle.acos().to_degrees() / 360.0;
+    Some((day.transit - offset, day.transit + offset))
+}
+
+fn julian_to_unix(julian: f64) -> i64 {
+    ((julian - UNIX_EPOCH_JULIAN) * 86400.0).round() as i64
+}
+
+fn format_local(julian: f64, timezone: Tz) -> String {
+    DateTime::from_timestamp(julian_to_unix(julian), 0)
+        .expect("valid timestamp")
+        .with_timezone(&timezone)
+        .to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn window(starts: f64, ends: f64, timezone: Tz) -> LightWindow {
+    LightWindow {
+        starts_at: format_local(starts, timezone),
+        ends_at: format_local(ends, timezone),
+        starts_unix: julian_to_unix(starts),
+        ends_unix: julian_to_unix(ends),
+    }
+}
+
+//sunrise, sunset, golden and blue hour for a local date, accurate to about a minute
+pub(crate) fn sun_times(date: NaiveDate, latitude: f64, longitude: f64, timezone: Tz) -> SunTimes {
+    let day = solar_day(date, longitude);
+    let sunrise = crossing(&day, latitude, SUNRISE_ELEVATION);
+    let golden_high = crossing(&day, latitude, GOLDEN_HOUR_HIGH);
+    let golden_low = crossing(&day, latitude, GOLDEN_HOUR_LOW);
+    let blue_low = crossing(&day, latitude, BLUE_HOUR_LOW);
+    //near the poles the sun may never climb above 6 degrees, then golden hour lasts all day
+    let (golden_morning_end, golden_evening_start) =
+        golden_high.unwrap_or((day.transit, day.transit));
+    SunTimes {
+        date: date.to_string(),
+        timezone: timezone.name().to_string(),
+        sunrise: sunrise.map(|(rise, _)| format_local(rise, timezone)),
+        solar_noon: format_local(day.transit, timezone),
+        sunset: sunrise.map(|(_, set)| format_local(set, timezone)),
+        morning_blue_hour: blue_low
+            .zip(golden_low)
+            .map(|((start, _), (end, _))| window(start, end, timezone)),
+        morning_golden_hour: golden_low
+            .map(|(start, _)| window(start, golden_morning_end, timezone)),
+        evening_golden_hour: golden_low.map(|(_, end)| window(golden_evening_start, end, timezone)),
+        evening_blue_hour: blue_low
+            .zip(golden_low)
+            .map(|((_, end), (_, start))| window(start, end, timezone)),
+    }
+}
+
+//light for the local day a moment falls on
+pub(crate) fn sun_times_at(
+    at: OffsetDateTime,
+    latitude: f64,
+    longitude: f64,
+    timezone: Tz,
+) -> SunTimes {
+    let local = DateTime::from_timestamp(at.unix_timestamp(), 0)
+        .expect("valid timestamp")
+        .with_timezone(&timezone);
+    sun_times(local.date_naive(), latitude, longitude, timezone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn matches_published_times_for_new_york() {
+        //published almanac times for Central Park on 2026-04-15 are 6:17 AM and 7:35 PM EDT
+        let times = sun_times(
+            NaiveDate::from_ymd_opt(2026, 4, 15).unwrap(),
+            40.7829,
+            -73.9654,
+            chrono_tz::America::New_York,
+        );
+        let sunrise = times.sunrise.clone().unwrap();
+        let sunset = times.sunset.clone().unwrap();
+        assert!(sunrise.starts_with("2026-04-15T06:1"), "{}", sunrise);
+        assert!(sunset.starts_with("2026-04-15T19:3"), "{}", sunset);
+
+        let evening = times.evening_golden_hour.clone().unwrap();
+        assert!(evening.starts_at < sunset && sunset < evening.ends_at);
+        assert!(times.in_golden_hour(
+            datetime!(2026-04-15 23:00 UTC),
+            datetime!(2026-04-16 00:00 UTC)
+        ));
+        assert!(!times.in_golden_hour(
+            datetime!(2026-04-15 16:00 UTC),
+            datetime!(2026-04-15 17:00 UTC)
+        ));
+    }
+
+    #[test]
+    fn polar_night_has_no_sunrise() {
+        let times = sun_times(
+            NaiveDate::from_ymd_opt(2026, 12, 21).unwrap(),
+            78.22,
+            15.65,
+            chrono_tz::Arctic::Longyearbyen,
+        );
+        assert_eq!(times.sunrise, None);
+        assert_eq!(times.evening_golden_hour, None);
+    }
+}
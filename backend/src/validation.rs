@@ -0,0 +1,170 @@
+use axum::Json;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+//country calling code assumed for phone numbers entered without one (the booking form defaults to US)
+const DEFAULT_COUNTRY_CODE: &str = "1";
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FieldError {
+    pub(crate) field: String,
+    pub(crate) message: String,
+}
+
+//every problem found with a submission, returned as a 422 so the form can show errors per field
+#[derive(Debug, Serialize)]
+pub(crate) struct ValidationErrors {
+    message: String,
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub(crate) fn new() -> Self {
+        ValidationErrors {
+            message: "Invalid submission".to_string(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, field: &str, message: &str) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    //Ok(value) if nothing was added, otherwise the collected errors
+    pub(crate) fn finish<T>(self, value: T) -> Result<T, ValidationErrors> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+//structural email check, deliverability is left to the mail server
+pub(crate) fn is_valid_email(email: &str) -> bool {
+    if email.len() > 254 || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && local.len() <= 64
+        && !local.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+        && domain
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+}
+
+//converts a phone number typed in any common format into E.164 ("+15551234567")
+//returns None if it can't be a valid number
+pub(crate) fn normalize_phone(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    let (international, rest) = if let Some(rest) = trimmed.strip_prefix('+') {
+        (true, rest)
+    } else if let Some(rest) = trimmed.strip_prefix("00") {
+        (true, rest)
+    } else {
+        (false, trimmed)
+    };
+
+    let mut digits = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '-' | '.' | '(' | ')' => {}
+            _ => return None,
+        }
+    }
+
+    //national numbers get the default country code unless they already start with it
+    let has_country_code = international
+        || (DEFAULT_COUNTRY_CODE == "1" && digits.len() == 11 && digits.starts_with('1'));
+    let number = if has_country_code {
+        digits
+    } else {
+        format!("{}{}", DEFAULT_COUNTRY_CODE, digits)
+    };
+
+    //E.164 numbers are at most 15 digits and country codes never start with 0
+    if number.len() < 8 || number.len() > 15 || number.starts_with('0') {
+        return None;
+    }
+    //North American numbers are always 1 + 10 digits
+    if number.starts_with('1') && number.len() != 11 {
+        return None;
+    }
+    Some(format!("+{}", number))
+}
+
+//checks for an IANA timezone name like "America/New_York"
+pub(crate) fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.parse::<Tz>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_phone_numbers_to_e164() {
+        assert_eq!(
+            normalize_phone("(555) 123-4567").as_deref(),
+            Some("+15551234567")
+        );
+        assert_eq!(
+            normalize_phone("1-555-123-4567").as_deref(),
+            Some("+15551234567")
+        );
+        assert_eq!(
+            normalize_phone("+15551234567").as_deref(),
+            Some("+15551234567")
+        );
+        assert_eq!(
+            normalize_phone("+44 20 7946 0958").as_deref(),
+            Some("+442079460958")
+        );
+        assert_eq!(
+            normalize_phone("0044 20 7946 0958").as_deref(),
+            Some("+442079460958")
+        );
+        assert_eq!(normalize_phone("555-1234"), None);
+        assert_eq!(normalize_phone("call me"), None);
+        assert_eq!(normalize_phone("+1234567890123456"), None);
+    }
+
+    #[test]
+    fn checks_emails() {
+        assert!(is_valid_email("client@example.com"));
+        assert!(is_valid_email("first.last+tag@mail.example.co.uk"));
+        assert!(!is_valid_email("client@example"));
+        assert!(!is_valid_email("client example@example.com"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("client@@example.com"));
+    }
+
+    #[test]
+    fn checks_iana_timezones() {
+        assert!(is_valid_timezone("America/New_York"));
+        assert!(!is_valid_timezone("Eastern"));
+    }
+}
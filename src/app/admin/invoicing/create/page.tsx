@@ -59,6 +59,8 @@ export default function CreateInvoice() {
         .object({ value: z.string(), label: z.string() })
         .optional(),
       amount_tax: z.number().min(0, "Must be >= 0").optional(),
+      //false saves a draft that can be issued later from the invoice page
+      issue: z.boolean(),
     })
     .refine(
       (data) => {
@@ -107,7 +109,7 @@ export default function CreateInvoice() {
       client_id: "",
       invoice_items: [{ description: "", quantity: 0, unit_price: 0.0 }],
       notes: "",
-      amount_tax: 0.0,
+      issue: true,
       address_country: defaultCountry || undefined,
       address_state: defaultState || undefined,
     },
@@ -331,12 +333,17 @@ export default function CreateInvoice() {
         {errors.amount_tax && (
           <p className="text-accent">*{errors.amount_tax.message}</p>
         )}
-        <label htmlFor="invoice-tax">Tax (as decimal: 8% = 0.08)</label>
+        <label htmlFor="invoice-tax">
+          Tax amount (leave blank to use the rate for the address)
+        </label>
         <input
           type="number"
           step="0.01"
           className="border-2 w-20 outline-none focus:border-accent"
-          {...register("amount_tax", { valueAsNumber: true })}
+          {...register("amount_tax", {
+            //blank calculates the tax from the client's address
+            setValueAs: (v) => (v === "" || v === null ? undefined : Number(v)),
+          })}
         />
         <span className="h-10"></span>
 
@@ -468,6 +475,11 @@ export default function CreateInvoice() {
           {...register("notes")}
         />
         <span className="h-10"></span>
+        <label htmlFor="invoice-issue">
+          <input type="checkbox" id="invoice-issue" {...register("issue")} />{" "}
+          Issue now (uncheck to save as a draft)
+        </label>
+        <span className="h-10"></span>
         <button className="border-2" type="submit">
           Create Invoice
         </button>
@@ -19,6 +19,10 @@ export type InvoiceItem = {
   description: string;
   quantity: number;
   unit_price: number;
+  taxable: boolean;
+  discount_kind: "percent" | "fixed" | null;
+  discount_value: number | null;
+  late_fee: boolean;
 };
 export type Client = {
   first_name: string;
@@ -40,6 +44,7 @@ export type Invoice = {
   payment_method: number;
   notes: string;
   amount_tax: number;
+  tax_rate: number | null;
   amount_total: number;
   booking_id: string;
   invoice_id: string;
@@ -85,6 +90,16 @@ export default function EditInvoice() {
     description: z.string().min(1, "Description is required"),
     quantity: z.number().int().min(1, "Min quantity is 1."),
     unit_price: z.number().min(0, "Must be >= 0"),
+    //not editable here yet, sent back so saving doesn't drop them
+    taxable: z.boolean().optional(),
+    late_fee: z.boolean().optional(),
+    discount: z
+      .object({
+        kind: z.enum(["percent", "fixed"]),
+        value: z.union([z.number(), z.string()]),
+      })
+      .nullable()
+      .optional(),
   });
 
   const InvoiceFormSchema = z
@@ -119,8 +134,6 @@ export default function EditInvoice() {
       address_country: z
         .object({ value: z.string(), label: z.string() })
         .optional(),
-      paid_at: z.string().optional(),
-      payment_completed: z.boolean(),
       amount_tax: z.number().min(0, "Must be >= 0").optional(),
     })
     .refine(
@@ -141,18 +154,6 @@ export default function EditInvoice() {
         message: "You must provide all address fields or none at all",
         path: ["address_street"], // This points the error to the street field
       },
-    )
-    .refine(
-      (data) => {
-        if (data.payment_completed) {
-          return data.paid_at !== undefined && data.paid_at !== "";
-        }
-        return true;
-      },
-      {
-        message: "Payment date cannot be blank if payment is completed!",
-        path: ["paid_at"], // This points the error to the street field
-      },
     );
 
   const country_list = useMemo(() => countryList().getData(), []);
@@ -173,7 +174,17 @@ export default function EditInvoice() {
     resolver: zodResolver(InvoiceFormSchema),
     values: {
       client_id: invoice?.invoice?.client_id,
-      invoice_items: invoice?.invoice_items,
+      invoice_items: invoice?.invoice_items?.map((item: InvoiceItem) => ({
+        description: item.description,
+        quantity: item.quantity,
+        unit_price: item.unit_price,
+        taxable: item.taxable,
+        late_fee: item.late_fee,
+        discount:
+          item.discount_kind && item.discount_value != null
+            ? { kind: item.discount_kind, value: item.discount_value }
+            : null,
+      })),
       notes: invoice?.invoice?.notes ?? undefined,
       due_date: isoToDatetimeLocal(invoice?.invoice?.due_date) ?? undefined,
       address_street: invoice?.client?.address_street ?? undefined,
@@ -187,9 +198,11 @@ export default function EditInvoice() {
         country_list.find(
           (o) => o.value === invoice?.client?.address_country,
         ) ?? undefined,
-      paid_at: isoToDatetimeLocal(invoice?.invoice?.paid_at) ?? undefined,
-      payment_completed: invoice?.invoice?.payment_completed,
-      amount_tax: invoice?.invoice?.amount_tax,
+      //only a tax entered by hand is sent back, otherwise it's recalculated
+      amount_tax:
+        invoice?.invoice?.tax_rate == null && invoice?.invoice?.amount_tax > 0
+          ? invoice?.invoice?.amount_tax
+          : undefined,
     },
   });
   const { fields, append, remove } = useFieldArray({
@@ -200,20 +213,14 @@ export default function EditInvoice() {
   //RUN ON FORM SUBMIT
   const onSubmit = async (values: any) => {
     const local_due_date = values.due_date;
-    const local_paid_at = values.paid_at;
     // JS interprets this string using the browser's current timezone.
     const due_dateWithCurrentOffset = new Date(local_due_date).toISOString();
-    let paid_atWithCurrentOffset = "";
-    if (local_paid_at != "") {
-      paid_atWithCurrentOffset = new Date(local_paid_at).toISOString();
-    }
     //console.log(dateWithCurrentOffset.toISOString());
     // 3. Convert to a full ISO string
     // .toISOString() converts the time to UTC (Z) automatically.
     const finalized_data = {
       ...values,
       due_date: due_dateWithCurrentOffset,
-      paid_at: paid_atWithCurrentOffset,
     };
 
     //Remove empty strings from the json
@@ -398,12 +405,17 @@ export default function EditInvoice() {
         {errors.amount_tax && (
           <p className="text-accent">*{errors.amount_tax.message}</p>
         )}
-        <label htmlFor="invoice-tax">Tax (as decimal: 8% = 0.08)</label>
+        <label htmlFor="invoice-tax">
+          Tax amount (leave blank to use the rate for the address)
+        </label>
         <input
           type="number"
           step="0.01"
           className="border-2 w-20 outline-none focus:border-accent"
-          {...register("amount_tax", { valueAsNumber: true })}
+          {...register("amount_tax", {
+            //blank calculates the tax from the client's address
+            setValueAs: (v) => (v === "" || v === null ? undefined : Number(v)),
+          })}
         />
         <span className="h-10"></span>
         {/*ADDRESS*/}
@@ -539,22 +551,8 @@ export default function EditInvoice() {
           className="border-2 w-100 outline-none focus:border-accent"
           {...register("notes")}
         />
-        <span className="h-10" />
-        <p>
-          Payment Complete:{" "}
-          <input type="checkbox" {...register("payment_completed")} />
-        </p>
         <span className="h-4" />
-        {/*PAYMENT DATE*/}
-        {errors.paid_at && (
-          <p className="text-accent">*{errors.paid_at.message}</p>
-        )}
-        <label htmlFor="invoice-due_date">Payment Date</label>
-        <input
-          type="datetime-local"
-          className="border-2 "
-          {...register("paid_at")}
-        />
+        <p>Payments are recorded from the invoice page</p>
 
         <span className="h-10"></span>
         <div className="flex justify-center items-center gap-4">
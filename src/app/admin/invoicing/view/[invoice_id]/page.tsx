@@ -6,7 +6,7 @@ import { useParams } from "next/navigation";
 
 import { parsePhoneNumberWithError } from "libphonenumber-js";
 
-import React from "react";
+import React, { useState } from "react";
 import Link from "next/link";
 export type InvoiceItem = {
   invoice_id: string;
@@ -41,6 +41,8 @@ export type Invoice = {
   due_date: string;
   payment_completed: boolean;
   paid_at: string;
+  issued_at: string | null;
+  voided_at: string | null;
   invoice_number: number;
   invoiceItems: InvoiceItem[];
 };
@@ -60,6 +62,7 @@ export default function ViewInvoice() {
     isLoading,
     isError,
     isSuccess,
+    refetch,
   } = useQuery({
     queryKey: ["invoice"],
     queryFn: async () => {
@@ -112,6 +115,42 @@ export default function ViewInvoice() {
     console.log(response);
   }
 
+  //drafts aren't sent or payable until they're issued
+  async function issue_invoice(invoice_id: string) {
+    await fetch(API_URL + `/invoicing/issue/${invoice_id}`, {
+      method: "POST",
+      credentials: "include",
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => refetch())
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
+  const [payment_amount, setPaymentAmount] = useState("");
+  const [payment_method, setPaymentMethod] = useState("");
+  //records a payment taken outside of online checkout (cash, check, etc.)
+  async function record_payment(invoice_id: string) {
+    await fetch(API_URL + `/invoicing/payments/${invoice_id}`, {
+      headers: {
+        "Content-Type": "application/json",
+      },
+      method: "POST",
+      credentials: "include",
+      body: JSON.stringify({
+        amount: Number(payment_amount),
+        method: payment_method || undefined,
+      }),
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => {
+        setPaymentAmount("");
+        refetch();
+      })
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
   if (isLoading)
     return (
       <div className="flex justify-center mt-30 items-center">
@@ -132,11 +171,19 @@ export default function ViewInvoice() {
     <AuthGuard>
       <>
         <div className="flex items-start pl-[3vw] md:pl-[10vw] flex-col">
-          <h1>INVOICE #{invoice?.invoice.invoice_number}</h1>
+          <h1>
+            INVOICE #{invoice?.invoice.invoice_number}
+            {invoice?.invoice.voided_at
+              ? " (VOID)"
+              : !invoice?.invoice.issued_at && " (DRAFT)"}
+          </h1>
           <div className="flex gap-4">
-            <Link href={`/admin/invoicing/edit/${invoice_id}`} passHref>
-              <button className="border-2">EDIT</button>
-            </Link>
+            {/* only drafts can be edited, issued invoices are voided instead */}
+            {!invoice?.invoice.issued_at && !invoice?.invoice.voided_at && (
+              <Link href={`/admin/invoicing/edit/${invoice_id}`} passHref>
+                <button className="border-2">EDIT</button>
+              </Link>
+            )}
             <button
               className="border-2"
               onClick={() => {
@@ -146,6 +193,14 @@ export default function ViewInvoice() {
             >
               PRINT
             </button>
+            {!invoice?.invoice.issued_at && !invoice?.invoice.voided_at && (
+              <button
+                className="border-2"
+                onClick={() => issue_invoice(invoice_id)}
+              >
+                ISSUE
+              </button>
+            )}
           </div>
         </div>
 
@@ -209,7 +264,7 @@ export default function ViewInvoice() {
             </tbody>
           </table>
           <h2 className="">Subtotal: {invoice?.invoice.amount_subtotal}</h2>
-          <h2 className="">Tax: {invoice?.invoice.amount_tax * 100}%</h2>
+          <h2 className="">Tax: {invoice?.invoice.amount_tax}</h2>
           <span className="h-6" />
           <h2>Due Date: {due_date}</h2>
           <span className="h-6" />
@@ -222,6 +277,33 @@ export default function ViewInvoice() {
             )}
           </p>
           <h2>Payment Date: {payment_date}</h2>
+          <h2>Amount Paid: {invoice?.amount_paid}</h2>
+          <h2>Balance Due: {invoice?.balance_due}</h2>
+          {invoice?.invoice.issued_at && !invoice?.invoice.voided_at && (
+            <div className="flex gap-2 items-center">
+              <input
+                type="number"
+                step="0.01"
+                placeholder="Amount"
+                className="border-2 w-28 outline-none focus:border-accent"
+                value={payment_amount}
+                onChange={(e) => setPaymentAmount(e.target.value)}
+              />
+              <input
+                placeholder="Method (cash, check...)"
+                className="border-2 outline-none focus:border-accent"
+                value={payment_method}
+                onChange={(e) => setPaymentMethod(e.target.value)}
+              />
+              <button
+                className="border-2"
+                disabled={payment_amount === ""}
+                onClick={() => record_payment(invoice_id)}
+              >
+                RECORD PAYMENT
+              </button>
+            </div>
+          )}
           <h2>Invoice_ID: {invoice?.invoice.invoice_id}</h2>
         </div>
       </>
@@ -0,0 +1,210 @@
+"use client";
+import { API_URL } from "@/_utilities/API_UTILS";
+import { useQuery } from "@tanstack/react-query";
+import { useParams } from "next/navigation";
+
+import React, { useState } from "react";
+
+export type AvailabilitySlot = {
+  slot_id: number;
+  starts_at: string;
+  ends_at: string;
+};
+export type SelfServiceBooking = {
+  booking_number: number;
+  first_name: string;
+  categories: string[];
+  scheduled_at: string | null;
+  duration_minutes: number | null;
+  location: string | null;
+  timezone: string | null;
+  cancelled: boolean;
+  //whether reschedule and cancel are still allowed
+  can_change: boolean;
+  change_deadline: string | null;
+  available_slots: AvailabilitySlot[];
+};
+
+//the page a client reaches from the link in their schedule confirmation email
+export default function ManageBooking() {
+  const params = useParams<{ token: string }>();
+  const token = params.token;
+  const {
+    data: booking,
+    isLoading,
+    isError,
+    refetch,
+  } = useQuery<SelfServiceBooking>({
+    queryKey: ["self_service_booking", token],
+    queryFn: async () => {
+      const response = await fetch(API_URL + `/self_service/booking/${token}`);
+
+      if (!response.ok) throw new Error("Network response was not ok");
+
+      return response.json();
+    },
+  });
+
+  //times are shown in the client's own timezone when we know it
+  const local_time = (at: string) =>
+    new Date(at).toLocaleString("en-US", {
+      weekday: "long",
+      year: "numeric",
+      month: "short",
+      day: "2-digit",
+      hour: "2-digit",
+      minute: "2-digit",
+      timeZone: booking?.timezone ?? undefined,
+      timeZoneName: "short",
+    });
+
+  const [slot_id, setSlotId] = useState("");
+  const [cancelling, setCancelling] = useState(false);
+  const [cancel_reason, setCancelReason] = useState("");
+
+  async function reschedule() {
+    await fetch(API_URL + `/self_service/booking/${token}/reschedule`, {
+      headers: {
+        "Content-Type": "application/json",
+      },
+      method: "POST",
+      body: JSON.stringify({
+        slot_id: Number(slot_id),
+      }),
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => {
+        setSlotId("");
+        refetch();
+      })
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
+  async function cancel() {
+    await fetch(API_URL + `/self_service/booking/${token}/cancel`, {
+      headers: {
+        "Content-Type": "application/json",
+      },
+      method: "POST",
+      body: JSON.stringify({
+        reason: cancel_reason || undefined,
+      }),
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => {
+        setCancelling(false);
+        refetch();
+      })
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
+  if (isLoading)
+    return (
+      <div className="flex justify-center mt-30 items-center">
+        <div className="animate-pulse flex space-x-4">
+          <div className="h-4">Loading...</div>
+        </div>
+      </div>
+    );
+  if (isError || !booking)
+    return (
+      <div className="flex justify-center items-center mt-30">
+        This link is invalid or has expired
+      </div>
+    );
+  return (
+    <>
+      <div className="flex items-start pl-[3vw] md:pl-[10vw] flex-col">
+        <h1>BOOKING #{booking.booking_number}</h1>
+      </div>
+
+      <div className="flex justify-center mx-10 mb-80 p-6 border-2 items-center mt-30 flex-col ">
+        <h2>Hi {booking.first_name}!</h2>
+        <span className="h-6" />
+        {booking.cancelled ? (
+          <p>This session has been cancelled.</p>
+        ) : booking.scheduled_at ? (
+          <>
+            <p>Session: {local_time(booking.scheduled_at)}</p>
+            {booking.duration_minutes && (
+              <p>Length: {booking.duration_minutes} minutes</p>
+            )}
+            <p>Location: {booking.location ?? "to be confirmed"}</p>
+            {booking.categories.length > 0 && (
+              <p className="capitalize">
+                Type: {booking.categories.join(", ")}
+              </p>
+            )}
+          </>
+        ) : (
+          <p>Your session hasn't been scheduled yet.</p>
+        )}
+        <span className="h-6" />
+        {booking.can_change ? (
+          <>
+            {booking.change_deadline && (
+              <p>
+                You can reschedule or cancel online until{" "}
+                {local_time(booking.change_deadline)}.
+              </p>
+            )}
+            <span className="h-6" />
+            <h2>RESCHEDULE</h2>
+            {booking.available_slots.length > 0 ? (
+              <div className="flex gap-2 items-center">
+                <select
+                  className="border-2 outline-none focus:border-accent"
+                  value={slot_id}
+                  onChange={(e) => setSlotId(e.target.value)}
+                >
+                  <option value="">Pick a new time</option>
+                  {booking.available_slots.map((slot) => (
+                    <option key={slot.slot_id} value={slot.slot_id}>
+                      {local_time(slot.starts_at)}
+                    </option>
+                  ))}
+                </select>
+                <button
+                  className="border-2"
+                  disabled={slot_id === ""}
+                  onClick={() => reschedule()}
+                >
+                  RESCHEDULE
+                </button>
+              </div>
+            ) : (
+              <p>There are no other open times right now, please contact us.</p>
+            )}
+            <span className="h-6" />
+            <button className="border-2" onClick={() => setCancelling(true)}>
+              CANCEL SESSION
+            </button>
+            {cancelling && (
+              <div className="flex gap-2 items-center mt-2">
+                <input
+                  placeholder="Reason (optional)"
+                  className="border-2 outline-none focus:border-accent"
+                  value={cancel_reason}
+                  onChange={(e) => setCancelReason(e.target.value)}
+                />
+                <button className="border-2" onClick={() => cancel()}>
+                  CONFIRM CANCEL
+                </button>
+              </div>
+            )}
+          </>
+        ) : (
+          !booking.cancelled &&
+          booking.scheduled_at && (
+            <p>
+              It's too close to the session to change it online, please contact
+              us.
+            </p>
+          )
+        )}
+      </div>
+    </>
+  );
+}
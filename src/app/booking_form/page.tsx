@@ -97,8 +97,16 @@ export default function BookingPage() {
       })
         .then(async (res) => {
           const body = await res.json();
-
-          alert(body);
+          //validation failures come back as a list of field errors
+          alert(
+            typeof body === "string"
+              ? body
+              : body.errors
+                ? body.errors
+                    .map((e: { field: string; message: string }) => e.message)
+                    .join("\n")
+                : body.message,
+          );
           if (res.ok) {
             window.location.reload();
           }
@@ -287,6 +295,8 @@ export default function BookingPage() {
                 setValue("turnstile_token", "", { shouldValidate: true })
               }
               options={{
+                //must match the action the backend expects for bookings
+                action: "booking",
                 theme: "dark",
                 size: "normal",
               }}
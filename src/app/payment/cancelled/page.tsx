@@ -0,0 +1,20 @@
+import Link from "next/link";
+
+//where checkout sends the client when they back out before paying
+export default function PaymentCancelled() {
+  return (
+    <div className="flex flex-col pl-[3vw] sm:pl-[6vw]">
+      <h1 className="">PAYMENT CANCELLED</h1>
+      <div className="pl-8 flex flex-col justify-start items-start gap-2 ">
+        <p>No payment was taken.</p>
+        <p>
+          You can pay any time using the link in your invoice email, or{" "}
+          <Link href="/contact" className="underline!">
+            contact me
+          </Link>{" "}
+          to arrange another payment method.
+        </p>
+      </div>
+    </div>
+  );
+}
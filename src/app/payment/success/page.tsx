@@ -0,0 +1,25 @@
+import Link from "next/link";
+
+//where checkout sends the client after paying, the webhook records the payment itself
+export default async function PaymentSuccess({
+  searchParams,
+}: {
+  searchParams: Promise<{ session_id?: string }>;
+}) {
+  const { session_id } = await searchParams;
+  return (
+    <div className="flex flex-col pl-[3vw] sm:pl-[6vw]">
+      <h1 className="">THANK YOU</h1>
+      <div className="pl-8 flex flex-col justify-start items-start gap-2 ">
+        <p>Your payment was received.</p>
+        <p>It will show on your invoice once it has been processed.</p>
+        {session_id && (
+          <p className="text-sm">Payment reference: {session_id}</p>
+        )}
+        <Link href="/" className="underline! mt-4">
+          Back to home
+        </Link>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,196 @@
+"use client";
+import { API_URL } from "@/_utilities/API_UTILS";
+import { useQuery } from "@tanstack/react-query";
+import { useParams } from "next/navigation";
+
+import React, { useState } from "react";
+
+export type QuoteItem = {
+  quote_item_id: string;
+  description: string;
+  quantity: number;
+  unit_price: string;
+  taxable: boolean;
+  discount_kind: string | null;
+  discount_value: string | null;
+};
+export type Quote = {
+  quote_id: string;
+  quote_number: number;
+  expires_at: string;
+  amount_subtotal: string;
+  amount_tax: string;
+  amount_total: string | null;
+  notes: string | null;
+  //draft, sent, accepted, declined or expired
+  status: string;
+  currency: string;
+};
+
+//the page a client reaches from the link in their quote email
+export default function ViewQuote() {
+  const params = useParams<{ token: string }>();
+  const token = params.token;
+  const {
+    data: quote,
+    isLoading,
+    isError,
+    refetch,
+  } = useQuery({
+    queryKey: ["quote", token],
+    queryFn: async () => {
+      const response = await fetch(API_URL + `/quote/${token}`);
+
+      if (!response.ok) throw new Error("Network response was not ok");
+
+      return response.json();
+    },
+  });
+
+  const money = (amount: string | number | null) =>
+    new Intl.NumberFormat("en-US", {
+      style: "currency",
+      currency: quote?.quote.currency ?? "USD",
+    }).format(Number(amount ?? 0));
+
+  const [declining, setDeclining] = useState(false);
+  const [decline_reason, setDeclineReason] = useState("");
+
+  //accepting turns the quote into an invoice
+  async function accept_quote() {
+    await fetch(API_URL + `/quote/${token}/accept`, {
+      method: "POST",
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => refetch())
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
+  async function decline_quote() {
+    await fetch(API_URL + `/quote/${token}/decline`, {
+      headers: {
+        "Content-Type": "application/json",
+      },
+      method: "POST",
+      body: JSON.stringify({
+        reason: decline_reason || undefined,
+      }),
+    })
+      .then((res) => res.json())
+      .then((json) => alert(json.message))
+      .then(() => {
+        setDeclining(false);
+        refetch();
+      })
+      .catch((err) => alert("ERROR: " + err.message));
+  }
+
+  if (isLoading)
+    return (
+      <div className="flex justify-center mt-30 items-center">
+        <div className="animate-pulse flex space-x-4">
+          <div className="h-4">Loading...</div>
+        </div>
+      </div>
+    );
+  if (isError)
+    return (
+      <div className="flex justify-center items-center mt-30">
+        This link is invalid or has expired
+      </div>
+    );
+
+  const expires_at = new Date(quote?.quote.expires_at).toLocaleString(
+    "en-US",
+    {
+      year: "numeric",
+      month: "short",
+      day: "2-digit",
+    },
+  );
+  //only a sent quote that hasn't expired can be answered
+  const respondable = quote?.quote.status === "sent";
+  return (
+    <>
+      <div className="flex items-start pl-[3vw] md:pl-[10vw] flex-col">
+        <h1>QUOTE #{quote?.quote.quote_number}</h1>
+        <div className="flex gap-4">
+          <button
+            className="border-2"
+            onClick={() => {
+              window.open(API_URL + `/quote/${token}/pdf`);
+            }}
+          >
+            PRINT
+          </button>
+        </div>
+      </div>
+
+      <div className="flex justify-center mx-10 mb-80 p-6 border-2 items-center mt-30 flex-col ">
+        <h2>
+          Prepared for {quote?.client.first_name} {quote?.client.last_name}
+        </h2>
+        <span className="h-6" />
+        <table className="table-auto border-separate border-spacing-4 border-2 ">
+          <thead>
+            <tr>
+              <th>Item</th>
+              <th>Quantity</th>
+              <th>Unit Price</th>
+              <th>Discount</th>
+            </tr>
+          </thead>
+          <tbody className="">
+            {quote?.quote_items?.map((item: QuoteItem) => (
+              <tr className="text-center" key={item.quote_item_id}>
+                <td>{item.description}</td>
+                <td>{item.quantity}</td>
+                <td>{money(item.unit_price)}</td>
+                <td>
+                  {item.discount_kind === "percent"
+                    ? `${Number(item.discount_value)}%`
+                    : item.discount_kind === "fixed"
+                      ? money(item.discount_value)
+                      : ""}
+                </td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+        <h2 className="">Subtotal: {money(quote?.quote.amount_subtotal)}</h2>
+        <h2 className="">Tax: {money(quote?.quote.amount_tax)}</h2>
+        <h2 className="">Total: {money(quote?.quote.amount_total)}</h2>
+        {quote?.quote.notes && <p className="mt-4">{quote?.quote.notes}</p>}
+        <span className="h-6" />
+        <h2>Valid until: {expires_at}</h2>
+        <h2 className="capitalize">Status: {quote?.quote.status}</h2>
+        {respondable && (
+          <div className="flex flex-col gap-2 items-center mt-6">
+            <div className="flex gap-4">
+              <button className="border-2" onClick={() => accept_quote()}>
+                ACCEPT
+              </button>
+              <button className="border-2" onClick={() => setDeclining(true)}>
+                DECLINE
+              </button>
+            </div>
+            {declining && (
+              <div className="flex gap-2 items-center">
+                <input
+                  placeholder="Reason (optional)"
+                  className="border-2 outline-none focus:border-accent"
+                  value={decline_reason}
+                  onChange={(e) => setDeclineReason(e.target.value)}
+                />
+                <button className="border-2" onClick={() => decline_quote()}>
+                  CONFIRM DECLINE
+                </button>
+              </div>
+            )}
+          </div>
+        )}
+      </div>
+    </>
+  );
+}